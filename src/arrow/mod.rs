@@ -0,0 +1,185 @@
+//! Arrow IPC output, available with the `arrow` feature.
+
+use std::{io::Write, sync::Arc};
+
+use arrow_array::{ArrayRef, BooleanArray, Decimal128Array, RecordBatch, StringArray, UInt16Array};
+use arrow_ipc::writer::{FileWriter, StreamWriter};
+use arrow_schema::{DataType, Field, Schema};
+use rust_decimal::Decimal;
+
+use crate::{
+    domain::{account::Account, PRECISION},
+    error::Result,
+};
+
+/// Digits of the decimal columns, enough for any balance.
+const DECIMAL_PRECISION: u8 = 38;
+
+/// Getter of an optional balance.
+type Balance = fn(&Account) -> Option<Decimal>;
+
+/// Write accounts as an Arrow IPC stream, as read by
+/// `pyarrow.ipc.open_stream`.
+///
+/// Balances are `decimal128` columns with [`PRECISION`] decimal places.
+/// Optional columns are only there if some account has them, as in CSV
+/// output.
+pub fn write_stream<'a>(
+    accounts: impl IntoIterator<Item = &'a Account>,
+    writer: impl Write,
+) -> Result<()> {
+    let batch = batch(accounts)?;
+    let mut writer = StreamWriter::try_new(writer, &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Like [`write_stream`], as an Arrow IPC file, also known as Feather, as
+/// read by `pyarrow.feather.read_table`.
+pub fn write_file<'a>(
+    accounts: impl IntoIterator<Item = &'a Account>,
+    writer: impl Write,
+) -> Result<()> {
+    let batch = batch(accounts)?;
+    let mut writer = FileWriter::try_new(writer, &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+fn batch<'a>(accounts: impl IntoIterator<Item = &'a Account>) -> Result<RecordBatch> {
+    let accounts: Vec<_> = accounts.into_iter().collect();
+    let decimal = DataType::Decimal128(DECIMAL_PRECISION, PRECISION as i8);
+
+    let decimals = |value: Balance| -> Result<ArrayRef> {
+        let array = accounts
+            .iter()
+            .map(|account| value(account).map(|value| value.mantissa()))
+            .collect::<Decimal128Array>()
+            .with_precision_and_scale(DECIMAL_PRECISION, PRECISION as i8)?;
+        Ok(Arc::new(array))
+    };
+
+    let mut fields = vec![
+        Field::new("client", DataType::UInt16, false),
+        Field::new("available", decimal.clone(), false),
+        Field::new("held", decimal.clone(), false),
+        Field::new("total", decimal.clone(), false),
+        Field::new("locked", DataType::Boolean, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(
+            accounts
+                .iter()
+                .map(|account| account.client())
+                .collect::<UInt16Array>(),
+        ),
+        decimals(|account| Some(account.available()))?,
+        decimals(|account| Some(account.held()))?,
+        decimals(|account| Some(account.total()))?,
+        Arc::new(
+            accounts
+                .iter()
+                .map(|account| Some(account.locked()))
+                .collect::<BooleanArray>(),
+        ),
+    ];
+
+    let optional: [(&str, Balance); 3] = [
+        ("overdraft", Account::overdraft),
+        ("adjusted", Account::adjusted),
+        ("fees", Account::fees),
+    ];
+    for (name, value) in optional {
+        if accounts.iter().any(|account| value(account).is_some()) {
+            fields.push(Field::new(name, decimal.clone(), true));
+            columns.push(decimals(value)?);
+        }
+    }
+
+    if accounts.iter().any(|account| account.currency().is_some()) {
+        let currencies: StringArray = accounts.iter().map(|account| account.currency()).collect();
+        fields.push(Field::new("currency", DataType::Utf8, true));
+        columns.push(Arc::new(currencies));
+    }
+
+    let optional: [(&str, Balance); 2] = [
+        ("base_total", Account::base_total),
+        ("interest", Account::interest),
+    ];
+    for (name, value) in optional {
+        if accounts.iter().any(|account| value(account).is_some()) {
+            fields.push(Field::new(name, decimal.clone(), true));
+            columns.push(decimals(value)?);
+        }
+    }
+
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, types::Decimal128Type};
+    use arrow_ipc::reader::{FileReader, StreamReader};
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    fn accounts() -> Vec<Account> {
+        let mut first = Account::new(1).with_fees();
+        first.deposit(1, dec!(1.5)).unwrap();
+        let mut second = Account::new(2).with_fees();
+        second.deposit(2, dec!(2)).unwrap();
+        second.hold(2, dec!(0.25)).unwrap();
+
+        vec![first, second]
+    }
+
+    fn check(batch: &RecordBatch) {
+        let schema = batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|field| field.name()).collect();
+        assert_eq!(
+            names,
+            ["client", "available", "held", "total", "locked", "fees"]
+        );
+
+        let available = batch.column(1).as_primitive::<Decimal128Type>();
+        assert_eq!(available.value_as_string(0), "1.5000");
+        assert_eq!(available.value_as_string(1), "1.7500");
+        let held = batch.column(2).as_primitive::<Decimal128Type>();
+        assert_eq!(held.value_as_string(1), "0.2500");
+    }
+
+    #[test]
+    fn stream() {
+        let mut output = Vec::new();
+        write_stream(&accounts(), &mut output).unwrap();
+
+        let batches: Vec<_> = StreamReader::try_new(output.as_slice(), None)
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(batches.len(), 1);
+        check(&batches[0]);
+    }
+
+    #[test]
+    fn file() {
+        let mut output = Vec::new();
+        write_file(&accounts(), &mut output).unwrap();
+
+        let batches: Vec<_> = FileReader::try_new(std::io::Cursor::new(output), None)
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+
+        check(&batches[0]);
+    }
+}
@@ -0,0 +1,203 @@
+//! Avro object container files, available with the `avro` feature.
+
+use std::{
+    io::{Read, Write},
+    sync::LazyLock,
+};
+
+use apache_avro::{from_value, types::Value, Reader, Schema, Writer};
+use rust_decimal::Decimal;
+
+use crate::{
+    domain::{account::Account, transaction::Transaction},
+    error::Result,
+};
+
+/// Schema of transactions, with the same fields as CSV rows.
+///
+/// Amounts are decimal strings, and timestamps seconds since the Unix epoch.
+pub const TRANSACTION_SCHEMA: &str = r#"{
+    "type": "record",
+    "name": "Transaction",
+    "namespace": "txns",
+    "fields": [
+        {"name": "type", "type": "string"},
+        {"name": "client", "type": "int"},
+        {"name": "tx", "type": "long"},
+        {"name": "amount", "type": ["null", "string"], "default": null},
+        {"name": "to_client", "type": ["null", "int"], "default": null},
+        {"name": "timestamp", "type": ["null", "long"], "default": null},
+        {"name": "currency", "type": ["null", "string"], "default": null}
+    ]
+}"#;
+
+/// Schema of accounts, with the same fields as CSV output.
+///
+/// Balances are decimal strings, and optional columns are null when not
+/// enabled.
+pub const ACCOUNT_SCHEMA: &str = r#"{
+    "type": "record",
+    "name": "Account",
+    "namespace": "txns",
+    "fields": [
+        {"name": "client", "type": "int"},
+        {"name": "available", "type": "string"},
+        {"name": "held", "type": "string"},
+        {"name": "total", "type": "string"},
+        {"name": "locked", "type": "boolean"},
+        {"name": "overdraft", "type": ["null", "string"], "default": null},
+        {"name": "adjusted", "type": ["null", "string"], "default": null},
+        {"name": "fees", "type": ["null", "string"], "default": null},
+        {"name": "currency", "type": ["null", "string"], "default": null},
+        {"name": "base_total", "type": ["null", "string"], "default": null},
+        {"name": "interest", "type": ["null", "string"], "default": null}
+    ]
+}"#;
+
+static TRANSACTION: LazyLock<Schema> =
+    LazyLock::new(|| Schema::parse_str(TRANSACTION_SCHEMA).expect("valid transaction schema"));
+static ACCOUNT: LazyLock<Schema> =
+    LazyLock::new(|| Schema::parse_str(ACCOUNT_SCHEMA).expect("valid account schema"));
+
+/// Parse [`Transaction`]s from an Avro object container file.
+///
+/// Records are resolved against [`TRANSACTION_SCHEMA`], so files written
+/// with a compatible schema, e.g. without the optional fields, are read as
+/// well. Fails right away if the file header can't be read, and otherwise
+/// yields an error for each record that is not a valid transaction.
+pub fn read(reader: impl Read) -> Result<impl Iterator<Item = Result<Transaction>>> {
+    let reader = Reader::builder(reader)
+        .reader_schema(&TRANSACTION)
+        .build()?;
+
+    Ok(reader.map(|value| Ok(from_value(&value?)?)))
+}
+
+/// Write accounts as an Avro object container file following
+/// [`ACCOUNT_SCHEMA`].
+pub fn write<'a>(
+    accounts: impl IntoIterator<Item = &'a Account>,
+    writer: impl Write,
+) -> Result<()> {
+    let mut writer = Writer::new(&ACCOUNT, writer)?;
+
+    for account in accounts {
+        writer.append_value(record(account))?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn record(account: &Account) -> Value {
+    let decimal = |value: Decimal| Value::String(value.to_string());
+    let optional = |value: Option<String>| match value {
+        Some(value) => Value::Union(1, Box::new(Value::String(value))),
+        None => Value::Union(0, Box::new(Value::Null)),
+    };
+    let optional_decimal = |value: Option<Decimal>| optional(value.map(|value| value.to_string()));
+
+    Value::Record(vec![
+        ("client".into(), Value::Int(account.client().into())),
+        ("available".into(), decimal(account.available())),
+        ("held".into(), decimal(account.held())),
+        ("total".into(), decimal(account.total())),
+        ("locked".into(), Value::Boolean(account.locked())),
+        ("overdraft".into(), optional_decimal(account.overdraft())),
+        ("adjusted".into(), optional_decimal(account.adjusted())),
+        ("fees".into(), optional_decimal(account.fees())),
+        (
+            "currency".into(),
+            optional(account.currency().map(Into::into)),
+        ),
+        ("base_total".into(), optional_decimal(account.base_total())),
+        ("interest".into(), optional_decimal(account.interest())),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{domain::transaction::TransactionKind, error::Error};
+
+    fn transaction(kind: &str, tx: i64, amount: Option<&str>) -> Value {
+        let amount = match amount {
+            Some(amount) => Value::Union(1, Box::new(Value::String(amount.into()))),
+            None => Value::Union(0, Box::new(Value::Null)),
+        };
+        let null = || Value::Union(0, Box::new(Value::Null));
+
+        Value::Record(vec![
+            ("type".into(), Value::String(kind.into())),
+            ("client".into(), Value::Int(1)),
+            ("tx".into(), Value::Long(tx)),
+            ("amount".into(), amount),
+            ("to_client".into(), null()),
+            (
+                "timestamp".into(),
+                Value::Union(1, Box::new(Value::Long(1_700_000_000))),
+            ),
+            ("currency".into(), null()),
+        ])
+    }
+
+    #[test]
+    fn read_transactions() {
+        let mut writer = Writer::new(&TRANSACTION, Vec::new()).unwrap();
+        writer
+            .append_value(transaction("deposit", 1, Some("1.5")))
+            .unwrap();
+        writer
+            .append_value(transaction("dispute", 1, None))
+            .unwrap();
+        writer
+            .append_value(transaction("withdrawal", 2, None))
+            .unwrap();
+        let file = writer.into_inner().unwrap();
+
+        let txns: Vec<_> = read(file.as_slice()).unwrap().collect();
+
+        let deposit = txns[0].as_ref().unwrap();
+        assert_eq!(deposit.kind, TransactionKind::Deposit { amount: dec!(1.5) });
+        assert_eq!(deposit.timestamp.unwrap().timestamp(), 1_700_000_000);
+        assert_eq!(
+            txns[1].as_ref().unwrap().kind,
+            TransactionKind::Dispute { amount: None }
+        );
+        assert!(matches!(txns[2], Err(Error::AvroError(_))));
+    }
+
+    #[test]
+    fn write_accounts() {
+        let mut account = Account::new(1).with_fees();
+        account.deposit(1, dec!(1.5)).unwrap();
+
+        let mut file = Vec::new();
+        write([&account], &mut file).unwrap();
+
+        let records: Vec<_> = Reader::new(file.as_slice())
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        let Value::Record(fields) = &records[0] else {
+            panic!("expected a record, got {:?}", records[0]);
+        };
+        assert_eq!(
+            fields[1],
+            ("available".into(), Value::String("1.5000".into()))
+        );
+        assert_eq!(
+            fields[7],
+            (
+                "fees".into(),
+                Value::Union(1, Box::new(Value::String("0.0000".into())))
+            )
+        );
+        assert_eq!(
+            fields[8],
+            ("currency".into(), Value::Union(0, Box::new(Value::Null)))
+        );
+    }
+}
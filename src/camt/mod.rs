@@ -0,0 +1,228 @@
+//! Import of ISO 20022 camt.053 account statements, available with the
+//! `camt` feature.
+
+use std::{
+    io::{BufReader, Read},
+    str::FromStr,
+};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use quick_xml::{events::Event, Reader};
+use rust_decimal::Decimal;
+
+use crate::{
+    domain::{error::Error as DomainError, transaction::Transaction},
+    error::Result,
+};
+
+/// Parse the entries of a camt.053 statement as deposits, for credits, and
+/// withdrawals, for debits, all of them for `client`.
+///
+/// Like with [OFX](crate::ofx::read), entries are numbered in order, starting
+/// at `first_transaction_id`. Their timestamp is their booking date and their
+/// currency the one of their amount.
+///
+/// Fails right away if the XML is malformed. Otherwise, each item is an error
+/// if the entry has an invalid amount or date.
+pub fn read(
+    reader: impl Read,
+    client: u16,
+    first_transaction_id: u64,
+) -> Result<impl Iterator<Item = Result<Transaction>>> {
+    let entries = parse(reader)?;
+
+    Ok((first_transaction_id..)
+        .zip(entries)
+        .map(move |(transaction_id, entry)| {
+            let amount = entry
+                .amount
+                .ok_or(DomainError::MissingAmount { transaction_id })?;
+            let amount = Decimal::from_str(&amount).map_err(|_| DomainError::InvalidAmount {
+                transaction_id,
+                amount: amount.clone(),
+            })?;
+            let amount = match entry.credit_debit.as_deref() {
+                Some("DBIT") => -amount,
+                _ => amount,
+            };
+
+            let mut tx = Transaction::signed(transaction_id, client, amount)?;
+            tx.timestamp = entry
+                .booked
+                .map(|date| {
+                    parse_date(&date).ok_or(DomainError::InvalidTimestamp {
+                        transaction_id,
+                        timestamp: date,
+                    })
+                })
+                .transpose()?;
+            tx.currency = entry.currency;
+
+            Ok(tx)
+        }))
+}
+
+/// Fields of an `Ntry` element.
+#[derive(Debug, Default)]
+struct Entry {
+    amount: Option<String>,
+    currency: Option<String>,
+    /// `CRDT` or `DBIT`.
+    credit_debit: Option<String>,
+    /// Booking date, or date and time.
+    booked: Option<String>,
+}
+
+/// Collect the entries of every statement in the document.
+///
+/// Only elements right below `Ntry` are read, leaving out the amounts of
+/// the transaction details some banks nest in it.
+fn parse(reader: impl Read) -> Result<Vec<Entry>> {
+    let mut reader = Reader::from_reader(BufReader::new(reader));
+    let mut buffer = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut entries = Vec::new();
+    let mut entry: Option<Entry> = None;
+
+    loop {
+        match reader.read_event_into(&mut buffer)? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                if name == "Ntry" {
+                    entry = Some(Entry::default());
+                } else if let (Some(entry), "Amt", Some("Ntry")) =
+                    (&mut entry, name.as_str(), path.last().map(String::as_str))
+                {
+                    if let Some(currency) = element
+                        .try_get_attribute("Ccy")
+                        .map_err(quick_xml::Error::from)?
+                    {
+                        entry.currency = Some(currency.unescape_value()?.into_owned());
+                    }
+                }
+                path.push(name);
+            }
+            Event::Text(text) => {
+                let Some(entry) = &mut entry else {
+                    continue;
+                };
+                let value = Some(text.unescape()?.trim().to_owned());
+                match path
+                    .iter()
+                    .rev()
+                    .take(3)
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()[..]
+                {
+                    ["Amt", "Ntry", ..] => entry.amount = value,
+                    ["CdtDbtInd", "Ntry", ..] => entry.credit_debit = value,
+                    ["Dt" | "DtTm", "BookgDt", "Ntry"] => entry.booked = value,
+                    _ => {}
+                }
+            }
+            Event::End(_) => {
+                let name = path.pop();
+                if name.as_deref() == Some("Ntry") {
+                    entries.extend(entry.take());
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buffer.clear();
+    }
+
+    Ok(entries)
+}
+
+/// Parse an ISO date, like `2024-01-31`, or date and time, with or without
+/// an offset. Those without one are taken to be in UTC.
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.to_utc());
+    }
+    if let Ok(datetime) = NaiveDateTime::from_str(value) {
+        return Some(datetime.and_utc());
+    }
+
+    Some(
+        NaiveDate::from_str(value)
+            .ok()?
+            .and_hms_opt(0, 0, 0)?
+            .and_utc(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{domain::transaction::TransactionKind, error::Error};
+
+    #[test]
+    fn read_statement() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+    <Stmt>
+      <Ntry>
+        <Amt Ccy="EUR">1500.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <BookgDt><Dt>2024-01-31</Dt></BookgDt>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">12.50</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <BookgDt><DtTm>2024-02-01T10:30:00+01:00</DtTm></BookgDt>
+        <NtryDtls><TxDtls><Amt Ccy="USD">13.00</Amt></TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">a lot</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>
+"#;
+
+        let txns: Vec<_> = read(input.as_bytes(), 7, 100).unwrap().collect();
+
+        assert_eq!(txns.len(), 3);
+        let deposit = txns[0].as_ref().unwrap();
+        assert_eq!(deposit.transaction_id, 100);
+        assert_eq!(deposit.client, 7);
+        assert_eq!(
+            deposit.kind,
+            TransactionKind::Deposit { amount: dec!(1500) }
+        );
+        assert_eq!(deposit.currency.as_deref(), Some("EUR"));
+        let withdrawal = txns[1].as_ref().unwrap();
+        assert_eq!(
+            withdrawal.kind,
+            TransactionKind::Withdrawal { amount: dec!(12.5) }
+        );
+        assert_eq!(withdrawal.currency.as_deref(), Some("EUR"));
+        assert_eq!(
+            withdrawal.timestamp.unwrap().to_rfc3339(),
+            "2024-02-01T09:30:00+00:00"
+        );
+        assert!(matches!(
+            txns[2],
+            Err(Error::BusinessError(DomainError::InvalidAmount {
+                transaction_id: 102,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn malformed_xml() {
+        let input = "<Document><Stmt><Ntry></Stmt></Document>";
+
+        assert!(matches!(
+            read(input.as_bytes(), 1, 1),
+            Err(Error::XmlError(_))
+        ));
+    }
+}
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use clap::Args;
+use rust_decimal::Decimal;
+use txns::{csv, engine::accrual::Accrual, error::Result};
+
+use super::{output::Output, EngineArgs, InputArgs, SortArgs};
+
+#[derive(Debug, Args)]
+pub struct AccrueArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    sort: SortArgs,
+    /// Yearly interest rate, like `0.05` for 5%.
+    #[arg(long)]
+    rate: Decimal,
+    /// Last day to accrue interest for, like `2024-12-31`.
+    #[arg(long)]
+    as_of: NaiveDate,
+    /// First day to accrue interest for. Defaults to the day of the first
+    /// transaction.
+    #[arg(long)]
+    since: Option<NaiveDate>,
+    /// File to write accounts to, defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Apply timestamped transactions on top of `--initial-state`, accruing
+/// interest on the balance each account ends every day with, and write the
+/// accounts with their interest credited.
+///
+/// Transactions after `--as-of` are left out.
+pub fn run(args: AccrueArgs) -> Result<()> {
+    let mut engine = args.engine.engine()?;
+    let mut accrual = Accrual::new(args.rate, args.since);
+    args.engine
+        .apply_with(args.input.read()?, args.input.mode, drop, |tx| {
+            let day = Accrual::day(&tx)?;
+            if day > args.as_of {
+                return Ok(());
+            }
+
+            accrual.accrue_until(&engine, day)?;
+            Ok(engine.process(tx)?)
+        })?;
+    accrual.finish(&mut engine, args.as_of)?;
+
+    let mut output = Output::open(args.output.as_deref())?;
+    csv::write(args.sort.sort(engine.accounts()), &mut output)?;
+    output.finish()
+}
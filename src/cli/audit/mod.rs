@@ -0,0 +1,108 @@
+use std::path::Path;
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use txns::{
+    domain::{error::Result as DomainResult, transaction::Transaction, PRECISION},
+    engine::{events::Event, Outcome, TransactionEngine},
+    error::Result,
+};
+
+use super::output::Output;
+
+/// CSV trail of every transaction the engine was given, with the balances of
+/// its client before and after it and what was decided about it.
+pub struct Audit {
+    writer: ::csv::Writer<Output>,
+}
+
+#[derive(Serialize)]
+struct Row {
+    tx: u64,
+    client: u16,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    decision: Decision,
+    available_before: Decimal,
+    held_before: Decimal,
+    total_before: Decimal,
+    available_after: Decimal,
+    held_after: Decimal,
+    total_after: Decimal,
+}
+
+/// What was made of a transaction.
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Applied,
+    Ignored,
+    Rejected,
+}
+
+impl Decision {
+    pub fn of(result: &DomainResult<Outcome>) -> Self {
+        match result {
+            Ok(Outcome::Applied) => Self::Applied,
+            Ok(Outcome::Ignored(_)) => Self::Ignored,
+            Err(_) => Self::Rejected,
+        }
+    }
+}
+
+impl Audit {
+    /// Start writing the trail to `path`, which is only replaced once
+    /// [`finish`](Self::finish)ed.
+    pub fn open(path: &Path, buffer: usize) -> Result<Self> {
+        Ok(Self {
+            writer: ::csv::Writer::from_writer(Output::with_buffer(Some(path), buffer)?),
+        })
+    }
+
+    /// Apply `tx` to `engine`, recording it, and passing the events it
+    /// causes to `emit`.
+    pub fn apply(
+        &mut self,
+        engine: &mut TransactionEngine,
+        tx: Transaction,
+        emit: impl FnMut(Event),
+    ) -> Result<Outcome> {
+        let (transaction_id, client, kind) = (tx.transaction_id, tx.client, tx.kind.name());
+        let [available_before, held_before, total_before] = balances(engine, client);
+
+        let result = engine.apply_with_events(tx, emit);
+        let [available_after, held_after, total_after] = balances(engine, client);
+
+        self.writer.serialize(Row {
+            tx: transaction_id,
+            client,
+            kind,
+            decision: Decision::of(&result),
+            available_before,
+            held_before,
+            total_before,
+            available_after,
+            held_after,
+            total_after,
+        })?;
+
+        Ok(result?)
+    }
+
+    /// Flush every row and move the file into place.
+    pub fn finish(self) -> Result<()> {
+        self.writer
+            .into_inner()
+            .map_err(|error| error.into_error())?
+            .finish()
+    }
+}
+
+/// Available, held and total funds of `client`, all zero before its first
+/// transaction.
+pub fn balances(engine: &TransactionEngine, client: u16) -> [Decimal; 3] {
+    match engine.account(client) {
+        Some(account) => [account.available(), account.held(), account.total()],
+        None => [Decimal::new(0, PRECISION); 3],
+    }
+}
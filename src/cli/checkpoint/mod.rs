@@ -0,0 +1,142 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use txns::{
+    engine::TransactionEngine,
+    error::{Error, Result},
+};
+
+use super::output::Output;
+
+/// Periodic checkpoints of an engine, saved to the same file as how many
+/// transactions were read so far, in 8 little-endian bytes, followed by a
+/// snapshot of the engine.
+pub struct Checkpoints {
+    path: PathBuf,
+    every: u64,
+    /// Position at which to save the next checkpoint.
+    next: u64,
+}
+
+impl Checkpoints {
+    /// Save a checkpoint every `every` transactions after `position`.
+    pub fn new(path: &Path, every: u64, position: u64) -> Self {
+        let every = every.max(1);
+        Self {
+            path: path.into(),
+            every,
+            next: position + every,
+        }
+    }
+
+    /// Save the state of `engine` if `every` transactions were read since
+    /// the last checkpoint, telling whether it was.
+    pub fn reached(&mut self, engine: &TransactionEngine, position: u64) -> Result<bool> {
+        if position < self.next {
+            return Ok(false);
+        }
+        self.save(engine, position)?;
+
+        Ok(true)
+    }
+
+    /// Save the state of `engine` now. The file is replaced atomically, so a
+    /// crash while saving leaves the previous checkpoint.
+    pub fn save(&mut self, engine: &TransactionEngine, position: u64) -> Result<()> {
+        self.write(engine, position, None)
+    }
+
+    /// Like [`save`](Self::save), along with `note`, read back by
+    /// [`resume_noted`], like what was done with the input at `position`.
+    /// The note takes 4 little-endian bytes for its length, then the bytes
+    /// of the note, between the position and the snapshot.
+    #[cfg(feature = "watch")]
+    pub fn save_noted(
+        &mut self,
+        engine: &TransactionEngine,
+        position: u64,
+        note: &str,
+    ) -> Result<()> {
+        self.write(engine, position, Some(note))
+    }
+
+    fn write(
+        &mut self,
+        engine: &TransactionEngine,
+        position: u64,
+        note: Option<&str>,
+    ) -> Result<()> {
+        let mut output = Output::open(Some(&self.path))?;
+        output.write_all(&position.to_le_bytes())?;
+        if let Some(note) = note {
+            output.write_all(&(note.len() as u32).to_le_bytes())?;
+            output.write_all(note.as_bytes())?;
+        }
+        output.write_all(&engine.snapshot()?)?;
+        output.finish()?;
+        self.next = position + self.every;
+
+        Ok(())
+    }
+}
+
+/// Restore `engine` from the checkpoint at `path`, returning how many
+/// transactions were read before it.
+pub fn resume(engine: &mut TransactionEngine, path: &Path) -> Result<u64> {
+    let checkpoint = fs::read(path)?;
+    let (position, snapshot) = split_position(&checkpoint)?;
+    engine.restore(snapshot)?;
+
+    Ok(position)
+}
+
+/// Like [`resume`], for checkpoints saved with
+/// [`save_noted`](Checkpoints::save_noted), returning their note as well.
+#[cfg(feature = "watch")]
+pub fn resume_noted(engine: &mut TransactionEngine, path: &Path) -> Result<(u64, String)> {
+    let checkpoint = fs::read(path)?;
+    let (position, rest) = split_position(&checkpoint)?;
+    let note = rest.split_first_chunk().and_then(|(len, rest)| {
+        let len = u32::from_le_bytes(*len) as usize;
+        let note = String::from_utf8(rest.get(..len)?.to_vec()).ok()?;
+        Some((note, &rest[len..]))
+    });
+    let Some((note, snapshot)) = note else {
+        return Err(truncated());
+    };
+    engine.restore(snapshot)?;
+
+    Ok((position, note))
+}
+
+/// Position a checkpoint starts with, and the rest of it.
+fn split_position(checkpoint: &[u8]) -> Result<(u64, &[u8])> {
+    match checkpoint.split_first_chunk() {
+        Some((position, rest)) => Ok((u64::from_le_bytes(*position), rest)),
+        None => Err(truncated()),
+    }
+}
+
+fn truncated() -> Error {
+    io::Error::new(io::ErrorKind::InvalidData, "truncated checkpoint").into()
+}
+
+/// Parse a count, like `1000`, optionally with a `k`, `M` or `G` suffix,
+/// like `500k`.
+pub fn parse_count(value: &str) -> std::result::Result<u64, String> {
+    let (digits, unit) = match value.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&value[..index], 1_000),
+        Some((index, 'm' | 'M')) => (&value[..index], 1_000_000),
+        Some((index, 'g' | 'G')) => (&value[..index], 1_000_000_000),
+        _ => (value, 1),
+    };
+
+    digits
+        .parse::<u64>()
+        .map_err(|error| error.to_string())?
+        .checked_mul(unit)
+        .ok_or_else(|| format!("{value} is too big"))
+}
@@ -0,0 +1,281 @@
+use std::{collections::HashMap, io::Write, iter};
+
+use clap::ValueEnum;
+use itertools::Itertools;
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use serde_json::Value;
+use txns::{
+    domain::account::Account,
+    engine::{Metrics, TransactionEngine},
+    error::{Error, Result},
+    json::Record,
+};
+
+/// Column of the accounts output, as chosen by `--columns`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum Column {
+    Client,
+    Available,
+    Held,
+    Total,
+    Locked,
+    Overdraft,
+    Adjusted,
+    Fees,
+    Currency,
+    BaseTotal,
+    Interest,
+    /// How many deposits of the client were applied.
+    Deposits,
+    /// How many withdrawals of the client were applied.
+    Withdrawals,
+    /// How many transactions of the client are under dispute.
+    Disputes,
+    /// How many transactions of the client were charged back.
+    Chargebacks,
+}
+
+/// Accounts laid out in chosen columns.
+pub struct Table {
+    columns: Vec<Column>,
+    /// Counts of the transactions of each client, only kept if needed.
+    metrics: HashMap<u16, Metrics>,
+}
+
+impl Table {
+    pub fn new(columns: Vec<Column>, engine: &TransactionEngine) -> Result<Self> {
+        let metrics = match columns.iter().any(|column| column.is_metric()) {
+            true => engine.metrics()?,
+            false => HashMap::new(),
+        };
+
+        Ok(Self { columns, metrics })
+    }
+
+    /// Value of each column for `account`, decimals being strings with four
+    /// decimal places as in the rest of the output.
+    fn row(&self, account: &Account) -> Row {
+        let decimal = |value: Option<rust_decimal::Decimal>| match value {
+            Some(value) => Value::String(value.to_string()),
+            None => Value::Null,
+        };
+
+        let metrics = self
+            .metrics
+            .get(&account.client())
+            .copied()
+            .unwrap_or_default();
+
+        self.columns
+            .iter()
+            .map(|column| {
+                let value = match column {
+                    Column::Client => account.client().into(),
+                    Column::Available => decimal(Some(account.available())),
+                    Column::Held => decimal(Some(account.held())),
+                    Column::Total => decimal(Some(account.total())),
+                    Column::Locked => account.locked().into(),
+                    Column::Overdraft => decimal(account.overdraft()),
+                    Column::Adjusted => decimal(account.adjusted()),
+                    Column::Fees => decimal(account.fees()),
+                    Column::Currency => account.currency().into(),
+                    Column::BaseTotal => decimal(account.base_total()),
+                    Column::Interest => decimal(account.interest()),
+                    Column::Deposits => metrics.deposits.into(),
+                    Column::Withdrawals => metrics.withdrawals.into(),
+                    Column::Disputes => metrics.open_disputes.into(),
+                    Column::Chargebacks => metrics.chargebacks.into(),
+                };
+                (column.name(), value)
+            })
+            .collect()
+    }
+
+    /// Text of each column for `account`, missing values being empty.
+    fn cells(&self, account: &Account) -> Vec<String> {
+        self.row(account)
+            .into_iter()
+            .map(|(_, value)| match value {
+                Value::String(value) => value,
+                Value::Null => String::new(),
+                value => value.to_string(),
+            })
+            .collect()
+    }
+
+    /// Write accounts as CSV rows, with a header.
+    pub fn write_csv<'b>(
+        &self,
+        accounts: impl IntoIterator<Item = &'b Account>,
+        writer: impl Write,
+    ) -> Result<()> {
+        let mut writer = ::csv::Writer::from_writer(writer);
+        writer.write_record(self.columns.iter().map(|column| column.name()))?;
+
+        for account in accounts {
+            writer.write_record(self.cells(account))?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write accounts as a table meant to be read by people, with a header
+    /// and aligned columns, numbers being aligned to the right.
+    pub fn write_text<'b>(
+        &self,
+        accounts: impl IntoIterator<Item = &'b Account>,
+        mut writer: impl Write,
+    ) -> Result<()> {
+        let header = self.columns.iter().map(|column| column.name().to_owned());
+        let rows: Vec<_> = iter::once(header.collect())
+            .chain(accounts.into_iter().map(|account| self.cells(account)))
+            .collect();
+        let widths: Vec<_> = (0..self.columns.len())
+            .map(|index| {
+                rows.iter()
+                    .map(|row: &Vec<String>| row[index].chars().count())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        for (index, row) in rows.iter().enumerate() {
+            let line = row
+                .iter()
+                .zip(&self.columns)
+                .zip(&widths)
+                .map(|((cell, column), &width)| match column.is_numeric() {
+                    true => format!("{cell:>width$}"),
+                    false => format!("{cell:<width$}"),
+                })
+                .join("  ");
+            writeln!(writer, "{}", line.trim_end())?;
+
+            if index == 0 {
+                let rule = widths.iter().map(|&width| "-".repeat(width)).join("  ");
+                writeln!(writer, "{rule}")?;
+            }
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Objects with the chosen columns of each account, followed by `errors`
+    /// like [`Record::Error`].
+    pub fn objects<'b>(
+        &'b self,
+        accounts: impl IntoIterator<Item = &'b Account> + 'b,
+        errors: impl IntoIterator<Item = &'b Error> + 'b,
+    ) -> impl Iterator<Item = Object<'b>> + 'b {
+        accounts
+            .into_iter()
+            .map(|account| Object::Row(self.row(account)))
+            .chain(errors.into_iter().map(|error| Object::Error(error.into())))
+    }
+}
+
+/// Values of an account, serialized as an object with the columns in order.
+type Row = Vec<(&'static str, Value)>;
+
+/// Item of JSON output with chosen columns.
+pub enum Object<'a> {
+    Row(Row),
+    Error(Record<'a>),
+}
+
+impl Serialize for Object<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Object::Row(row) => {
+                let mut map = serializer.serialize_map(Some(row.len()))?;
+                for (column, value) in row {
+                    map.serialize_entry(column, value)?;
+                }
+                map.end()
+            }
+            Object::Error(record) => record.serialize(serializer),
+        }
+    }
+}
+
+impl Column {
+    /// Columns of the usual output of `accounts`: the optional ones are only
+    /// there if some account has them.
+    pub fn present<'a>(accounts: impl IntoIterator<Item = &'a Account>) -> Vec<Column> {
+        let mut columns = vec![
+            Column::Client,
+            Column::Available,
+            Column::Held,
+            Column::Total,
+            Column::Locked,
+        ];
+        let optional = [
+            Column::Overdraft,
+            Column::Adjusted,
+            Column::Fees,
+            Column::Currency,
+            Column::BaseTotal,
+            Column::Interest,
+        ];
+        let is_set = |column, account: &Account| match column {
+            Column::Overdraft => account.overdraft().is_some(),
+            Column::Adjusted => account.adjusted().is_some(),
+            Column::Fees => account.fees().is_some(),
+            Column::Currency => account.currency().is_some(),
+            Column::BaseTotal => account.base_total().is_some(),
+            Column::Interest => account.interest().is_some(),
+            _ => true,
+        };
+
+        let accounts: Vec<_> = accounts.into_iter().collect();
+        columns.extend(
+            optional
+                .into_iter()
+                .filter(|&column| accounts.iter().any(|account| is_set(column, account))),
+        );
+
+        columns
+    }
+
+    /// Counts of transactions appended by `--metrics`.
+    pub const METRICS: [Column; 4] = [
+        Column::Deposits,
+        Column::Withdrawals,
+        Column::Disputes,
+        Column::Chargebacks,
+    ];
+
+    /// Whether the column is one of the [`METRICS`](Self::METRICS).
+    fn is_metric(self) -> bool {
+        Self::METRICS.contains(&self)
+    }
+
+    /// Whether the values of the column are numbers.
+    fn is_numeric(self) -> bool {
+        !matches!(self, Column::Locked | Column::Currency)
+    }
+
+    /// Header of the column, as in the usual output.
+    fn name(self) -> &'static str {
+        match self {
+            Column::Client => "client",
+            Column::Available => "available",
+            Column::Held => "held",
+            Column::Total => "total",
+            Column::Locked => "locked",
+            Column::Overdraft => "overdraft",
+            Column::Adjusted => "adjusted",
+            Column::Fees => "fees",
+            Column::Currency => "currency",
+            Column::BaseTotal => "base_total",
+            Column::Interest => "interest",
+            Column::Deposits => "deposits",
+            Column::Withdrawals => "withdrawals",
+            Column::Disputes => "disputes",
+            Column::Chargebacks => "chargebacks",
+        }
+    }
+}
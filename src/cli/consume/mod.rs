@@ -0,0 +1,180 @@
+use std::{mem, path::PathBuf};
+
+use clap::{ArgGroup, Args};
+use tracing::info;
+#[cfg(feature = "amqp")]
+use txns::source::amqp::{self, Amqp};
+#[cfg(feature = "kafka")]
+use txns::source::kafka::{self, Kafka};
+#[cfg(feature = "nats")]
+use txns::source::nats::{self, Nats};
+#[cfg(feature = "redis")]
+use txns::source::redis::{self, Redis};
+use txns::{
+    csv,
+    engine::TransactionEngine,
+    error::{Error, Result},
+    source::Source,
+};
+
+use super::{
+    checkpoint::{self, parse_count, Checkpoints},
+    output::Output,
+    report_skip, EngineArgs,
+};
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("source").required(true)))]
+pub struct ConsumeArgs {
+    /// RabbitMQ server and queue to consume, like
+    /// `amqp://localhost:5672/%2f/transactions`, a JSON transaction per
+    /// message.
+    #[cfg(feature = "amqp")]
+    #[arg(long, env = "TXNS_AMQP", value_name = "URI/QUEUE", group = "source")]
+    amqp: Option<amqp::Address>,
+    /// Queue to publish the messages of `--amqp` that hold no transaction
+    /// to, declared unless it exists. Without it, they are rejected, so the
+    /// dead-letter exchange of the queue receives them if it has one.
+    #[cfg(feature = "amqp")]
+    #[arg(
+        long,
+        env = "TXNS_AMQP_DEAD_LETTER",
+        value_name = "QUEUE",
+        requires = "amqp"
+    )]
+    amqp_dead_letter: Option<String>,
+    /// Kafka brokers, topic and consumer group to consume, like
+    /// `localhost:9092/transactions/txns`, a JSON transaction per message.
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_name = "BROKERS/TOPIC/GROUP", group = "source")]
+    kafka: Option<kafka::Address>,
+    /// NATS servers, JetStream subject and durable consumer to consume, like
+    /// `localhost:4222/transactions.>/txns`, a JSON transaction per message.
+    #[cfg(feature = "nats")]
+    #[arg(long, value_name = "SERVERS/SUBJECT/DURABLE", group = "source")]
+    nats: Option<nats::Address>,
+    /// Redis server, stream, consumer group and consumer to read, like
+    /// `redis://localhost:6379/transactions/txns/txns-1`, a JSON transaction
+    /// in the `transaction` field of each entry.
+    #[cfg(feature = "redis")]
+    #[arg(long, value_name = "URL/STREAM/GROUP/CONSUMER", group = "source")]
+    redis: Option<redis::Address>,
+    /// File to save the state of the engine to every `--checkpoint-every`
+    /// transactions, and to start from if it exists. Messages are only
+    /// acknowledged once saved, so those consumed after the last checkpoint
+    /// are delivered again after a crash. Without it, each message is
+    /// acknowledged once applied.
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<PathBuf>,
+    /// How many transactions to consume between checkpoints, like `10k`.
+    #[arg(long, value_name = "N", default_value = "10k", value_parser = parse_count)]
+    checkpoint_every: u64,
+    /// File to write the accounts to once stopped, defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    engine: EngineArgs,
+}
+
+/// Apply the transactions consumed from a broker until stopped with Ctrl-C,
+/// then write the accounts as CSV, in client order.
+pub fn run(args: ConsumeArgs) -> Result<()> {
+    let mut engine = args.engine.engine()?;
+    let resumed = match &args.checkpoint {
+        Some(path) if path.exists() => checkpoint::resume(&mut engine, path)?,
+        _ => 0,
+    };
+    let checkpoints = args
+        .checkpoint
+        .as_deref()
+        .map(|path| Checkpoints::new(path, args.checkpoint_every, resumed));
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            #[cfg(feature = "amqp")]
+            if let Some(address) = &args.amqp {
+                info!(queue = address.queue, "consuming {}", address.queue);
+                let amqp = Amqp::connect(address, args.amqp_dead_letter.as_deref()).await?;
+                return consume(amqp, &mut engine, checkpoints, resumed).await;
+            }
+            #[cfg(feature = "kafka")]
+            if let Some(address) = &args.kafka {
+                info!(topic = address.topic, "consuming {}", address.topic);
+                let kafka = Kafka::connect(address)?;
+                return consume(kafka, &mut engine, checkpoints, resumed).await;
+            }
+            #[cfg(feature = "nats")]
+            if let Some(address) = &args.nats {
+                info!(subject = address.subject, "consuming {}", address.subject);
+                let nats = Nats::connect(address).await?;
+                return consume(nats, &mut engine, checkpoints, resumed).await;
+            }
+            #[cfg(feature = "redis")]
+            if let Some(address) = &args.redis {
+                info!(stream = address.stream, "consuming {}", address.stream);
+                let redis = Redis::connect(address).await?;
+                return consume(redis, &mut engine, checkpoints, resumed).await;
+            }
+
+            unreachable!("a source is required")
+        })?;
+
+    let mut accounts: Vec<_> = engine.accounts().collect();
+    accounts.sort_by_key(|account| account.client());
+    let mut output = Output::open(args.output.as_deref())?;
+    csv::write(accounts, &mut output)?;
+
+    output.finish()
+}
+
+/// Apply the transactions of `source` until Ctrl-C, acknowledging them once
+/// applied, or once saved to `checkpoints` if any. Messages that hold no
+/// transaction are rejected by the source.
+async fn consume(
+    mut source: impl Source,
+    engine: &mut TransactionEngine,
+    mut checkpoints: Option<Checkpoints>,
+    mut position: u64,
+) -> Result<()> {
+    let stop = tokio::signal::ctrl_c();
+    tokio::pin!(stop);
+    let mut pending = Vec::new();
+
+    loop {
+        let delivery = tokio::select! {
+            _ = &mut stop => break,
+            delivery = source.receive() => delivery?,
+        };
+        position += 1;
+        let receipt = match delivery.transaction() {
+            Ok(tx) => {
+                if let Err(error) = engine.apply(tx).map_err(Error::from) {
+                    report_skip(&error);
+                }
+                Some(delivery.receipt)
+            }
+            Err(error) => {
+                report_skip(&error);
+                source.reject(delivery).await?
+            }
+        };
+
+        pending.extend(receipt);
+        let saved = match &mut checkpoints {
+            Some(checkpoints) => checkpoints.reached(engine, position)?,
+            None => true,
+        };
+        if saved {
+            source.acknowledge(mem::take(&mut pending)).await?;
+        }
+    }
+
+    if let Some(checkpoints) = &mut checkpoints {
+        checkpoints.save(engine, position)?;
+        source.acknowledge(pending).await?;
+    }
+
+    Ok(())
+}
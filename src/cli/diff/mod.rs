@@ -0,0 +1,94 @@
+use std::{collections::BTreeMap, fs::File, path::PathBuf};
+
+use clap::Args;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use txns::{
+    csv,
+    domain::{account::Account, PRECISION},
+    error::Result,
+};
+
+use super::output::Output;
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// Accounts before, as written by `txns process`.
+    old: PathBuf,
+    /// Accounts after, as written by `txns process`.
+    new: PathBuf,
+    /// File to write the differences to, defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// How an account differs from one snapshot to the other.
+#[derive(Serialize)]
+struct Delta {
+    client: u16,
+    change: Change,
+    available: Decimal,
+    held: Decimal,
+    total: Decimal,
+    /// Locked in the new snapshot only.
+    newly_locked: bool,
+}
+
+#[derive(Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Write, in client order, the accounts that differ between two snapshots,
+/// with how much their balances moved by.
+pub fn run(args: DiffArgs) -> Result<()> {
+    let mut accounts: BTreeMap<u16, [Option<Account>; 2]> = BTreeMap::new();
+    for (index, path) in [&args.old, &args.new].into_iter().enumerate() {
+        for account in csv::read_accounts(File::open(path)?) {
+            let account = account?;
+            let client = account.client();
+            accounts.entry(client).or_default()[index] = Some(account);
+        }
+    }
+
+    let mut output = Output::open(args.output.as_deref())?;
+    let mut writer = ::csv::Writer::from_writer(&mut output);
+    for (client, [old, new]) in accounts {
+        let balances = |account: &Option<Account>| match account {
+            Some(account) => [account.available(), account.held(), account.total()],
+            None => [Decimal::new(0, PRECISION); 3],
+        };
+        let ([available, held, total], [old_available, old_held, old_total]) =
+            (balances(&new), balances(&old));
+
+        let change = match (&old, &new) {
+            (None, _) => Change::Added,
+            (_, None) => Change::Removed,
+            _ => Change::Changed,
+        };
+        let locked = |account: &Option<Account>| account.as_ref().is_some_and(Account::locked);
+        let newly_locked = locked(&new) && !locked(&old);
+        let delta = Delta {
+            client,
+            change,
+            available: available - old_available,
+            held: held - old_held,
+            total: total - old_total,
+            newly_locked,
+        };
+
+        let unchanged = delta.change == Change::Changed
+            && [delta.available, delta.held, delta.total] == [Decimal::ZERO; 3]
+            && locked(&old) == locked(&new);
+        if !unchanged {
+            writer.serialize(delta)?;
+        }
+    }
+
+    writer.flush()?;
+    drop(writer);
+    output.finish()
+}
@@ -0,0 +1,31 @@
+use std::{io::Write, path::Path};
+
+use txns::{engine::events::Event, error::Result};
+
+use super::output::Output;
+
+/// Side output of the events the engine emits, as newline-delimited JSON.
+pub struct Events {
+    output: Output,
+}
+
+impl Events {
+    /// Start writing to `path`, which is only replaced once
+    /// [`finish`](Self::finish)ed.
+    pub fn open(path: &Path, buffer: usize) -> Result<Self> {
+        Ok(Self {
+            output: Output::with_buffer(Some(path), buffer)?,
+        })
+    }
+
+    pub fn write(&mut self, event: &Event) -> Result<()> {
+        serde_json::to_writer(&mut self.output, event)?;
+        self.output.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Move the file into place.
+    pub fn finish(self) -> Result<()> {
+        self.output.finish()
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use txns::error::Result;
+
+use super::output::Output;
+
+#[derive(Debug, Args)]
+pub struct GenerateArgs {
+    /// Number of transactions to generate.
+    #[arg(short = 'n', long, default_value_t = 1000)]
+    count: u64,
+    /// Number of distinct clients.
+    #[arg(long, default_value_t = 10)]
+    clients: u16,
+    /// Seed for reproducible output.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// File to write transactions to, defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    client: u16,
+    tx: u64,
+    amount: Option<Decimal>,
+}
+
+/// What the generator knows about a client, so it only emits rows the engine
+/// will accept.
+#[derive(Debug, Default)]
+struct Client {
+    available: Decimal,
+    /// Deposits that can still be disputed.
+    deposits: Vec<(u64, Decimal)>,
+    /// Deposits under dispute.
+    disputed: Vec<(u64, Decimal)>,
+}
+
+pub fn run(args: GenerateArgs) -> Result<()> {
+    let mut rng = match args.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    };
+    let mut clients: Vec<Client> = (0..args.clients.max(1))
+        .map(|_| Client::default())
+        .collect();
+    let mut output = Output::open(args.output.as_deref())?;
+    let mut writer = ::csv::Writer::from_writer(&mut output);
+    let mut next_tx = 1;
+
+    for _ in 0..args.count {
+        let index = rng.random_range(0..clients.len());
+        let state = &mut clients[index];
+        // Client ids start at 1, and there are at most u16::MAX of them.
+        let client = index as u16 + 1;
+        let amount = Decimal::new(rng.random_range(1..=10_000_000), 4);
+
+        let row = match rng.random_range(0..100) {
+            0..10 if !state.deposits.is_empty() => {
+                let (tx, amount) = state
+                    .deposits
+                    .swap_remove(rng.random_range(0..state.deposits.len()));
+                state.available -= amount;
+                state.disputed.push((tx, amount));
+                Row::new("dispute", client, tx)
+            }
+            10..16 if !state.disputed.is_empty() => {
+                let (tx, amount) = state
+                    .disputed
+                    .swap_remove(rng.random_range(0..state.disputed.len()));
+                state.available += amount;
+                Row::new("resolve", client, tx)
+            }
+            16..17 if !state.disputed.is_empty() => {
+                let (tx, _) = state
+                    .disputed
+                    .swap_remove(rng.random_range(0..state.disputed.len()));
+                Row::new("chargeback", client, tx)
+            }
+            17..45 if state.available >= amount => {
+                state.available -= amount;
+                next_tx += 1;
+                Row::with_amount("withdrawal", client, next_tx - 1, amount)
+            }
+            _ => {
+                state.available += amount;
+                state.deposits.push((next_tx, amount));
+                next_tx += 1;
+                Row::with_amount("deposit", client, next_tx - 1, amount)
+            }
+        };
+
+        writer.serialize(row)?;
+    }
+
+    writer.flush()?;
+    drop(writer);
+    output.finish()
+}
+
+impl Row {
+    fn new(kind: &'static str, client: u16, tx: u64) -> Self {
+        Self {
+            kind,
+            client,
+            tx,
+            amount: None,
+        }
+    }
+
+    fn with_amount(kind: &'static str, client: u16, tx: u64, amount: Decimal) -> Self {
+        Self {
+            amount: Some(amount),
+            ..Self::new(kind, client, tx)
+        }
+    }
+}
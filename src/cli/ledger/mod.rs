@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use txns::{domain::transaction::TransactionKind, error::Result};
+
+use super::{
+    audit::{balances, Decision},
+    output::Output,
+    EngineArgs, InputArgs, Mode,
+};
+
+#[derive(Debug, Args)]
+pub struct LedgerArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+    /// Client whose ledger to print, the transactions of bank statements
+    /// belonging to it.
+    #[arg(long, default_value_t = 1, conflicts_with = "statement_client")]
+    client: u16,
+    /// File to write the ledger to, defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// A transaction touching the client, with its balances right after it.
+#[derive(Serialize)]
+struct Entry {
+    tx: u64,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    amount: Option<Decimal>,
+    /// Other client of a transfer.
+    counterparty: Option<u16>,
+    decision: Decision,
+    available: Decimal,
+    held: Decimal,
+    total: Decimal,
+    locked: bool,
+}
+
+/// Apply every transaction and write those of `--client`, in input order,
+/// each with the balances it left the account with.
+///
+/// Transactions of other clients are part of the ledger when they change the
+/// balances of `--client`, like transfers to it and their disputes. Invalid
+/// transactions are skipped rather than aborting the run, those of `--client`
+/// being written as rejected.
+pub fn run(mut args: LedgerArgs) -> Result<()> {
+    let client = args.client;
+    args.input.statement_client = client;
+    let mut engine = args.engine.engine()?;
+    let mut writer = ::csv::Writer::from_writer(Output::open(args.output.as_deref())?);
+
+    args.engine
+        .apply_with(args.input.read()?, Mode::Lenient, drop, |tx| {
+            let counterparty = match tx.kind {
+                TransactionKind::Transfer { to_client, .. } if tx.client == client => {
+                    Some(to_client)
+                }
+                TransactionKind::Transfer { to_client, .. } if to_client == client => {
+                    Some(tx.client)
+                }
+                _ => None,
+            };
+            let (transaction_id, kind, amount) =
+                (tx.transaction_id, tx.kind.name(), tx.kind.amount());
+            let concerned = tx.client == client || counterparty.is_some();
+
+            let before = balances(&engine, client);
+            let result = engine.apply(tx);
+            let [available, held, total] = balances(&engine, client);
+
+            if concerned || [available, held, total] != before {
+                writer.serialize(Entry {
+                    tx: transaction_id,
+                    kind,
+                    amount,
+                    counterparty,
+                    decision: Decision::of(&result),
+                    available,
+                    held,
+                    total,
+                    locked: engine
+                        .account(client)
+                        .is_some_and(|account| account.locked()),
+                })?;
+            }
+
+            Ok(result.map(drop)?)
+        })?;
+
+    writer
+        .into_inner()
+        .map_err(|error| error.into_error())?
+        .finish()
+}
@@ -0,0 +1,987 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    iter,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use encoding_rs::Encoding;
+use rust_decimal::Decimal;
+use telemetry::Traced;
+use tracing::{debug, info, info_span, trace, warn};
+use txns::{
+    compression,
+    csv::{self, Dialect},
+    domain::{
+        account::Account,
+        error::Error as DomainError,
+        transaction::{self, PrecisionPolicy, Transaction},
+    },
+    encoding,
+    engine::{
+        config::{
+            Config, DuplicatePolicy, LockedAccountPolicy, NegativeBalancePolicy, OverdraftLimits,
+        },
+        TransactionEngine,
+    },
+    error::{Error, Result},
+    json,
+    merge::{self, Order},
+    msgpack, ofx, protobuf, qif,
+};
+
+mod accrue;
+mod audit;
+mod checkpoint;
+mod columns;
+#[cfg(any(
+    feature = "amqp",
+    feature = "kafka",
+    feature = "nats",
+    feature = "redis"
+))]
+mod consume;
+mod diff;
+mod events;
+mod generate;
+mod ledger;
+mod output;
+mod process;
+mod reconcile;
+mod rejected;
+mod replay;
+#[cfg(feature = "server")]
+mod serve;
+mod statement;
+mod stats;
+mod telemetry;
+mod validate;
+mod verify;
+#[cfg(feature = "watch")]
+mod watch;
+
+/// Simple CLI to compute transactions.
+///
+/// Running without a subcommand is the same as `txns process`.
+#[derive(Debug, Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    process: process::ProcessArgs,
+    #[command(flatten)]
+    telemetry: telemetry::TelemetryArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Apply transactions and print the resulting accounts.
+    Process(Box<process::ProcessArgs>),
+    /// Check that every transaction can be parsed and applied.
+    Validate(validate::ValidateArgs),
+    /// Print a summary of the transactions.
+    Stats(InputArgs),
+    /// Generate random transactions, e.g. for benchmarks.
+    Generate(generate::GenerateArgs),
+    /// Apply interest on daily balances to an accounts snapshot.
+    Accrue(accrue::AccrueArgs),
+    /// Print the transactions of `--client` with its balances after each.
+    Ledger(ledger::LedgerArgs),
+    /// Write the statement of every client over a period.
+    Statement(statement::StatementArgs),
+    /// Compare two accounts snapshots.
+    Diff(diff::DiffArgs),
+    /// Check the invariants of accounts.
+    Verify(verify::VerifyArgs),
+    /// Match the totals of accounts with balances from elsewhere.
+    Reconcile(reconcile::ReconcileArgs),
+    /// Rebuild accounts from the events of `process --events`.
+    Replay(replay::ReplayArgs),
+    /// Apply transactions posted over HTTP and answer queries about accounts.
+    #[cfg(feature = "server")]
+    Serve(serve::ServeArgs),
+    /// Apply transactions consumed from a message broker, like Kafka.
+    #[cfg(any(
+        feature = "amqp",
+        feature = "kafka",
+        feature = "nats",
+        feature = "redis"
+    ))]
+    Consume(consume::ConsumeArgs),
+    /// Apply the files landing in a directory, keeping accounts across them.
+    #[cfg(feature = "watch")]
+    Watch(watch::WatchArgs),
+}
+
+#[derive(Debug, Args)]
+struct InputArgs {
+    /// Files to read transactions from, one after the other. Directories and
+    /// glob patterns read the files they hold or match. Reads from stdin when
+    /// one is `-` or there are none. With the `object-store` feature, objects
+    /// like `s3://bucket/key` or `gs://bucket/key` are streamed.
+    #[arg(value_name = "INPUT")]
+    inputs: Vec<PathBuf>,
+    /// Format of the input files. Defaults to the one the extension of each
+    /// stands for, or CSV.
+    #[arg(long, value_enum)]
+    input_format: Option<InputFormat>,
+    /// Byte separating the fields of CSV input, like `;` or `tab`. Defaults
+    /// to the one of `--locale`, or a tab for files ending in `.tsv`.
+    #[arg(long, value_parser = parse_delimiter)]
+    delimiter: Option<u8>,
+    /// Interleave the inputs, each sorted the same way, in that order
+    /// instead of reading them one after the other.
+    #[arg(long, value_enum)]
+    merge: Option<MergeOrder>,
+    /// Encoding of text input, like `latin1`, used unless it starts with a
+    /// byte order mark. Defaults to UTF-8, or Windows-1252 for input that
+    /// isn't valid UTF-8.
+    #[arg(long, value_parser = parse_encoding)]
+    encoding: Option<&'static Encoding>,
+    /// Header of CSV input standing for one of the usual columns, like
+    /// `ref=tx`. Can be repeated.
+    #[arg(long, value_name = "HEADER=COLUMN", value_parser = parse_alias)]
+    header_alias: Vec<(String, String)>,
+    /// Reject transaction types that aren't in lowercase, like `DEPOSIT`,
+    /// instead of reading them in any case.
+    #[arg(long)]
+    case_sensitive_types: bool,
+    /// Number format and field separator of CSV input.
+    #[arg(long, value_enum, default_value_t)]
+    locale: Locale,
+    /// How to handle transactions that can't be parsed or applied.
+    #[arg(long, value_enum, default_value_t)]
+    mode: Mode,
+    /// What to do with amounts with more than four decimal places.
+    #[arg(long, value_enum, default_value_t)]
+    precision: Precision,
+    /// Size of the buffer input is read through, like `1M`. Bigger ones mean
+    /// fewer reads, which pays off on network filesystems.
+    #[arg(long, value_name = "SIZE", default_value = "64k", value_parser = parse_size)]
+    read_buffer: u64,
+    /// Map input files into memory instead of reading them through a
+    /// buffer. Stdin, pipes and compressed files are read as usual.
+    #[cfg(feature = "mmap")]
+    #[arg(long)]
+    mmap: bool,
+    /// age identity file to decrypt the input with.
+    #[cfg(feature = "age")]
+    #[arg(long, env = "TXNS_AGE_IDENTITY")]
+    identity: Option<PathBuf>,
+    /// Client the transactions of bank statements belong to.
+    #[arg(long, default_value_t = 1)]
+    statement_client: u16,
+    /// Id of the first transaction of bank statements, the next ones being
+    /// numbered in order.
+    #[arg(long, default_value_t = 1)]
+    first_tx: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum Precision {
+    /// Round them to four decimal places.
+    #[default]
+    Round,
+    /// Treat them as invalid transactions.
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum Locale {
+    /// Decimal points, with fields separated by commas.
+    #[default]
+    En,
+    /// Decimal commas, like `100,1234`, with fields separated by semicolons.
+    Eu,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum MergeOrder {
+    /// By transaction id, disputes, resolves, chargebacks and refunds
+    /// coming after the transaction they reference.
+    Tx,
+    /// By timestamp, transactions without one coming first.
+    Timestamp,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Abort on the first invalid transaction.
+    #[default]
+    Strict,
+    /// Skip invalid transactions, reporting them on stderr.
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum InputFormat {
+    Csv,
+    /// Newline-delimited JSON, one transaction object per line.
+    Ndjson,
+    /// Concatenated MessagePack maps, one per transaction.
+    Msgpack,
+    /// Length-delimited protobuf messages of `proto/txns.proto`.
+    Protobuf,
+    /// OFX bank statement, imported as deposits and withdrawals of
+    /// `--statement-client`.
+    Ofx,
+    /// QIF file, imported as deposits and withdrawals of
+    /// `--statement-client`.
+    Qif,
+    /// ISO 20022 camt.053 statement, imported as deposits and withdrawals
+    /// of `--statement-client`.
+    #[cfg(feature = "camt")]
+    Camt053,
+    /// Avro object container file.
+    #[cfg(feature = "avro")]
+    Avro,
+    /// First sheet of a spreadsheet, with the same columns as CSV.
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+}
+
+impl Precision {
+    fn policy(self) -> PrecisionPolicy {
+        match self {
+            Self::Round => PrecisionPolicy::Round,
+            Self::Reject => PrecisionPolicy::Reject,
+        }
+    }
+}
+
+impl InputFormat {
+    /// Whether the format is text, which may need transcoding to UTF-8.
+    fn is_text(self) -> bool {
+        matches!(
+            self,
+            InputFormat::Csv | InputFormat::Ndjson | InputFormat::Ofx | InputFormat::Qif
+        )
+    }
+}
+
+/// Policies of the transaction engine.
+#[derive(Debug, Args)]
+struct EngineArgs {
+    /// Accounts to start from, as written by `txns process`, like the output
+    /// of the previous day.
+    #[arg(long, alias = "snapshot", value_name = "FILE")]
+    initial_state: Option<PathBuf>,
+    /// What to do with deposits and withdrawals for accounts locked by a
+    /// chargeback.
+    #[arg(long, value_enum, default_value_t)]
+    locked_policy: LockedPolicy,
+    /// What to do with deposits and withdrawals reusing a transaction id.
+    #[arg(long, value_enum, default_value_t)]
+    on_duplicate: OnDuplicate,
+    /// What to do with withdrawals bigger than the available funds.
+    #[arg(long, value_enum, default_value_t)]
+    negative_balance: NegativeBalance,
+    /// How far below zero withdrawals can take available funds, for clients
+    /// without a limit in `--overdraft-file`. Adds an `overdraft` column to
+    /// the accounts.
+    #[arg(long)]
+    overdraft: Option<Decimal>,
+    /// CSV file with `client` and `limit` columns setting the overdraft of
+    /// specific clients.
+    #[arg(long)]
+    overdraft_file: Option<PathBuf>,
+    /// Add an `adjusted` column to the accounts, with the net amount of their
+    /// adjustments.
+    #[arg(long)]
+    audit_adjustments: bool,
+    /// Accept unlock transactions, which clear the lock of an account.
+    #[arg(long)]
+    allow_unlock: bool,
+    /// Reject disputes coming more than this many days after the transaction
+    /// they reference, according to their timestamps.
+    #[arg(long, value_name = "DAYS", value_parser = parse_days)]
+    dispute_window: Option<Duration>,
+    /// TOML file with the fees of deposits, withdrawals and transfers. Adds a
+    /// `fees` column to the accounts.
+    #[arg(long)]
+    fees: Option<PathBuf>,
+}
+
+/// Order of the accounts output.
+#[derive(Debug, Args)]
+struct SortArgs {
+    /// What to sort accounts by, ties being broken by client.
+    #[arg(long, value_enum, default_value_t)]
+    sort: SortKey,
+    /// Sort accounts in descending order.
+    #[arg(long)]
+    reverse: bool,
+}
+
+/// Which accounts to output.
+#[derive(Debug, Args)]
+struct FilterArgs {
+    /// Only output locked accounts.
+    #[arg(long)]
+    only_locked: bool,
+    /// Only output the account of this client, or of a range of them like
+    /// `100-199`. Can be repeated.
+    #[arg(long, alias = "only-client", value_name = "ID|RANGE", value_parser = parse_clients)]
+    client: Vec<RangeInclusive<u16>>,
+    /// Only output accounts with at least this total.
+    #[arg(long, value_name = "AMOUNT")]
+    min_total: Option<Decimal>,
+    /// Only output accounts whose balances or lock changed since
+    /// `--initial-state`, including new ones.
+    #[arg(long, requires = "initial_state")]
+    only_changed: bool,
+    /// Only output the N accounts ranking highest by `--by`, in that order.
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+    /// What to rank accounts by for `--top`.
+    #[arg(long, value_enum, default_value_t, requires = "top")]
+    by: Rank,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum Rank {
+    /// Total funds.
+    #[default]
+    Balance,
+    /// Amount of deposits, withdrawals and transfers sent.
+    Volume,
+    /// Number of transactions disputed.
+    Disputes,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum SortKey {
+    #[default]
+    Client,
+    Available,
+    Held,
+    Total,
+    /// Locked accounts last.
+    Locked,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum LockedPolicy {
+    /// Skip them, or abort in strict mode.
+    #[default]
+    Reject,
+    /// Apply them anyway.
+    Allow,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum OnDuplicate {
+    /// Treat them as invalid transactions.
+    #[default]
+    Error,
+    /// Skip and report them, even in strict mode.
+    Skip,
+    /// Silently keep the first transaction with a given id.
+    KeepFirst,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum NegativeBalance {
+    /// Treat the withdrawal as an invalid transaction.
+    #[default]
+    RejectWithdrawal,
+    /// Also treat every later transaction of the client as invalid, leaving
+    /// its account out of the output.
+    ErrorClient,
+    /// Apply the withdrawal anyway.
+    AllowOverdraft,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Csv,
+    /// A JSON array of account objects.
+    Json,
+    /// Newline-delimited JSON, one account object per line.
+    Ndjson,
+    /// Concatenated MessagePack maps, one per account.
+    Msgpack,
+    /// Length-delimited protobuf messages of `proto/txns.proto`.
+    Protobuf,
+    /// Arrow IPC stream, with a single record batch.
+    #[cfg(feature = "arrow")]
+    Arrow,
+    /// Arrow IPC file, also known as Feather.
+    #[cfg(feature = "arrow")]
+    Feather,
+    /// Avro object container file.
+    #[cfg(feature = "avro")]
+    Avro,
+    /// Aligned columns, meant to be read in a terminal.
+    Table,
+}
+
+impl Cli {
+    pub fn run(self) -> Result<()> {
+        let _telemetry = self.telemetry.init()?;
+
+        match self.command {
+            Some(Command::Process(args)) => process::run(*args),
+            Some(Command::Validate(args)) => validate::run(args),
+            Some(Command::Stats(args)) => stats::run(args),
+            Some(Command::Generate(args)) => generate::run(args),
+            Some(Command::Accrue(args)) => accrue::run(args),
+            Some(Command::Ledger(args)) => ledger::run(args),
+            Some(Command::Statement(args)) => statement::run(args),
+            Some(Command::Diff(args)) => diff::run(args),
+            Some(Command::Verify(args)) => verify::run(args),
+            Some(Command::Reconcile(args)) => reconcile::run(args),
+            Some(Command::Replay(args)) => replay::run(args),
+            #[cfg(feature = "server")]
+            Some(Command::Serve(args)) => serve::run(args),
+            #[cfg(any(
+                feature = "amqp",
+                feature = "kafka",
+                feature = "nats",
+                feature = "redis"
+            ))]
+            Some(Command::Consume(args)) => consume::run(args),
+            #[cfg(feature = "watch")]
+            Some(Command::Watch(args)) => watch::run(args),
+            None => process::run(self.process),
+        }
+    }
+}
+
+/// Transactions of a single input file.
+type Transactions = Box<dyn Iterator<Item = Result<Transaction>>>;
+
+impl InputArgs {
+    /// Read the transactions of every input in order, as a single stream.
+    fn read(&self) -> Result<impl Iterator<Item = Result<Transaction>> + '_> {
+        let txns: Box<dyn Iterator<Item = Result<Transaction>> + '_> = match self.merge {
+            Some(order) => {
+                let order = match order {
+                    MergeOrder::Tx => Order::TransactionId,
+                    MergeOrder::Timestamp => Order::Timestamp,
+                };
+                Box::new(merge::merge(self.files()?.collect::<Result<_>>()?, order))
+            }
+            None => Box::new(
+                self.files()?
+                    .flat_map(|txns| txns.unwrap_or_else(|error| Box::new(iter::once(Err(error))))),
+            ),
+        };
+
+        Ok(txns)
+    }
+
+    /// Open each input only once the previous ones are read, so there is no
+    /// limit to how many there are unless they are merged, reporting on
+    /// stderr which one is opened when there are several. Bank statements are
+    /// numbered on from the last transaction of the previous one.
+    fn files(&self) -> Result<impl Iterator<Item = Result<Transactions>> + '_> {
+        let paths = self.paths()?;
+        let count = paths.len();
+        let mut first_tx = self.first_tx;
+
+        Ok(paths.into_iter().enumerate().map(move |(index, path)| {
+            let input = path.as_deref().unwrap_or(Path::new("-")).display();
+            match count {
+                2.. => info!(%input, file = index + 1, files = count, "reading {input} ({}/{count})", index + 1),
+                _ => debug!(%input, "reading {input}"),
+            }
+            let span = info_span!("read", %input);
+            let txns = span.in_scope(|| self.read_file(path.as_deref(), &mut first_tx))?;
+
+            Ok(Box::new(Traced::new(txns, span)) as Transactions)
+        }))
+    }
+
+    /// Input files, directories being replaced by the files they hold and
+    /// glob patterns by the files they match, both in lexicographic order.
+    /// Stdin is read when there are none.
+    fn paths(&self) -> Result<Vec<Option<PathBuf>>> {
+        if self.inputs.is_empty() {
+            return Ok(vec![None]);
+        }
+
+        let mut paths = Vec::new();
+        for input in &self.inputs {
+            #[cfg(feature = "object-store")]
+            if txns::object::is_url(input) {
+                paths.push(Some(input.clone()));
+                continue;
+            }
+
+            if input.is_dir() {
+                let mut files = Vec::new();
+                for entry in fs::read_dir(input)? {
+                    let entry = entry?;
+                    let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                    if entry.file_type()?.is_file() && !hidden {
+                        files.push(entry.path());
+                    }
+                }
+                files.sort();
+                paths.extend(files.into_iter().map(Some));
+            } else if !input.exists() && is_pattern(input) {
+                let pattern = input.to_string_lossy();
+                let mut files = Vec::new();
+                for path in glob::glob(&pattern)
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?
+                {
+                    let path = path.map_err(io::Error::from)?;
+                    if path.is_file() {
+                        files.push(path);
+                    }
+                }
+                if files.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no file matches `{pattern}`"),
+                    )
+                    .into());
+                }
+                files.sort();
+                paths.extend(files.into_iter().map(Some));
+            } else {
+                paths.push(Some(input.clone()));
+            }
+        }
+
+        Ok(paths)
+    }
+
+    /// Read the transactions of a single input, with `--precision` applied
+    /// to their amounts.
+    fn read_file(&self, path: Option<&Path>, first_tx: &mut u64) -> Result<Transactions> {
+        let format = self.format(path);
+        transaction::set_case_sensitive_types(self.case_sensitive_types);
+        let reader = match format.is_text() {
+            true => encoding::decode(self.open(path)?, self.encoding)?,
+            false => self.open(path)?,
+        };
+
+        let txns: Transactions = match format {
+            InputFormat::Csv => Box::new(csv::read_with(reader, self.dialect(path))),
+            InputFormat::Ndjson => Box::new(json::read(reader)),
+            InputFormat::Msgpack => Box::new(msgpack::read(reader)),
+            InputFormat::Protobuf => Box::new(protobuf::read(reader)),
+            InputFormat::Ofx => statement(
+                ofx::read(reader, self.statement_client, *first_tx)?,
+                first_tx,
+            ),
+            InputFormat::Qif => statement(
+                qif::read(reader, self.statement_client, *first_tx)?,
+                first_tx,
+            ),
+            #[cfg(feature = "camt")]
+            InputFormat::Camt053 => statement(
+                txns::camt::read(reader, self.statement_client, *first_tx)?,
+                first_tx,
+            ),
+            #[cfg(feature = "avro")]
+            InputFormat::Avro => Box::new(txns::avro::read(reader)?),
+            #[cfg(feature = "xlsx")]
+            InputFormat::Xlsx => Box::new(txns::xlsx::read(reader)?),
+        };
+        let policy = self.precision.policy();
+
+        Ok(Box::new(
+            txns.map(move |tx| Ok(tx?.enforce_precision(policy)?)),
+        ))
+    }
+
+    fn format(&self, path: Option<&Path>) -> InputFormat {
+        let extension = extension(path);
+
+        self.input_format.unwrap_or(match extension {
+            Some(extension) if extension == "ndjson" || extension == "jsonl" => InputFormat::Ndjson,
+            Some(extension) if extension == "msgpack" || extension == "mpk" => InputFormat::Msgpack,
+            Some(extension) if extension == "pb" => InputFormat::Protobuf,
+            Some(extension) if extension == "ofx" || extension == "qfx" => InputFormat::Ofx,
+            Some(extension) if extension == "qif" => InputFormat::Qif,
+            #[cfg(feature = "avro")]
+            Some(extension) if extension == "avro" => InputFormat::Avro,
+            #[cfg(feature = "xlsx")]
+            Some(extension)
+                if ["xlsx", "xlsm", "xls", "ods"]
+                    .iter()
+                    .any(|xlsx| extension == *xlsx) =>
+            {
+                InputFormat::Xlsx
+            }
+            _ => InputFormat::Csv,
+        })
+    }
+
+    fn dialect(&self, path: Option<&Path>) -> Dialect {
+        let dialect = match (self.locale, extension(path)) {
+            (Locale::Eu, _) => Dialect::eu(),
+            (Locale::En, Some(extension)) if extension == "tsv" => Dialect {
+                delimiter: b'\t',
+                ..Default::default()
+            },
+            (Locale::En, _) => Dialect::default(),
+        };
+
+        Dialect {
+            delimiter: self.delimiter.unwrap_or(dialect.delimiter),
+            aliases: self.header_alias.iter().cloned().collect(),
+            ..dialect
+        }
+    }
+
+    /// Open an input, or stdin when there is none or it is `-`, decrypting and
+    /// decompressing it if needed.
+    fn open(&self, path: Option<&Path>) -> Result<Box<dyn Read>> {
+        let buffer = self.read_buffer as usize;
+        let reader: Box<dyn Read> = match path {
+            #[cfg(feature = "object-store")]
+            Some(path) if txns::object::is_url(path) => Box::new(BufReader::with_capacity(
+                buffer,
+                txns::object::open(&path.to_string_lossy())?,
+            )),
+            #[cfg(feature = "mmap")]
+            Some(path) if self.mmap && path != Path::new("-") => match txns::mmap::open(path)? {
+                Some(mapped) => Box::new(mapped),
+                None => Box::new(BufReader::with_capacity(buffer, File::open(path)?)),
+            },
+            Some(path) if path != Path::new("-") => {
+                Box::new(BufReader::with_capacity(buffer, File::open(path)?))
+            }
+            _ => Box::new(BufReader::with_capacity(buffer, io::stdin().lock())),
+        };
+
+        #[cfg(feature = "age")]
+        let reader: Box<dyn Read> = match &self.identity {
+            Some(identity) => {
+                let identities = txns::encryption::read_identities(identity)?;
+                Box::new(txns::encryption::decrypt(reader, &identities)?)
+            }
+            None => reader,
+        };
+
+        Ok(compression::decompress(reader)?)
+    }
+}
+
+/// Transactions of a bank statement starting at `first_tx`, which is moved
+/// past them.
+fn statement(txns: impl Iterator<Item = Result<Transaction>>, first_tx: &mut u64) -> Transactions {
+    let txns: Vec<_> = txns.collect();
+    *first_tx += txns.len() as u64;
+
+    Box::new(txns.into_iter())
+}
+
+/// Whether `path` has glob wildcards, like `txns-2024-01-*.csv`.
+fn is_pattern(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Extension of an input file, skipping the ones of its compression and
+/// encryption, as in `transactions.csv.gz.age`.
+fn extension(path: Option<&Path>) -> Option<&OsStr> {
+    let mut path = path?;
+
+    loop {
+        match path.extension() {
+            Some(extension)
+                if compression::EXTENSIONS.iter().any(|ext| extension == *ext)
+                    || extension == "age" =>
+            {
+                path = Path::new(path.file_stem()?);
+            }
+            extension => return extension,
+        }
+    }
+}
+
+impl EngineArgs {
+    fn engine(&self) -> Result<TransactionEngine> {
+        let overdraft = match (self.overdraft, &self.overdraft_file) {
+            (None, None) => None,
+            (default, file) => Some(OverdraftLimits {
+                default: default.unwrap_or_default(),
+                clients: match file {
+                    Some(path) => csv::read_overdrafts(File::open(path)?)?,
+                    None => Default::default(),
+                },
+            }),
+        };
+
+        let fees = match &self.fees {
+            Some(path) => Some(toml::from_str(&fs::read_to_string(path)?)?),
+            None => None,
+        };
+
+        let mut engine = TransactionEngine::with_config(Config {
+            locked_account: match self.locked_policy {
+                LockedPolicy::Reject => LockedAccountPolicy::Reject,
+                LockedPolicy::Allow => LockedAccountPolicy::Allow,
+            },
+            duplicate_transaction: match self.on_duplicate {
+                OnDuplicate::Error | OnDuplicate::Skip => DuplicatePolicy::Reject,
+                OnDuplicate::KeepFirst => DuplicatePolicy::KeepFirst,
+            },
+            negative_balance: match self.negative_balance {
+                NegativeBalance::RejectWithdrawal => NegativeBalancePolicy::RejectWithdrawal,
+                NegativeBalance::ErrorClient => NegativeBalancePolicy::ErrorClient,
+                NegativeBalance::AllowOverdraft => NegativeBalancePolicy::AllowOverdraft,
+            },
+            overdraft,
+            audit_adjustments: self.audit_adjustments,
+            allow_unlock: self.allow_unlock,
+            dispute_window: self.dispute_window,
+            fees,
+        });
+
+        for account in self.initial_state()?.into_values() {
+            engine.insert_account(account);
+        }
+
+        Ok(engine)
+    }
+
+    /// Accounts of `--initial-state`, if any, by client.
+    fn initial_state(&self) -> Result<HashMap<u16, Account>> {
+        let Some(path) = &self.initial_state else {
+            return Ok(HashMap::new());
+        };
+
+        csv::read_accounts(File::open(path)?)
+            .map(|account| account.map(|account| (account.client(), account)))
+            .collect()
+    }
+
+    /// Whether `error` only skips its transaction, even in strict mode.
+    fn tolerates(&self, error: &Error) -> bool {
+        self.on_duplicate == OnDuplicate::Skip
+            && matches!(
+                error,
+                Error::BusinessError(DomainError::DuplicateTransaction { .. })
+            )
+    }
+
+    /// Whether `error` ends a run in `mode`, instead of skipping its
+    /// transaction.
+    fn stops(&self, error: &Error, mode: Mode) -> bool {
+        (mode == Mode::Strict && !self.tolerates(error)) || fatal(error)
+    }
+
+    /// Apply every transaction with `process` according to `mode`, logging
+    /// each skipped one as it goes before handing its error to `skip`, and
+    /// return how many were skipped.
+    fn apply_with(
+        &self,
+        txns: impl Iterator<Item = Result<Transaction>>,
+        mode: Mode,
+        mut skip: impl FnMut(Error),
+        mut process: impl FnMut(Transaction) -> Result<()>,
+    ) -> Result<usize> {
+        let mut skipped = 0;
+
+        for tx in txns {
+            if let Ok(tx) = &tx {
+                trace!(
+                    client = tx.client,
+                    transaction = tx.transaction_id,
+                    "applying transaction {} of client {}",
+                    tx.transaction_id,
+                    tx.client
+                );
+            }
+            let Err(error) = tx.and_then(&mut process) else {
+                continue;
+            };
+
+            if self.stops(&error, mode) {
+                report_skipped(skipped);
+                return Err(error);
+            }
+            report_skip(&error);
+            skipped += 1;
+            skip(error);
+        }
+
+        report_skipped(skipped);
+        Ok(skipped)
+    }
+}
+
+impl SortArgs {
+    /// Accounts in the chosen order, so output is the same from one run to
+    /// the next.
+    fn sort<'a>(&self, accounts: impl Iterator<Item = &'a Account>) -> Vec<&'a Account> {
+        let mut accounts: Vec<_> = accounts.collect();
+        accounts.sort_by(|a, b| {
+            let order = match self.sort {
+                SortKey::Client => Ordering::Equal,
+                SortKey::Available => a.available().cmp(&b.available()),
+                SortKey::Held => a.held().cmp(&b.held()),
+                SortKey::Total => a.total().cmp(&b.total()),
+                SortKey::Locked => a.locked().cmp(&b.locked()),
+            };
+            order.then(a.client().cmp(&b.client()))
+        });
+        if self.reverse {
+            accounts.reverse();
+        }
+
+        accounts
+    }
+}
+
+impl FilterArgs {
+    /// Keep the accounts ranking highest by `--by`, if `--top` is given,
+    /// biggest first, ties being in client order.
+    fn top<'a>(
+        &self,
+        mut accounts: Vec<&'a Account>,
+        engine: &TransactionEngine,
+    ) -> Result<Vec<&'a Account>> {
+        let Some(top) = self.top else {
+            return Ok(accounts);
+        };
+
+        let metrics = match self.by {
+            Rank::Balance => HashMap::new(),
+            Rank::Volume | Rank::Disputes => engine.metrics()?,
+        };
+        let metrics =
+            |account: &Account| metrics.get(&account.client()).copied().unwrap_or_default();
+        let key = |account: &Account| match self.by {
+            Rank::Balance => (account.total(), 0),
+            Rank::Volume => (metrics(account).volume, 0),
+            Rank::Disputes => (Decimal::ZERO, metrics(account).disputes),
+        };
+
+        accounts.sort_by(|a, b| key(b).cmp(&key(a)).then(a.client().cmp(&b.client())));
+        accounts.truncate(top);
+
+        Ok(accounts)
+    }
+
+    /// Whether `account` passes every filter.
+    fn matches(&self, account: &Account) -> bool {
+        (!self.only_locked || account.locked())
+            && (self.client.is_empty()
+                || self
+                    .client
+                    .iter()
+                    .any(|clients| clients.contains(&account.client())))
+            && self.min_total.is_none_or(|min| account.total() >= min)
+    }
+}
+
+/// Whether `account` differs from what it was in `initial`, as far as
+/// balances and lock go.
+fn changed(initial: &HashMap<u16, Account>, account: &Account) -> bool {
+    initial.get(&account.client()).is_none_or(|initial| {
+        (
+            initial.available(),
+            initial.held(),
+            initial.total(),
+            initial.locked(),
+        ) != (
+            account.available(),
+            account.held(),
+            account.total(),
+            account.locked(),
+        )
+    })
+}
+
+/// Parse a client id, like `7`, or an inclusive range of them, like
+/// `100-199`.
+fn parse_clients(value: &str) -> std::result::Result<RangeInclusive<u16>, String> {
+    let parse = |client: &str| {
+        client
+            .trim()
+            .parse::<u16>()
+            .map_err(|error| format!("invalid client `{client}`: {error}"))
+    };
+
+    match value.split_once('-') {
+        Some((first, last)) => Ok(parse(first)?..=parse(last)?),
+        None => Ok(parse(value)?..=parse(value)?),
+    }
+}
+
+/// Parse a number of days, like `30`.
+fn parse_days(value: &str) -> std::result::Result<Duration, String> {
+    value
+        .parse::<u64>()
+        .map_err(|error| error.to_string())?
+        .checked_mul(24 * 60 * 60)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("{value} days is too long"))
+}
+
+/// Parse a size in bytes, like `512`, optionally with a `k`, `M`, `G` or `T`
+/// suffix for powers of 1024, like `2G`.
+fn parse_size(value: &str) -> std::result::Result<u64, String> {
+    let (digits, unit) = match value.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&value[..index], 1 << 10),
+        Some((index, 'm' | 'M')) => (&value[..index], 1 << 20),
+        Some((index, 'g' | 'G')) => (&value[..index], 1 << 30),
+        Some((index, 't' | 'T')) => (&value[..index], 1 << 40),
+        _ => (value, 1),
+    };
+
+    digits
+        .parse::<u64>()
+        .map_err(|error| error.to_string())?
+        .checked_mul(unit)
+        .ok_or_else(|| format!("{value} is too big"))
+}
+
+/// Parse a CSV delimiter, given as a single ASCII character or `tab`.
+fn parse_delimiter(value: &str) -> std::result::Result<u8, String> {
+    match value.as_bytes() {
+        b"tab" | b"\\t" => Ok(b'\t'),
+        [delimiter] if delimiter.is_ascii() => Ok(*delimiter),
+        _ => Err("expected a single ASCII character or `tab`".to_owned()),
+    }
+}
+
+/// Parse a header alias, like `ref=tx`.
+fn parse_alias(value: &str) -> std::result::Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((header, column)) => Ok((header.to_owned(), column.to_owned())),
+        None => Err("expected `HEADER=COLUMN`".to_owned()),
+    }
+}
+
+/// Parse an encoding label, like `utf-8`, `latin1` or `windows-1252`.
+fn parse_encoding(value: &str) -> std::result::Result<&'static Encoding, String> {
+    Encoding::for_label(value.as_bytes()).ok_or_else(|| format!("unknown encoding `{value}`"))
+}
+
+/// Log how many transactions were skipped, which is still logged with `-q`.
+fn report_skipped(skipped: usize) {
+    if skipped > 0 {
+        warn!(skipped, "skipped {skipped} invalid transactions");
+    }
+}
+
+/// Whether `error` stops the run even in lenient mode, the stored
+/// transactions being out of reach for the ones after it too.
+fn fatal(error: &Error) -> bool {
+    matches!(error, Error::BusinessError(DomainError::Store(_)))
+}
+
+/// Log why a transaction was skipped.
+fn report_skip(error: &Error) {
+    match error {
+        Error::BusinessError(reason) => {
+            info!(reason = reason.code(), "skipped transaction: {error}")
+        }
+        Error::InvalidRow { line, .. } => info!(line, "skipped transaction: {error}"),
+        _ => info!("skipped transaction: {error}"),
+    }
+}
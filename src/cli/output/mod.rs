@@ -0,0 +1,90 @@
+use std::{
+    io::{self, BufWriter, IsTerminal, StdoutLock, Write},
+    path::Path,
+};
+
+use tempfile::NamedTempFile;
+use txns::error::Result;
+
+/// Destination of a command's output.
+///
+/// Files are written to a temporary sibling and only renamed into place by
+/// [`Output::finish`], so readers never see a partially written file. Dropping
+/// an unfinished output removes the temporary file.
+pub enum Output {
+    Stdout(BufWriter<StdoutLock<'static>>),
+    File {
+        file: BufWriter<NamedTempFile>,
+        path: Box<Path>,
+    },
+}
+
+/// Buffer size of outputs unless told otherwise, the one of [`BufWriter`].
+const BUFFER: usize = 8 * 1024;
+
+impl Output {
+    /// Open `path` for writing, or stdout if there is none.
+    pub fn open(path: Option<&Path>) -> Result<Self> {
+        Self::with_buffer(path, BUFFER)
+    }
+
+    /// Like [`open`](Self::open), writing `buffer` bytes at a time.
+    pub fn with_buffer(path: Option<&Path>, buffer: usize) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::Stdout(BufWriter::with_capacity(
+                buffer,
+                io::stdout().lock(),
+            )));
+        };
+
+        // The temporary file must live in the same directory, otherwise the
+        // rename could cross filesystems and stop being atomic.
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        Ok(Self::File {
+            file: BufWriter::with_capacity(buffer, NamedTempFile::new_in(dir)?),
+            path: path.into(),
+        })
+    }
+
+    /// Whether the output goes to a terminal, rather than a file or a pipe.
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            Self::Stdout(stdout) => stdout.get_ref().is_terminal(),
+            Self::File { .. } => false,
+        }
+    }
+
+    /// Flush everything written and move the file into its final path.
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Self::Stdout(mut stdout) => stdout.flush()?,
+            Self::File { file, path } => {
+                let file = file.into_inner().map_err(|error| error.into_error())?;
+                file.as_file().sync_all()?;
+                file.persist(path).map_err(|error| error.error)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(stdout) => stdout.write(buf),
+            Self::File { file, .. } => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(stdout) => stdout.flush(),
+            Self::File { file, .. } => file.flush(),
+        }
+    }
+}
@@ -0,0 +1,425 @@
+use std::{cell::Cell, fs::File, io, num::NonZeroUsize, path::PathBuf};
+#[cfg(feature = "prometheus")]
+use std::{net::SocketAddr, sync::Arc};
+
+use clap::Args;
+use tracing::{info, info_span};
+#[cfg(feature = "sqlite")]
+use txns::engine::store::sqlite::SqliteStore;
+#[cfg(feature = "prometheus")]
+use txns::prometheus::Metrics;
+use txns::{
+    csv,
+    domain::fx::Rates,
+    engine::{journal::Journal, shard, store::spill::SpillStore, TransactionEngine},
+    error::{Error, Result},
+    json::{self, Record},
+    msgpack, protobuf,
+};
+#[cfg(feature = "sled")]
+use txns::{
+    domain::error::Error as DomainError,
+    engine::store::{sled::SledStore, TransactionStore},
+};
+
+use super::{
+    audit::Audit,
+    changed,
+    checkpoint::{self, parse_count, Checkpoints},
+    columns::{Column, Table},
+    events::Events,
+    output::Output,
+    parse_size,
+    rejected::{Rejected, Row},
+    report_skip, report_skipped, EngineArgs, FilterArgs, InputArgs, OutputFormat, SortArgs,
+};
+
+#[derive(Debug, Args)]
+pub struct ProcessArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    sort: SortArgs,
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// File to write accounts to, defaults to stdout. The file is only
+    /// replaced once every account is written. Built with the `postgres`
+    /// feature, a `postgres://` URL upserts them into `--postgres-table`
+    /// instead.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Table, possibly qualified by its schema, to upsert accounts into when
+    /// `--output` is a Postgres URL. Created if needed.
+    #[cfg(feature = "postgres")]
+    #[arg(long, value_name = "TABLE", default_value = "accounts")]
+    postgres_table: String,
+    /// CSV file to record every transaction to, with the balances of its
+    /// client before and after it and whether it was applied, ignored or
+    /// rejected. The file is only replaced once the run ends.
+    #[arg(long, value_name = "FILE")]
+    audit: Option<PathBuf>,
+    /// File to write every ignored or rejected transaction to, with a
+    /// `reason` code like `insufficient_funds`. Written as NDJSON for paths
+    /// ending in `.ndjson` or `.jsonl`, or CSV otherwise.
+    #[arg(long, value_name = "FILE")]
+    rejected: Option<PathBuf>,
+    /// File to write an event to for every transaction, like
+    /// `{"event":"FundsHeld","client":1,"tx":3,"amount":"2.0000"}`, as
+    /// NDJSON. The file is only replaced once the run ends.
+    #[arg(long, value_name = "FILE")]
+    events: Option<PathBuf>,
+    /// Print a BLAKE3 hash of every account to stderr once done, like
+    /// `state hash: 1f0c...`, even with `--quiet`, to check that another run
+    /// ended up with the same accounts.
+    #[arg(long)]
+    state_hash: bool,
+    /// Size of the buffers of the accounts and side outputs, like `1M`.
+    /// Bigger ones mean fewer writes, which pays off on network filesystems.
+    #[arg(long, value_name = "SIZE", default_value = "64k", value_parser = parse_size)]
+    write_buffer: u64,
+    /// Format of the accounts output. Defaults to a table when writing to a
+    /// terminal, or CSV otherwise.
+    #[arg(long, value_enum)]
+    output_format: Option<OutputFormat>,
+    /// Columns of the accounts output, in order, like `client,total,locked`.
+    /// Besides the usual ones, `deposits`, `withdrawals`, `disputes` and
+    /// `chargebacks` count the transactions of each client. Only applies to
+    /// CSV, JSON and table output formats.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    columns: Option<Vec<Column>>,
+    /// Append the `deposits`, `withdrawals`, `disputes` and `chargebacks`
+    /// columns to the usual ones.
+    #[arg(long, conflicts_with = "columns")]
+    metrics: bool,
+    /// Follow the accounts with an `{"error": ...}` object for each skipped
+    /// transaction. Only applies to JSON output formats.
+    #[arg(long)]
+    output_errors: bool,
+    /// CSV file with `from`, `to` and `rate` columns to convert every total
+    /// into `--base-currency`. Adds `currency` and `base_total` columns to the
+    /// accounts.
+    #[arg(long, requires = "base_currency")]
+    rates: Option<PathBuf>,
+    /// Currency to convert totals into.
+    #[arg(long, requires = "rates")]
+    base_currency: Option<String>,
+    /// File to save the state of the engine to every `--checkpoint-every`
+    /// transactions, to resume the run with `--resume` if it is interrupted.
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<PathBuf>,
+    /// How many transactions to read between checkpoints, like `500k` or
+    /// `1M`.
+    #[arg(long, value_name = "N", default_value = "1M", value_parser = parse_count)]
+    checkpoint_every: u64,
+    /// Start from the state saved in `--checkpoint`, skipping the
+    /// transactions read before it, if it exists. Engine options must be the
+    /// same as when it was saved.
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+    /// Write-ahead journal to append every transaction to before applying
+    /// it, and to apply again on startup. Meant for long-lived runs reading
+    /// stdin, where transactions aren't given again after a restart.
+    #[arg(long, value_name = "FILE", conflicts_with = "checkpoint")]
+    journal: Option<PathBuf>,
+    /// Memory to keep transactions that could still be disputed in, like
+    /// `2G`, beyond which they are spilled to temporary files and read back
+    /// when referenced. Accounts always stay in memory, at most 65,536 of
+    /// them.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    #[cfg_attr(feature = "sqlite", arg(conflicts_with = "sqlite"))]
+    #[cfg_attr(feature = "sled", arg(conflicts_with = "sled"))]
+    max_memory: Option<u64>,
+    /// Apply transactions with this many threads, the ones of each client
+    /// going to thread `client % N`, with the same results as a single one.
+    /// Transactions involving clients or ids of several threads wait for
+    /// them.
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["audit", "rejected", "events", "checkpoint", "journal", "max_memory"]
+    )]
+    #[cfg_attr(feature = "sqlite", arg(conflicts_with = "sqlite"))]
+    #[cfg_attr(feature = "sled", arg(conflicts_with = "sled"))]
+    workers: Option<NonZeroUsize>,
+    /// Address to serve Prometheus metrics on at `/metrics`, like
+    /// `0.0.0.0:9090`, for as long as the run lasts.
+    #[cfg(feature = "prometheus")]
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "workers")]
+    metrics_listen: Option<SocketAddr>,
+    /// SQLite database to keep transactions in instead of memory, starting
+    /// from the accounts it holds and saving them to its `accounts` table
+    /// once done.
+    #[cfg(feature = "sqlite")]
+    #[arg(long, value_name = "FILE")]
+    sqlite: Option<PathBuf>,
+    /// Directory of a sled database to keep transactions in instead of
+    /// memory. Whatever it held is cleared first.
+    #[cfg(feature = "sled")]
+    #[arg(long, value_name = "DIR")]
+    #[cfg_attr(feature = "sqlite", arg(conflicts_with = "sqlite"))]
+    sled: Option<PathBuf>,
+}
+
+pub fn run(args: ProcessArgs) -> Result<()> {
+    let _span = info_span!("process").entered();
+    let load = info_span!("load").entered();
+    let mut engine = args.engine.engine()?;
+    if let Some(max_memory) = args.max_memory {
+        engine = engine.with_store(SpillStore::new(max_memory));
+    }
+    #[cfg(feature = "sqlite")]
+    let sqlite = match &args.sqlite {
+        Some(path) => {
+            let store = SqliteStore::open(path)?;
+            for account in store.accounts()? {
+                engine.insert_account(account);
+            }
+            engine = engine.with_store(store.clone());
+            Some(store)
+        }
+        None => None,
+    };
+    #[cfg(feature = "sled")]
+    if let Some(path) = &args.sled {
+        let mut store = SledStore::open(path)?;
+        store.clear().map_err(DomainError::from)?;
+        engine = engine.with_store(store);
+    }
+    let resumed = match (&args.checkpoint, args.resume) {
+        (Some(path), true) if path.exists() => checkpoint::resume(&mut engine, path)?,
+        _ => 0,
+    };
+    let mut journal = match &args.journal {
+        Some(path) => {
+            let (journal, count) = Journal::open(path, &mut engine)?;
+            info!(count, "applied {count} journaled transactions");
+            Some(journal)
+        }
+        None => None,
+    };
+    #[cfg(feature = "prometheus")]
+    let metrics = match args.metrics_listen {
+        Some(address) => {
+            let metrics = Arc::new(Metrics::new());
+            metrics.count_clients(&engine);
+            let address = metrics.serve(address)?;
+            info!(%address, "serving metrics on http://{address}/metrics");
+            Some(metrics)
+        }
+        None => None,
+    };
+    load.exit();
+
+    let mut checkpoints = args
+        .checkpoint
+        .as_deref()
+        .map(|path| Checkpoints::new(path, args.checkpoint_every, resumed));
+    let position = Cell::new(resumed);
+    let txns = args
+        .input
+        .read()?
+        .skip(resumed as usize)
+        .inspect(|_| position.set(position.get() + 1));
+
+    let buffer = args.write_buffer as usize;
+    let mut audit = args
+        .audit
+        .as_deref()
+        .map(|path| Audit::open(path, buffer))
+        .transpose()?;
+    let mut rejected = args
+        .rejected
+        .as_deref()
+        .map(|path| Rejected::open(path, buffer))
+        .transpose()?;
+    let mut events = args
+        .events
+        .as_deref()
+        .map(|path| Events::open(path, buffer))
+        .transpose()?;
+    let mut emitted = Vec::new();
+    // Errors of skipped transactions, only kept for `--output-errors`.
+    let mut kept = Vec::new();
+    let mut keep = |error| {
+        if args.output_errors {
+            kept.push(error);
+        }
+    };
+    let applying = info_span!("apply");
+    let skipped = match args.workers {
+        Some(workers) => {
+            let _entered = applying.enter();
+            let (mut skipped, mut failed) = (0, None);
+            engine = shard::process(engine, workers.get(), txns, |index, error| {
+                if args.engine.stops(&error, args.input.mode) {
+                    // A single thread stops at the first one in input order.
+                    if failed.as_ref().is_none_or(|(first, _)| index < *first) {
+                        failed = Some((index, error));
+                    }
+                    return true;
+                }
+                report_skip(&error);
+                skipped += 1;
+                keep(error);
+                false
+            })?;
+            report_skipped(skipped);
+            failed.map_or(Ok(skipped), |(_, error)| Err(error))
+        }
+        None => args.engine.apply_with(txns, args.input.mode, keep, |tx| {
+            let _entered = applying.enter();
+            if let Some(journal) = &mut journal {
+                journal.append(&tx)?;
+                #[cfg(feature = "prometheus")]
+                if let Some(metrics) = &metrics {
+                    metrics.journaled();
+                }
+            }
+            let row = rejected.as_ref().map(|_| Row::new(&tx));
+            let emit = |event| {
+                if events.is_some() {
+                    emitted.push(event);
+                }
+            };
+            let apply = |engine: &mut TransactionEngine, tx| match &mut audit {
+                Some(audit) => audit.apply(engine, tx, emit),
+                None => engine.apply_with_events(tx, emit).map_err(Error::from),
+            };
+            #[cfg(feature = "prometheus")]
+            let outcome = match &metrics {
+                Some(metrics) => metrics.record(&mut engine, tx, apply),
+                None => apply(&mut engine, tx),
+            };
+            #[cfg(not(feature = "prometheus"))]
+            let outcome = apply(&mut engine, tx);
+            if let Some(events) = &mut events {
+                for event in emitted.drain(..) {
+                    events.write(&event)?;
+                }
+            }
+            if let (Some(rejected), Some(row)) = (&mut rejected, row) {
+                rejected.record(row, &outcome)?;
+            }
+            if let Some(checkpoints) = &mut checkpoints {
+                checkpoints.reached(&engine, position.get())?;
+            }
+            outcome.map(drop)
+        }),
+    };
+    // Side outputs are kept even when a transaction aborts the run, as they
+    // tell what happened up to that point.
+    if let Some(audit) = audit {
+        audit.finish()?;
+    }
+    if let Some(rejected) = rejected {
+        rejected.finish()?;
+    }
+    if let Some(events) = events {
+        events.finish()?;
+    }
+    drop(applying);
+    skipped?;
+    let _write = info_span!("write").entered();
+    #[cfg(feature = "sqlite")]
+    if let Some(store) = sqlite {
+        store.save_accounts(engine.accounts())?;
+    }
+
+    if let (Some(path), Some(base)) = (&args.rates, &args.base_currency) {
+        let rates = csv::read_rates(File::open(path)?)?;
+        engine.convert(&Rates::new(base, rates))?;
+    }
+
+    if args.state_hash {
+        // Written whatever the log level, as it is what was asked for.
+        eprintln!("state hash: {}", engine.state_hash());
+    }
+
+    let initial = match args.filter.only_changed {
+        true => Some(args.engine.initial_state()?),
+        false => None,
+    };
+    let accounts = args.sort.sort(engine.accounts().filter(|account| {
+        args.filter.matches(account)
+            && initial
+                .as_ref()
+                .is_none_or(|initial| changed(initial, account))
+    }));
+    let accounts = args.filter.top(accounts, &engine)?;
+
+    #[cfg(feature = "postgres")]
+    if let Some(url) = args
+        .output
+        .as_deref()
+        .and_then(|output| output.to_str())
+        .filter(|output| txns::postgres::is_url(output))
+    {
+        return txns::postgres::write(accounts.iter().copied(), url, &args.postgres_table);
+    }
+
+    let mut output = Output::with_buffer(args.output.as_deref(), buffer)?;
+
+    let errors = kept.iter();
+    let format = args.output_format.unwrap_or(match output.is_terminal() {
+        true => OutputFormat::Table,
+        false => OutputFormat::Csv,
+    });
+
+    let columns = match (&args.columns, args.metrics) {
+        (Some(columns), _) => Some(columns.clone()),
+        (None, true) => {
+            let mut columns = Column::present(accounts.iter().copied());
+            columns.extend(Column::METRICS);
+            Some(columns)
+        }
+        (None, false) => None,
+    };
+
+    if let Some(columns) = columns {
+        let table = Table::new(columns, &engine)?;
+        match format {
+            OutputFormat::Csv => table.write_csv(accounts.iter().copied(), &mut output)?,
+            OutputFormat::Table => table.write_text(accounts.iter().copied(), &mut output)?,
+            OutputFormat::Json => {
+                json::write(table.objects(accounts.iter().copied(), errors), &mut output)?
+            }
+            OutputFormat::Ndjson => {
+                json::write_lines(table.objects(accounts.iter().copied(), errors), &mut output)?
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--columns and --metrics only apply to CSV, JSON and table output",
+                )
+                .into())
+            }
+        }
+        return output.finish();
+    }
+
+    let records = accounts
+        .iter()
+        .copied()
+        .map(Record::from)
+        .chain(errors.map(Record::from));
+
+    match format {
+        OutputFormat::Csv => csv::write(accounts.iter().copied(), &mut output)?,
+        OutputFormat::Json => json::write(records, &mut output)?,
+        OutputFormat::Ndjson => json::write_lines(records, &mut output)?,
+        OutputFormat::Msgpack => msgpack::write(accounts.iter().copied(), &mut output)?,
+        OutputFormat::Protobuf => protobuf::write(accounts.iter().copied(), &mut output)?,
+        #[cfg(feature = "arrow")]
+        OutputFormat::Arrow => txns::arrow::write_stream(accounts.iter().copied(), &mut output)?,
+        #[cfg(feature = "arrow")]
+        OutputFormat::Feather => txns::arrow::write_file(accounts.iter().copied(), &mut output)?,
+        #[cfg(feature = "avro")]
+        OutputFormat::Avro => txns::avro::write(accounts.iter().copied(), &mut output)?,
+        OutputFormat::Table => Table::new(Column::present(accounts.iter().copied()), &engine)?
+            .write_text(accounts.iter().copied(), &mut output)?,
+    }
+
+    output.finish()
+}
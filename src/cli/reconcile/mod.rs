@@ -0,0 +1,175 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    path::PathBuf,
+};
+
+use clap::Args;
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use txns::{
+    csv,
+    domain::{transaction::TransactionKind, PRECISION},
+    engine::Outcome,
+    error::Result,
+};
+
+use super::{output::Output, EngineArgs, InputArgs, Mode};
+
+#[derive(Debug, Args)]
+pub struct ReconcileArgs {
+    /// Accounts to reconcile, as written by `txns process`.
+    accounts: PathBuf,
+    /// Balances to reconcile them with, like a bank statement, with `client`
+    /// and `balance` columns.
+    balances: PathBuf,
+    // Transactions the accounts were computed from, to look for the ones
+    // that could explain a difference. Unlike for other commands, none
+    // doesn't mean stdin.
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+    /// Largest difference between total and balance still taken as a match.
+    #[arg(long, default_value_t = Decimal::ZERO)]
+    tolerance: Decimal,
+    /// File to write the reconciliation to, defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct Line {
+    client: u16,
+    status: Status,
+    total: Option<Decimal>,
+    balance: Option<Decimal>,
+    /// Balance minus total.
+    difference: Decimal,
+    /// Transaction that would make up for the difference, like
+    /// `deposit of 10.0000`.
+    suggestion: Option<String>,
+    /// Skipped transactions of the suggested kind and amount, like `7;12`.
+    candidates: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Matched,
+    Mismatched,
+    /// Only in the balances.
+    Missing,
+    /// Only in the accounts.
+    Extra,
+}
+
+/// Match the total of every account with the balance of its client, in
+/// client order, suggesting what could be missing when they differ.
+///
+/// Given the transactions, the deposits and withdrawals the engine skipped
+/// that would make up for a difference are listed as candidates. The exit
+/// status is an error if anything doesn't match.
+pub fn run(args: ReconcileArgs) -> Result<()> {
+    let mut clients: BTreeMap<u16, (Option<Decimal>, Option<Decimal>)> = BTreeMap::new();
+    for account in csv::read_accounts(File::open(&args.accounts)?) {
+        let account = account?;
+        clients.entry(account.client()).or_default().0 = Some(account.total());
+    }
+    for (client, balance) in csv::read_balances(File::open(&args.balances)?)? {
+        clients.entry(client).or_default().1 = Some(balance);
+    }
+
+    let skipped = match args.input.inputs.is_empty() {
+        true => HashMap::new(),
+        false => skipped(&args)?,
+    };
+
+    let mut output = Output::open(args.output.as_deref())?;
+    let mut writer = ::csv::Writer::from_writer(&mut output);
+    let mut mismatches = 0;
+    for (client, (total, balance)) in clients {
+        let difference = balance.unwrap_or_default() - total.unwrap_or_default();
+        let mut difference = difference.round_dp(PRECISION);
+        difference.rescale(PRECISION);
+
+        let status = match (total, balance) {
+            (Some(_), None) => Status::Extra,
+            (None, _) => Status::Missing,
+            _ if difference.abs() <= args.tolerance => Status::Matched,
+            _ => Status::Mismatched,
+        };
+        if !matches!(status, Status::Matched) {
+            mismatches += 1;
+        }
+
+        let suggestion = match difference {
+            _ if matches!(status, Status::Matched) => None,
+            difference if difference > Decimal::ZERO => Some(format!("deposit of {difference}")),
+            difference if difference < Decimal::ZERO => {
+                Some(format!("withdrawal of {}", -difference))
+            }
+            _ => None,
+        };
+        let candidates = match suggestion {
+            Some(_) => skipped.get(&client).map_or(String::new(), |skipped| {
+                skipped
+                    .iter()
+                    .filter(|(_, effect)| (*effect - difference).abs() <= args.tolerance)
+                    .map(|(transaction_id, _)| transaction_id)
+                    .join(";")
+            }),
+            None => String::new(),
+        };
+
+        writer.serialize(Line {
+            client,
+            status,
+            total,
+            balance,
+            difference,
+            suggestion,
+            candidates,
+        })?;
+    }
+
+    writer.flush()?;
+    drop(writer);
+    output.finish()?;
+
+    if mismatches > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Deposits and withdrawals the engine doesn't apply, by client, with how
+/// they would have changed its total.
+fn skipped(args: &ReconcileArgs) -> Result<HashMap<u16, Vec<(u64, Decimal)>>> {
+    let mut engine = args.engine.engine()?;
+    let mut skipped: HashMap<u16, Vec<(u64, Decimal)>> = HashMap::new();
+
+    args.engine
+        .apply_with(args.input.read()?, Mode::Lenient, drop, |tx| {
+            let effect = match tx.kind {
+                TransactionKind::Deposit { amount } => Some(amount),
+                TransactionKind::Withdrawal { amount } => Some(-amount),
+                _ => None,
+            };
+            let (transaction_id, client) = (tx.transaction_id, tx.client);
+
+            let outcome = engine.apply(tx);
+            if let (false, Some(effect)) = (matches!(outcome, Ok(Outcome::Applied)), effect) {
+                skipped
+                    .entry(client)
+                    .or_default()
+                    .push((transaction_id, effect));
+            }
+
+            Ok(outcome.map(drop)?)
+        })?;
+
+    Ok(skipped)
+}
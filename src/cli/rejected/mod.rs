@@ -0,0 +1,88 @@
+use std::{io::Write, path::Path};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use txns::{
+    domain::transaction::Transaction,
+    engine::Outcome,
+    error::{Error, Result},
+};
+
+use super::output::Output;
+
+/// Side output of the transactions the engine ignored or rejected, each with
+/// a code telling why, as CSV or, for paths ending in `.ndjson` or `.jsonl`,
+/// newline-delimited JSON.
+pub enum Rejected {
+    Csv(Box<::csv::Writer<Output>>),
+    Ndjson(Output),
+}
+
+/// What to remember of a transaction until its outcome is known.
+#[derive(Serialize)]
+pub struct Row {
+    tx: u64,
+    client: u16,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    amount: Option<Decimal>,
+    decision: &'static str,
+    reason: &'static str,
+}
+
+impl Row {
+    pub fn new(tx: &Transaction) -> Self {
+        Self {
+            tx: tx.transaction_id,
+            client: tx.client,
+            kind: tx.kind.name(),
+            amount: tx.kind.amount(),
+            decision: "",
+            reason: "",
+        }
+    }
+}
+
+impl Rejected {
+    /// Start writing to `path`, which is only replaced once
+    /// [`finish`](Self::finish)ed.
+    pub fn open(path: &Path, buffer: usize) -> Result<Self> {
+        let output = Output::with_buffer(Some(path), buffer)?;
+        Ok(match path.extension() {
+            Some(extension) if extension == "ndjson" || extension == "jsonl" => {
+                Self::Ndjson(output)
+            }
+            _ => Self::Csv(Box::new(::csv::Writer::from_writer(output))),
+        })
+    }
+
+    /// Write `row` if `outcome` is anything but applied.
+    pub fn record(&mut self, mut row: Row, outcome: &Result<Outcome>) -> Result<()> {
+        (row.decision, row.reason) = match outcome {
+            Ok(Outcome::Applied) => return Ok(()),
+            Ok(Outcome::Ignored(ignored)) => ("ignored", ignored.code()),
+            Err(Error::BusinessError(error)) => ("rejected", error.code()),
+            Err(_) => ("rejected", "error"),
+        };
+
+        match self {
+            Self::Csv(writer) => writer.serialize(row)?,
+            Self::Ndjson(output) => {
+                serde_json::to_writer(&mut *output, &row)?;
+                output.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush every row and move the file into place.
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Self::Csv(writer) => writer
+                .into_inner()
+                .map_err(|error| error.into_error())?
+                .finish(),
+            Self::Ndjson(output) => output.finish(),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+
+use clap::Args;
+use tracing::warn;
+use txns::{
+    csv,
+    domain::account::Account,
+    engine::events::{Event, Replay},
+    error::Result,
+};
+
+use super::output::Output;
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// Events to replay, as written by `txns process --events`.
+    events: PathBuf,
+    /// Only replay the first `N` events, to get back to the accounts at an
+    /// earlier point.
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+    /// Accounts to compare the replayed ones with, as written by
+    /// `txns process`, printing every difference.
+    #[arg(long, value_name = "FILE")]
+    against: Option<PathBuf>,
+    /// File to write the replayed accounts to, defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Rebuild accounts from events alone and write them as CSV, in client
+/// order.
+///
+/// With `--against`, the exit status is an error if they differ from the
+/// given accounts, like when the engine didn't make the same decisions.
+pub fn run(args: ReplayArgs) -> Result<()> {
+    let mut replay = Replay::new();
+    let lines = BufReader::new(File::open(&args.events)?).lines();
+    for line in lines.take(args.limit.unwrap_or(usize::MAX)) {
+        let event: Event = serde_json::from_str(&line?)?;
+        replay.apply(&event)?;
+    }
+
+    let accounts: BTreeMap<_, _> = replay
+        .accounts()
+        .map(|account| (account.client(), account))
+        .collect();
+    let mut output = Output::open(args.output.as_deref())?;
+    csv::write(accounts.values().copied(), &mut output)?;
+    output.finish()?;
+
+    let Some(path) = &args.against else {
+        return Ok(());
+    };
+    let mut expected = BTreeMap::new();
+    for account in csv::read_accounts(File::open(path)?) {
+        let account = account?;
+        expected.insert(account.client(), account);
+    }
+
+    let balances = |account: &Account| {
+        (
+            account.available(),
+            account.held(),
+            account.total(),
+            account.locked(),
+        )
+    };
+    let clients: BTreeSet<_> = accounts.keys().chain(expected.keys()).collect();
+    let mut differences = 0;
+    for client in clients {
+        let message = match (accounts.get(client), expected.get(client)) {
+            (Some(replayed), Some(expected)) if balances(replayed) != balances(expected) => {
+                format!(
+                    "replayed {:?} but expected {:?} as (available, held, total, locked)",
+                    balances(replayed),
+                    balances(expected)
+                )
+            }
+            (Some(_), None) => "only replayed".to_owned(),
+            (None, Some(_)) => "never replayed".to_owned(),
+            _ => continue,
+        };
+        differences += 1;
+        warn!(client, "client {client}: {message}");
+    }
+
+    if differences > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use std::{io, net::SocketAddr, num::NonZeroUsize, path::PathBuf, sync::Arc};
+
+use clap::Args;
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tracing::info;
+use txns::{
+    csv,
+    engine::{journal::Journal, shard::ConcurrentEngine},
+    error::{Error, Result},
+    server::{self, Shared},
+};
+
+use super::{output::Output, EngineArgs, Precision};
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// Address to listen on.
+    #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+    /// Address to serve the gRPC service of `proto/txns.proto` on as well,
+    /// sharing the same engine.
+    #[cfg(feature = "grpc")]
+    #[arg(long, value_name = "ADDRESS")]
+    grpc_listen: Option<SocketAddr>,
+    /// Address to take transactions on over plain TCP as well, a CSV row or
+    /// JSON object per line, like with `nc host port < transactions.csv`.
+    #[arg(long, value_name = "ADDRESS")]
+    tcp_listen: Option<SocketAddr>,
+    /// Unix socket to take transactions and balance queries on as well, a
+    /// JSON object per line, answered in order. Removed once stopped.
+    #[cfg(unix)]
+    #[arg(long, value_name = "PATH")]
+    unix_listen: Option<PathBuf>,
+    /// How many shards clients are split into, the transactions of clients
+    /// of different shards being applied in parallel. With more than one,
+    /// transfers between clients of different shards are rejected, and
+    /// transaction ids are only checked against those of the same shard, so
+    /// disputes of another client's transaction may be ignored as unknown.
+    #[arg(long, value_name = "N", default_value = "1")]
+    shards: NonZeroUsize,
+    /// Write-ahead journal to append every transaction to before applying
+    /// it, and to apply again on startup, so accounts survive restarts.
+    /// Transactions are then applied one at a time, on a single shard.
+    #[arg(long, value_name = "FILE")]
+    journal: Option<PathBuf>,
+    /// What to do with amounts with more than four decimal places.
+    #[arg(long, value_enum, default_value_t)]
+    precision: Precision,
+    /// File to write the accounts to once the server stops, defaults to
+    /// stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    engine: EngineArgs,
+}
+
+/// Apply the transactions posted over HTTP, or sent over gRPC, plain TCP or
+/// a Unix socket, until stopped with Ctrl-C, then write the accounts as CSV, in
+/// client order.
+pub fn run(args: ServeArgs) -> Result<()> {
+    if args.journal.is_some() && args.shards.get() > 1 {
+        // Journaled transactions are applied again on a single shard, which
+        // takes transfers the shards would have rejected.
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "`--journal` applies transactions on a single shard",
+        )
+        .into());
+    }
+
+    let mut engine = args.engine.engine()?;
+    let journal = match &args.journal {
+        Some(path) => {
+            let (journal, count) = Journal::open(path, &mut engine)?;
+            info!(count, "applied {count} journaled transactions");
+            Some(journal)
+        }
+        None => None,
+    };
+    let engine = Arc::new(ConcurrentEngine::new(engine, args.shards.get())?);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            let listener = TcpListener::bind(args.listen).await?;
+            let address = listener.local_addr()?;
+            info!(%address, "listening on http://{address}");
+
+            let shutdown = || async {
+                // Without a handler, stopping the server is all that is left.
+                let _ = tokio::signal::ctrl_c().await;
+            };
+            let mut shared =
+                Shared::new(Arc::clone(&engine)).with_precision(args.precision.policy());
+            if let Some(journal) = journal {
+                shared = shared.with_journal(journal);
+            }
+            let lines = match args.tcp_listen {
+                Some(address) => {
+                    let lines = TcpListener::bind(address).await?;
+                    let address = lines.local_addr()?;
+                    info!(%address, "taking transaction lines on {address}");
+                    Some(lines)
+                }
+                None => None,
+            };
+            let lines = async {
+                match lines {
+                    Some(lines) => server::lines::serve(lines, shared.clone(), shutdown()).await,
+                    None => Ok(()),
+                }
+            };
+
+            #[cfg(unix)]
+            let unix = match &args.unix_listen {
+                Some(path) => {
+                    let unix = UnixListener::bind(path)?;
+                    info!(path = %path.display(), "answering on {}", path.display());
+                    Some(unix)
+                }
+                None => None,
+            };
+            let unix = async {
+                #[cfg(unix)]
+                if let Some(unix) = unix {
+                    let served = server::unix::serve(unix, shared.clone(), shutdown()).await;
+                    let path = args.unix_listen.as_deref().expect("the socket has a path");
+                    // Later runs could not bind it otherwise.
+                    std::fs::remove_file(path)?;
+                    return served;
+                }
+                Ok::<_, io::Error>(())
+            };
+
+            #[cfg(feature = "grpc")]
+            let grpc = match args.grpc_listen {
+                Some(address) => {
+                    let grpc = TcpListener::bind(address).await?;
+                    let address = grpc.local_addr()?;
+                    info!(%address, "serving gRPC on {address}");
+                    Some(grpc)
+                }
+                None => None,
+            };
+            let grpc = async {
+                #[cfg(feature = "grpc")]
+                if let Some(grpc) = grpc {
+                    return Ok(server::grpc::serve(grpc, shared.clone(), shutdown()).await?);
+                }
+                Ok::<_, Error>(())
+            };
+
+            let (http, grpc, lines, unix) = tokio::join!(
+                server::serve(listener, shared.clone(), shutdown()),
+                grpc,
+                lines,
+                unix,
+            );
+            http?;
+            grpc?;
+            lines?;
+            Ok::<_, Error>(unix?)
+        })?;
+
+    let engine = Arc::into_inner(engine).expect("the server is stopped");
+    let mut accounts = engine.accounts();
+    accounts.sort_by_key(|account| account.client());
+    let mut output = Output::open(args.output.as_deref())?;
+    csv::write(&accounts, &mut output)?;
+
+    output.finish()
+}
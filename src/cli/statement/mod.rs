@@ -0,0 +1,153 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::NaiveDate;
+use clap::{Args, ValueEnum};
+use txns::{
+    engine::statement::{Statement, Statements},
+    error::Result,
+};
+
+use super::{output::Output, EngineArgs, InputArgs};
+
+#[derive(Debug, Args)]
+pub struct StatementArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+    /// First day of the period, like `2024-01-01`.
+    #[arg(long)]
+    from: NaiveDate,
+    /// Last day of the period, like `2024-01-31`.
+    #[arg(long)]
+    to: NaiveDate,
+    /// Directory to write statements to, one file per client named after
+    /// it, like `42.csv`. Created if missing.
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+    /// Format of the statements.
+    #[arg(long, value_enum, default_value_t)]
+    format: StatementFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum StatementFormat {
+    /// `date`, `tx`, `type`, `amount` and `balance` columns, between an
+    /// `opening` and a `closing` row.
+    #[default]
+    Csv,
+    /// A page meant to be read by people.
+    Text,
+}
+
+/// Apply timestamped transactions and write the statement of every client
+/// over the period: its total balance before it, the transactions in it, and
+/// its total balance after it.
+///
+/// Transactions after `--to` are left out.
+pub fn run(args: StatementArgs) -> Result<()> {
+    let mut engine = args.engine.engine()?;
+    let mut statements = Statements::new(args.from, args.to);
+    args.engine
+        .apply_with(args.input.read()?, args.input.mode, drop, |tx| {
+            Ok(statements.process(&mut engine, tx)?)
+        })?;
+
+    fs::create_dir_all(&args.output_dir)?;
+    for statement in statements.finish(&engine) {
+        let extension = match args.format {
+            StatementFormat::Csv => "csv",
+            StatementFormat::Text => "txt",
+        };
+        let path = args
+            .output_dir
+            .join(Path::new(&statement.client.to_string()).with_extension(extension));
+
+        let mut output = Output::open(Some(&path))?;
+        match args.format {
+            StatementFormat::Csv => write_csv(&statement, &mut output)?,
+            StatementFormat::Text => write_text(&statement, &mut output)?,
+        }
+        output.finish()?;
+    }
+
+    Ok(())
+}
+
+fn write_csv(statement: &Statement, writer: impl Write) -> Result<()> {
+    let mut writer = ::csv::Writer::from_writer(writer);
+    writer.write_record(["date", "tx", "type", "amount", "balance"])?;
+
+    let opening = statement.opening.to_string();
+    writer.write_record([&statement.from.to_string(), "", "opening", "", &opening])?;
+    for item in &statement.items {
+        writer.write_record([
+            &item.timestamp.date_naive().to_string(),
+            &item.transaction_id.to_string(),
+            item.kind,
+            &item.amount.to_string(),
+            &item.balance.to_string(),
+        ])?;
+    }
+    let closing = statement.closing.to_string();
+    writer.write_record([&statement.to.to_string(), "", "closing", "", &closing])?;
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_text(statement: &Statement, mut writer: impl Write) -> Result<()> {
+    writeln!(
+        writer,
+        "Statement of client {}, {} to {}",
+        statement.client, statement.from, statement.to
+    )?;
+    writeln!(writer)?;
+
+    let rows: Vec<_> = statement
+        .items
+        .iter()
+        .map(|item| {
+            [
+                item.timestamp.date_naive().to_string(),
+                item.transaction_id.to_string(),
+                item.kind.to_owned(),
+                format!("{:+}", item.amount),
+                item.balance.to_string(),
+            ]
+        })
+        .collect();
+    let width = |index: usize, min: usize| {
+        rows.iter()
+            .map(|row| row[index].chars().count())
+            .fold(min, usize::max)
+    };
+    let widths = [width(0, 10), width(1, 0), width(2, 0), width(3, 0)];
+    let balance =
+        width(4, statement.opening.to_string().len()).max(statement.closing.to_string().len());
+    let label = widths.iter().sum::<usize>() + widths.len() * 2;
+
+    writeln!(
+        writer,
+        "{:<label$}{:>balance$}",
+        "Opening balance", statement.opening
+    )?;
+    for [date, tx, kind, amount, total] in &rows {
+        writeln!(
+            writer,
+            "{date:<0$}  {tx:>1$}  {kind:<2$}  {amount:>3$}  {total:>balance$}",
+            widths[0], widths[1], widths[2], widths[3]
+        )?;
+    }
+    writeln!(
+        writer,
+        "{:<label$}{:>balance$}",
+        "Closing balance", statement.closing
+    )?;
+
+    Ok(())
+}
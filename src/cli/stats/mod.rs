@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use txns::{
+    domain::{transaction::TransactionKind, PRECISION},
+    error::Result,
+};
+
+use super::{InputArgs, Mode};
+
+#[derive(Debug, Default)]
+struct Stats {
+    deposits: usize,
+    withdrawals: usize,
+    transfers: usize,
+    refunds: usize,
+    adjustments: usize,
+    disputes: usize,
+    resolves: usize,
+    chargebacks: usize,
+    unlocks: usize,
+    clients: HashSet<u16>,
+    skipped: usize,
+    /// Earliest and latest timestamps, if any transaction has one.
+    period: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    deposited: Decimal,
+    withdrawn: Decimal,
+    /// Smallest and biggest amounts of deposits, withdrawals, transfers and
+    /// refunds, if any.
+    range: Option<(Decimal, Decimal)>,
+    /// Sum of the amounts in `range`, to get their mean.
+    amounts: Decimal,
+}
+
+impl Stats {
+    fn amount(&mut self, amount: Decimal) {
+        self.range = Some(match self.range {
+            Some((min, max)) => (min.min(amount), max.max(amount)),
+            None => (amount, amount),
+        });
+        self.amounts += amount;
+    }
+}
+
+pub fn run(args: InputArgs) -> Result<()> {
+    let mut stats = Stats::default();
+
+    for tx in args.read()? {
+        let tx = match (tx, args.mode) {
+            (Ok(tx), _) => tx,
+            (Err(_), Mode::Lenient) => {
+                stats.skipped += 1;
+                continue;
+            }
+            (Err(error), Mode::Strict) => return Err(error),
+        };
+
+        stats.clients.insert(tx.client);
+        if let Some(timestamp) = tx.timestamp {
+            stats.period = Some(match stats.period {
+                Some((first, last)) => (first.min(timestamp), last.max(timestamp)),
+                None => (timestamp, timestamp),
+            });
+        }
+        match tx.kind {
+            TransactionKind::Deposit { amount } => {
+                stats.deposits += 1;
+                stats.deposited += amount;
+                stats.amount(amount);
+            }
+            TransactionKind::Withdrawal { amount } => {
+                stats.withdrawals += 1;
+                stats.withdrawn += amount;
+                stats.amount(amount);
+            }
+            TransactionKind::Transfer { to_client, amount } => {
+                stats.clients.insert(to_client);
+                stats.transfers += 1;
+                stats.amount(amount);
+            }
+            TransactionKind::Refund { amount } => {
+                stats.refunds += 1;
+                stats.amount(amount);
+            }
+            TransactionKind::Adjustment { .. } => stats.adjustments += 1,
+            TransactionKind::Dispute { .. } => stats.disputes += 1,
+            TransactionKind::Resolve => stats.resolves += 1,
+            TransactionKind::Chargeback => stats.chargebacks += 1,
+            TransactionKind::Unlock => stats.unlocks += 1,
+        }
+    }
+
+    let total = stats.deposits
+        + stats.withdrawals
+        + stats.transfers
+        + stats.refunds
+        + stats.adjustments
+        + stats.disputes
+        + stats.resolves
+        + stats.chargebacks
+        + stats.unlocks;
+
+    println!("transactions: {total}");
+    println!("clients: {}", stats.clients.len());
+    if let Some((first, last)) = stats.period {
+        println!("period: {} to {}", first.to_rfc3339(), last.to_rfc3339());
+    }
+    println!("deposits: {}", stats.deposits);
+    println!("withdrawals: {}", stats.withdrawals);
+    println!("transfers: {}", stats.transfers);
+    println!("refunds: {}", stats.refunds);
+    println!("adjustments: {}", stats.adjustments);
+    println!("disputes: {}", stats.disputes);
+    println!("resolves: {}", stats.resolves);
+    println!("chargebacks: {}", stats.chargebacks);
+    println!("unlocks: {}", stats.unlocks);
+    println!("deposited: {}", stats.deposited);
+    println!("withdrawn: {}", stats.withdrawn);
+
+    // Disputes can only reference deposits, withdrawals and transfers.
+    let disputable = stats.deposits + stats.withdrawals + stats.transfers;
+    if disputable > 0 {
+        let rate = |count: usize| count as f64 / disputable as f64 * 100.0;
+        println!("dispute rate: {:.2}%", rate(stats.disputes));
+        println!("chargeback rate: {:.2}%", rate(stats.chargebacks));
+    }
+    if let Some((min, max)) = stats.range {
+        let count = disputable + stats.refunds;
+        let mean = (stats.amounts / Decimal::from(count)).round_dp(PRECISION);
+        println!("min amount: {min}");
+        println!("max amount: {max}");
+        println!("mean amount: {mean}");
+    }
+    if args.mode == Mode::Lenient {
+        println!("skipped: {}", stats.skipped);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,258 @@
+//! What a run logs to stderr, like skipped transactions, and spans of its
+//! stages, like reading the input or writing accounts, printed to stderr or
+//! exported to an OpenTelemetry collector.
+
+use std::{fmt, io};
+
+use clap::{ArgAction, Args, ValueEnum};
+#[cfg(feature = "otlp")]
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+#[cfg(feature = "otlp")]
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+#[cfg(feature = "otlp")]
+use tokio::runtime::Runtime;
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    Event, Span, Subscriber,
+};
+use tracing_subscriber::{
+    filter::{filter_fn, Targets},
+    fmt::{
+        format::{FmtSpan, Writer},
+        FmtContext, FormatEvent, FormatFields,
+    },
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    Layer,
+};
+use txns::error::Result;
+
+#[derive(Debug, Args)]
+pub struct TelemetryArgs {
+    /// Format of what is logged to stderr.
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Least severe events to log, like `warn` to only log how many
+    /// transactions were skipped and errors. Defaults to `info`.
+    #[arg(long, global = true, value_enum, conflicts_with_all = ["verbose", "quiet"])]
+    log_level: Option<LogLevel>,
+    /// Log more: every file read with `-v`, and every transaction with `-vv`.
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Log less: only how many transactions were skipped with `-q`, and only
+    /// errors with `-qq`.
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    quiet: u8,
+    /// Print how long each stage of the run took to stderr once it ends.
+    #[arg(long, global = true)]
+    trace: bool,
+    /// OpenTelemetry collector to export the spans of each stage to, over
+    /// gRPC, like `http://localhost:4317`.
+    #[cfg(feature = "otlp")]
+    #[arg(
+        long,
+        global = true,
+        value_name = "URL",
+        env = "OTEL_EXPORTER_OTLP_ENDPOINT"
+    )]
+    otlp_endpoint: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// The message of each event, on a line of its own.
+    Text,
+    /// A JSON object per line, with the timestamp, level and fields of each
+    /// event, like the `input` a file was read from.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    const LEVELS: [Self; 5] = [
+        Self::Error,
+        Self::Warn,
+        Self::Info,
+        Self::Debug,
+        Self::Trace,
+    ];
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// Keeps exporting spans until dropped, which flushes those left.
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    otlp: Option<(TracerProvider, Runtime)>,
+}
+
+impl TelemetryArgs {
+    /// Start logging and collecting spans as asked.
+    pub fn init(&self) -> Result<Telemetry> {
+        // Only events of txns itself are logged, not those of dependencies.
+        let targets = Targets::new().with_target("txns", LevelFilter::from(self.log_level()));
+        let log = tracing_subscriber::fmt::layer().with_writer(io::stderr);
+        let log = match self.log_format {
+            LogFormat::Text => log.event_format(Message).boxed(),
+            LogFormat::Json => log
+                .json()
+                .flatten_event(true)
+                .with_current_span(false)
+                .with_span_list(false)
+                .boxed(),
+        };
+        let trace = self.trace.then(|| {
+            let trace = tracing_subscriber::fmt::layer()
+                .with_writer(io::stderr)
+                .with_span_events(FmtSpan::CLOSE)
+                .with_target(false);
+            let trace = match self.log_format {
+                LogFormat::Text => trace.boxed(),
+                LogFormat::Json => trace.json().flatten_event(true).boxed(),
+            };
+            trace.with_filter(filter_fn(|metadata| {
+                metadata.is_span() && metadata.target().starts_with("txns")
+            }))
+        });
+        let subscriber = tracing_subscriber::registry()
+            .with(log.with_filter(targets))
+            .with(trace);
+
+        #[cfg(feature = "otlp")]
+        let (subscriber, otlp) = match &self.otlp_endpoint {
+            Some(endpoint) => {
+                // The exporter sends batches from a runtime of its own, so
+                // the run itself stays synchronous.
+                let runtime = tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .enable_all()
+                    .build()?;
+                let _guard = runtime.enter();
+                let exporter = SpanExporter::builder()
+                    .with_tonic()
+                    .with_endpoint(endpoint)
+                    .build()?;
+                let provider = TracerProvider::builder()
+                    .with_batch_exporter(exporter, runtime::Tokio)
+                    .with_resource(Resource::new([KeyValue::new("service.name", "txns")]))
+                    .build();
+                let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("txns"));
+
+                (subscriber.with(Some(layer)), Some((provider, runtime)))
+            }
+            None => (subscriber.with(None), None),
+        };
+
+        subscriber.init();
+
+        Ok(Telemetry {
+            #[cfg(feature = "otlp")]
+            otlp,
+        })
+    }
+
+    /// Level of `--log-level`, or `info` made more verbose by `-v` or
+    /// quieter by `-q`, each as many times as given.
+    fn log_level(&self) -> LogLevel {
+        let info = LogLevel::Info as usize;
+        let level = (info + usize::from(self.verbose))
+            .saturating_sub(self.quiet.into())
+            .min(LogLevel::LEVELS.len() - 1);
+
+        self.log_level.unwrap_or(LogLevel::LEVELS[level])
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some((provider, runtime)) = self.otlp.take() {
+            let _guard = runtime.enter();
+            if let Err(error) = provider.shutdown() {
+                eprintln!("could not export traces: {error}");
+            }
+        }
+    }
+}
+
+/// Events formatted as their message alone, like `skipped 3 invalid
+/// transactions`.
+struct Message;
+
+impl<S, N> FormatEvent<S, N> for Message
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut visitor = MessageVisitor {
+            writer: &mut writer,
+            result: Ok(()),
+        };
+        event.record(&mut visitor);
+        visitor.result?;
+
+        writeln!(writer)
+    }
+}
+
+struct MessageVisitor<'a, 'w> {
+    writer: &'a mut Writer<'w>,
+    result: fmt::Result,
+}
+
+impl Visit for MessageVisitor<'_, '_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.result = write!(self.writer, "{value:?}");
+        }
+    }
+}
+
+/// Transactions of an input read within `span`, which tells how long reading
+/// them took apart from applying them.
+pub struct Traced<I> {
+    txns: I,
+    span: Span,
+}
+
+impl<I> Traced<I> {
+    pub fn new(txns: I, span: Span) -> Self {
+        Self { txns, span }
+    }
+}
+
+impl<I: Iterator> Iterator for Traced<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.span.in_scope(|| self.txns.next())
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use txns::{
+    domain::{error::Error as DomainError, transaction::TransactionKind},
+    error::{Error, Result},
+};
+
+use super::{EngineArgs, InputArgs, Precision};
+
+#[derive(Debug, Args)]
+pub struct ValidateArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+    /// How to print the outcome.
+    #[arg(long, value_enum, default_value_t)]
+    report: Report,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum Report {
+    /// How many transactions are valid.
+    #[default]
+    Text,
+    /// A JSON object with the counts and every violation.
+    Json,
+}
+
+/// Outcome of a validation, as printed by `--report json`.
+#[derive(Serialize)]
+struct Summary {
+    transactions: usize,
+    valid: usize,
+    violations: Vec<Violation>,
+}
+
+#[derive(Serialize)]
+struct Violation {
+    /// Line of the row at fault, for CSV input.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u64>,
+    message: String,
+}
+
+impl From<&Error> for Violation {
+    fn from(error: &Error) -> Self {
+        Self {
+            line: match error {
+                Error::InvalidRow { line, .. } => *line,
+                _ => None,
+            },
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Run transactions through the engine without writing any account.
+///
+/// On top of what the engine rejects, amounts with more than four decimal
+/// places and disputes, resolves, chargebacks or refunds of transactions
+/// that don't exist are violations. Strict mode fails on the first invalid
+/// transaction, while lenient mode reports all of them. Either way, the
+/// exit status is an error if any transaction was skipped.
+pub fn run(mut args: ValidateArgs) -> Result<()> {
+    args.input.precision = Precision::Reject;
+
+    let mut engine = args.engine.engine()?;
+    let mut known = HashSet::new();
+    let mut count = 0;
+    let txns = args.input.read()?.inspect(|_| count += 1);
+
+    // Violations are only kept for the JSON report, which lists them.
+    let mut violations = Vec::new();
+    let keep = |error: Error| {
+        if matches!(args.report, Report::Json) {
+            violations.push(Violation::from(&error));
+        }
+    };
+    let result = args.engine.apply_with(txns, args.input.mode, keep, |tx| {
+        match tx.kind {
+            TransactionKind::Deposit { .. }
+            | TransactionKind::Withdrawal { .. }
+            | TransactionKind::Transfer { .. } => {
+                let transaction_id = tx.transaction_id;
+                engine.process(tx)?;
+                known.insert(transaction_id);
+            }
+            TransactionKind::Dispute { .. }
+            | TransactionKind::Resolve
+            | TransactionKind::Chargeback
+            | TransactionKind::Refund { .. }
+                if !known.contains(&tx.transaction_id) =>
+            {
+                return Err(DomainError::UnknownTransaction {
+                    client: tx.client,
+                    transaction_id: tx.transaction_id,
+                }
+                .into())
+            }
+            _ => engine.process(tx)?,
+        }
+
+        Ok(())
+    });
+
+    let skipped = match (result, args.report) {
+        (Ok(skipped), _) => skipped,
+        (Err(error), Report::Json) => {
+            violations.push(Violation::from(&error));
+            1
+        }
+        (Err(error), Report::Text) => return Err(error),
+    };
+
+    match args.report {
+        Report::Text if skipped == 0 => println!("{count} transactions are valid"),
+        Report::Text => println!("{} of {count} transactions are valid", count - skipped),
+        Report::Json => {
+            let summary = Summary {
+                transactions: count,
+                valid: count - skipped,
+                violations,
+            };
+            serde_json::to_writer_pretty(std::io::stdout(), &summary)?;
+            println!();
+        }
+    }
+
+    if skipped > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
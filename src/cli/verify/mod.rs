@@ -0,0 +1,77 @@
+use std::{collections::HashSet, fs::File, path::PathBuf};
+
+use clap::Args;
+use rust_decimal::Decimal;
+use txns::{csv, domain::transaction::TransactionKind, error::Result};
+
+use super::InputArgs;
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// Accounts to check, as written by `txns process`.
+    accounts: PathBuf,
+    // Transactions the accounts were computed from, to check locked accounts
+    // against. Unlike for other commands, none doesn't mean stdin.
+    #[command(flatten)]
+    input: InputArgs,
+}
+
+/// Check the invariants of every account, printing each violation.
+///
+/// Totals must be the sum of available and held funds, and held funds can't
+/// be negative. With transactions, locked accounts must have had a
+/// chargeback. The exit status is an error if any invariant is broken.
+pub fn run(args: VerifyArgs) -> Result<()> {
+    let charged_back = match args.input.inputs.is_empty() {
+        true => None,
+        false => {
+            let mut clients = HashSet::new();
+            for tx in args.input.read()? {
+                let tx = tx?;
+                if tx.kind == TransactionKind::Chargeback {
+                    clients.insert(tx.client);
+                }
+            }
+            Some(clients)
+        }
+    };
+
+    let mut count = 0;
+    let mut violations = 0;
+    for account in csv::read_accounts(File::open(&args.accounts)?) {
+        let account = account?;
+        let client = account.client();
+        count += 1;
+
+        let mut violation = |message: String| {
+            violations += 1;
+            println!("client {client}: {message}");
+        };
+        if account.total() != account.available() + account.held() {
+            violation(format!(
+                "total {} is not available {} plus held {}",
+                account.total(),
+                account.available(),
+                account.held()
+            ));
+        }
+        if account.held() < Decimal::ZERO {
+            violation(format!("held {} is negative", account.held()));
+        }
+        if let Some(clients) = &charged_back {
+            if account.locked() && !clients.contains(&client) {
+                violation("locked without any chargeback".to_owned());
+            }
+        }
+    }
+
+    match violations {
+        0 => println!("{count} accounts are consistent"),
+        _ => println!("{violations} violations in {count} accounts"),
+    }
+    if violations > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
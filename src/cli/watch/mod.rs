@@ -0,0 +1,318 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs, future, io, iter,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Args;
+use notify::{RecursiveMode, Watcher};
+use tokio::{
+    sync::mpsc,
+    time::{self, Instant},
+};
+use tracing::{info, warn};
+use txns::{
+    csv,
+    engine::{savepoint::Savepoint, TransactionEngine},
+    error::Result,
+};
+
+use super::{
+    checkpoint::{self, Checkpoints},
+    output::Output,
+    EngineArgs, InputArgs,
+};
+
+/// Directory files are moved to once applied.
+const PROCESSED: &str = "processed";
+/// Directory files are moved to when they can't be read, or hold an invalid
+/// transaction in strict mode.
+const FAILED: &str = "failed";
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// Directory to watch for files of transactions.
+    #[arg(value_name = "DIR")]
+    dir: PathBuf,
+    /// How long a file must go unchanged before being read, in milliseconds,
+    /// so files still being written aren't read halfway.
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    settle: u64,
+    /// File to save the state of the engine to once each file is applied,
+    /// and to start from if it exists, so accounts survive restarts.
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<PathBuf>,
+    /// File to write the accounts to once stopped, defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+}
+
+/// Apply each file landing in a directory, the ones already there first, in
+/// lexicographic order, until stopped with Ctrl-C, then write the accounts as
+/// CSV, in client order.
+///
+/// Files are moved to `processed/` once applied, or to `failed/` otherwise,
+/// in which case none of their transactions are, under a name like
+/// `transactions.1.csv` if the directory already has one of the same name.
+/// Hidden files are left alone, so they can be written under a hidden name
+/// and renamed once done.
+pub fn run(mut args: WatchArgs) -> Result<()> {
+    if !args.input.inputs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "`txns watch` reads the files of a single directory",
+        )
+        .into());
+    }
+
+    // Events name files after the directory as watched, so they can be told
+    // apart from those of its subdirectories.
+    args.dir = fs::canonicalize(&args.dir)?;
+    for dir in [PROCESSED, FAILED] {
+        fs::create_dir_all(args.dir.join(dir))?;
+    }
+
+    let mut engine = args.engine.engine()?;
+    let resumed = match &args.checkpoint {
+        Some(path) if path.exists() => {
+            let (resumed, moved) = checkpoint::resume_noted(&mut engine, path)?;
+            args.finish_move(&moved)?;
+            resumed
+        }
+        _ => 0,
+    };
+    let checkpoints = args
+        .checkpoint
+        .as_deref()
+        .map(|path| Checkpoints::new(path, 1, resumed));
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            let (sender, mut events) = mpsc::unbounded_channel();
+            let mut watcher = notify::recommended_watcher(move |event| {
+                // The receiver only goes away once stopped.
+                let _ = sender.send(event);
+            })?;
+            watcher.watch(&args.dir, RecursiveMode::NonRecursive)?;
+            info!(dir = %args.dir.display(), "watching {}", args.dir.display());
+
+            let settle = Duration::from_millis(args.settle);
+            let mut files = args.files()?;
+            let mut watch = Watch::new(&args, &mut engine, checkpoints, resumed);
+            let stop = tokio::signal::ctrl_c();
+            tokio::pin!(stop);
+
+            loop {
+                let mut ready: Vec<_> = files
+                    .iter()
+                    .filter(|(_, changed)| changed.elapsed() >= settle)
+                    .map(|(path, _)| path.clone())
+                    .collect();
+                ready.sort();
+                for path in ready {
+                    files.remove(&path);
+                    if let Err(error) = watch.apply(&path) {
+                        let input = path.display();
+                        warn!(%input, "{input} failed: {error}");
+                    }
+                }
+
+                let next = files.values().min().map(|changed| *changed + settle);
+                let settled = async {
+                    match next {
+                        Some(next) => time::sleep_until(next).await,
+                        None => future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    _ = &mut stop => break,
+                    event = events.recv() => {
+                        let Some(event) = event else { break };
+                        match event {
+                            Ok(event) => {
+                                let now = Instant::now();
+                                for path in event.paths {
+                                    if args.watched(&path) {
+                                        files.insert(path, now);
+                                    }
+                                }
+                            }
+                            Err(error) => {
+                                warn!("could not watch {}: {error}", args.dir.display())
+                            }
+                        }
+                    }
+                    _ = settled => {}
+                }
+            }
+
+            Ok::<_, txns::error::Error>(())
+        })?;
+
+    let mut accounts: Vec<_> = engine.accounts().collect();
+    accounts.sort_by_key(|account| account.client());
+    let mut output = Output::open(args.output.as_deref())?;
+    csv::write(accounts, &mut output)?;
+
+    output.finish()
+}
+
+impl WatchArgs {
+    /// Files already in the directory, as if they had just landed.
+    fn files(&self) -> Result<HashMap<PathBuf, Instant>> {
+        let now = Instant::now();
+        let mut files = HashMap::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if self.watched(&path) {
+                files.insert(path, now);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Move the file the checkpoint was saved for to `processed/`, if a crash
+    /// left it in the directory, so it isn't applied twice. `moved` is the
+    /// name of the file, then that of where it goes, separated by a slash.
+    fn finish_move(&self, moved: &str) -> Result<()> {
+        let Some((name, destination)) = moved.split_once('/') else {
+            return Ok(());
+        };
+        let (path, destination) = (
+            self.dir.join(name),
+            self.dir.join(PROCESSED).join(destination),
+        );
+        if path.is_file() && !destination.exists() {
+            info!(input = %path.display(), "moving {} applied before", path.display());
+            fs::rename(path, destination)?;
+        }
+
+        Ok(())
+    }
+
+    /// Path in subdirectory `dir` to move the file `name` to, with a number
+    /// before its extension if one of the same name is there already.
+    fn destination(&self, dir: &str, name: &OsStr) -> PathBuf {
+        let dir = self.dir.join(dir);
+        let name = Path::new(name);
+        let stem = name.file_stem().unwrap_or(name.as_os_str());
+
+        let numbered = (1..).map(|number| {
+            let mut unique = stem.to_owned();
+            unique.push(format!(".{number}"));
+            if let Some(extension) = name.extension() {
+                unique.push(".");
+                unique.push(extension);
+            }
+            dir.join(unique)
+        });
+
+        iter::once(dir.join(name))
+            .chain(numbered)
+            .find(|path| !path.exists())
+            .expect("some number is free")
+    }
+
+    /// Whether `path` is a file of the directory to apply.
+    fn watched(&self, path: &Path) -> bool {
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+        path.parent() == Some(&self.dir) && !hidden
+    }
+}
+
+/// Engine the files of a directory are applied to, one after the other.
+struct Watch<'a> {
+    args: &'a WatchArgs,
+    engine: &'a mut TransactionEngine,
+    checkpoints: Option<Checkpoints>,
+    /// How many files were applied, counting those before the checkpoint.
+    position: u64,
+    /// Id of the first transaction of the next bank statement.
+    first_tx: u64,
+}
+
+impl<'a> Watch<'a> {
+    fn new(
+        args: &'a WatchArgs,
+        engine: &'a mut TransactionEngine,
+        checkpoints: Option<Checkpoints>,
+        position: u64,
+    ) -> Self {
+        Self {
+            args,
+            engine,
+            checkpoints,
+            position,
+            first_tx: args.input.first_tx,
+        }
+    }
+
+    /// Apply the file at `path` and move it out of the way, unless it is gone
+    /// already, like a file moved out of the directory.
+    ///
+    /// Once applied, the checkpoint is saved along with where the file goes
+    /// before it is moved there, so a crash in between doesn't apply it
+    /// twice.
+    fn apply(&mut self, path: &Path) -> Result<()> {
+        let Some(name) = path.file_name().filter(|_| path.is_file()) else {
+            return Ok(());
+        };
+
+        let input = path.display();
+        info!(%input, "reading {input}");
+        let mut savepoint = Savepoint::default();
+        let first_tx = self.first_tx;
+        let engine = &mut *self.engine;
+        let applied = self
+            .args
+            .input
+            .read_file(Some(path), &mut self.first_tx)
+            .and_then(|txns| {
+                self.args
+                    .engine
+                    .apply_with(txns, self.args.input.mode, drop, |tx| {
+                        engine.apply_saved(tx, &mut savepoint)?;
+                        Ok(())
+                    })
+            });
+
+        let destination = match applied {
+            Ok(_) => {
+                let destination = self.args.destination(PROCESSED, name);
+                self.position += 1;
+                if let Some(checkpoints) = &mut self.checkpoints {
+                    let moved = format!(
+                        "{}/{}",
+                        name.to_string_lossy(),
+                        destination
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy(),
+                    );
+                    checkpoints.save_noted(self.engine, self.position, &moved)?;
+                }
+                destination
+            }
+            Err(error) => {
+                warn!(%input, "could not apply {input}: {error}");
+                self.engine.rollback(savepoint)?;
+                self.first_tx = first_tx;
+                self.args.destination(FAILED, name)
+            }
+        };
+
+        Ok(fs::rename(path, destination)?)
+    }
+}
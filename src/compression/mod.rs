@@ -0,0 +1,74 @@
+//! Transparent decompression of input.
+
+use std::io::{self, Cursor, Read};
+
+use flate2::read::MultiGzDecoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Extensions of compressed files, which don't tell the format of their
+/// content.
+pub const EXTENSIONS: [&str; 2] = ["gz", "zst"];
+
+/// Whether `head`, the start of some input, is gzip or zstd data.
+pub fn is_compressed(head: &[u8]) -> bool {
+    head.starts_with(GZIP_MAGIC) || head.starts_with(ZSTD_MAGIC)
+}
+
+/// Wrap `reader` into a decoder if it starts like gzip or zstd data, so it
+/// is decompressed on the fly, or leave it as is otherwise.
+pub fn decompress<'a>(mut reader: impl Read + 'a) -> io::Result<Box<dyn Read + 'a>> {
+    let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+    (&mut reader)
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    let reader = Cursor::new(magic.clone()).chain(reader);
+
+    Ok(if magic.starts_with(GZIP_MAGIC) {
+        Box::new(MultiGzDecoder::new(reader))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::Decoder::new(reader)?)
+    } else {
+        Box::new(reader)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    const INPUT: &str = "type,client,tx,amount\ndeposit,1,1,1.0\n";
+
+    fn read(input: Vec<u8>) -> String {
+        let mut output = String::new();
+        decompress(input.as_slice())
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(INPUT.as_bytes()).unwrap();
+
+        assert_eq!(read(encoder.finish().unwrap()), INPUT);
+    }
+
+    #[test]
+    fn zstd() {
+        assert_eq!(read(zstd::encode_all(INPUT.as_bytes(), 0).unwrap()), INPUT);
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(read(INPUT.into()), INPUT);
+        assert_eq!(read(b"a".to_vec()), "a");
+    }
+}
@@ -1,24 +1,581 @@
-use std::io::{Read, Write};
+//! CSV input and output.
 
-use csv::{Reader, Result, Writer};
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    io::{Read, Write},
+    iter,
+    str::FromStr,
+};
 
-use crate::domain::{account::Account, transaction::Transaction};
+use csv::{ByteRecord, DeserializeError, Reader, ReaderBuilder, StringRecord, Trim, Writer};
+use itertools::{Either, Itertools};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::{
+    domain::{
+        account::Account,
+        transaction::{Transaction, TransactionRecord},
+    },
+    error::{Error, Result},
+};
+
+/// Other names of columns found in exports, along with the column they stand
+/// for.
+const ALIASES: [(&str, &str); 5] = [
+    ("tx_id", "tx"),
+    ("txid", "tx"),
+    ("transaction_id", "tx"),
+    ("client_id", "client"),
+    ("transaction_type", "type"),
+];
+
+/// How fields are laid out in CSV input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dialect {
+    /// Byte separating fields, like `b','` or `b'\t'`.
+    pub delimiter: u8,
+    /// Whether amounts use a comma as decimal separator, like `100,1234`.
+    pub decimal_comma: bool,
+    /// Column each header stands for, on top of the built-in aliases like
+    /// `tx_id` for `tx`.
+    pub aliases: HashMap<String, String>,
+}
+
+impl Dialect {
+    /// Layout of European exports, with fields separated by semicolons and
+    /// decimal commas.
+    pub fn eu() -> Self {
+        Self {
+            delimiter: b';',
+            decimal_comma: true,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            decimal_comma: false,
+            aliases: HashMap::new(),
+        }
+    }
+}
 
 /// Parse [`Transaction`]s from a reader.
 ///
-/// This function assumes the content is a valid CSV, otherwise it will throw an
-/// error.
-pub fn read(reader: impl Read) -> Result<Vec<Transaction>> {
-    Reader::from_reader(reader).into_deserialize().collect()
+/// Records are deserialized lazily, one per call to [`Iterator::next`], so the
+/// whole input is never held in memory. Each item is an error if the row is
+/// not a valid transaction, pointing to the line and field at fault.
+pub fn read(reader: impl Read) -> impl Iterator<Item = Result<Transaction>> {
+    read_with(reader, Dialect::default())
+}
+
+/// Like [`read`], with fields laid out according to `dialect`.
+///
+/// Spaces around fields are ignored, and headers are matched regardless of
+/// case and [aliases](Dialect::aliases). Rows can leave out trailing empty
+/// fields or have more than the header, and columns nothing reads are
+/// ignored.
+///
+/// Rows are read into a single buffer and deserialized from it, so reading a
+/// transaction only allocates for its currency, if any.
+pub fn read_with(reader: impl Read, dialect: Dialect) -> impl Iterator<Item = Result<Transaction>> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(dialect.delimiter)
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+
+    match reader.headers() {
+        Ok(headers) => {
+            let headers = Headers::new(&normalize(headers, &dialect.aliases));
+            let mut record = ByteRecord::new();
+
+            Either::Left(iter::from_fn(move || {
+                match reader.read_byte_record(&mut record) {
+                    Ok(true) => {}
+                    Ok(false) => return None,
+                    Err(source) => {
+                        return Some(Err(Error::InvalidRow {
+                            line: source.position().map(|position| position.line()),
+                            field: None,
+                            record: None,
+                            source,
+                        }))
+                    }
+                }
+                for _ in record.len()..headers.names.len() {
+                    record.push_field(b"");
+                }
+
+                Some(parse(&record, &headers, dialect.decimal_comma))
+            }))
+        }
+        Err(error) => Either::Right(iter::once(Err(error.into()))),
+    }
+}
+
+/// Name of the column each header stands for: the header itself, trimmed and
+/// in lowercase, unless it is an alias.
+pub(crate) fn normalize(headers: &StringRecord, aliases: &HashMap<String, String>) -> StringRecord {
+    headers
+        .iter()
+        .map(|header| {
+            let header = header.trim().to_lowercase();
+            let alias = aliases
+                .iter()
+                .map(|(alias, column)| (alias.as_str(), column.as_str()))
+                .chain(ALIASES)
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(&header));
+
+            match alias {
+                Some((_, column)) => column.to_owned(),
+                None => header,
+            }
+        })
+        .collect()
+}
+
+/// Headers of tabular input, as given by [`normalize`].
+pub(crate) struct Headers {
+    names: ByteRecord,
+    /// The same, but for `amount`, so deserializing a row leaves it out.
+    fields: ByteRecord,
+    amount: Option<usize>,
+}
+
+impl Headers {
+    pub(crate) fn new(names: &StringRecord) -> Self {
+        let amount = names.iter().position(|header| header == "amount");
+        let fields = names
+            .iter()
+            .enumerate()
+            .map(|(index, header)| match Some(index) == amount {
+                true => "",
+                false => header,
+            })
+            .collect();
+
+        Self {
+            names: names.as_byte_record().clone(),
+            fields,
+            amount,
+        }
+    }
+}
+
+/// Deserialize a row, read by [`read`] or built from another tabular format,
+/// into a [`Transaction`].
+///
+/// The amount is parsed straight from its text, as csv would make a float out
+/// of it first, and everything else is borrowed from the row. Rows that fail
+/// are deserialized again as a whole, to tell what is wrong with them.
+pub(crate) fn parse(
+    record: &ByteRecord,
+    headers: &Headers,
+    decimal_comma: bool,
+) -> Result<Transaction> {
+    let amount = match headers.amount.and_then(|amount| record.get(amount)) {
+        Some(amount) => parse_amount(amount, decimal_comma),
+        None => Some(None),
+    };
+    let tx = amount.and_then(|amount| {
+        let mut tx: TransactionRecord = record.deserialize(Some(&headers.fields)).ok()?;
+        tx.amount = amount;
+        Transaction::try_from(tx).ok()
+    });
+
+    match (tx, headers.amount) {
+        (Some(tx), _) => Ok(tx),
+        (None, Some(amount)) if decimal_comma => {
+            diagnose(&with_decimal_point(record, amount), &headers.names)
+        }
+        (None, _) => diagnose(record, &headers.names),
+    }
+}
+
+/// Parse an amount, like `1.5`, `1,5` with `decimal_comma` or `1e3`, empty
+/// fields being missing amounts. Returns `None` for invalid ones.
+fn parse_amount(field: &[u8], decimal_comma: bool) -> Option<Option<Decimal>> {
+    // Amounts are short enough to fix their decimal comma on the stack.
+    let mut fixed = [0; 64];
+    let field = match decimal_comma {
+        true => {
+            let fixed = fixed.get_mut(..field.len())?;
+            for (byte, &field) in fixed.iter_mut().zip(field) {
+                *byte = if field == b',' { b'.' } else { field };
+            }
+            &*fixed
+        }
+        false => field,
+    };
+
+    match std::str::from_utf8(field).ok()? {
+        "" => Some(None),
+        amount => Decimal::from_str(amount)
+            .or_else(|_| Decimal::from_scientific(amount))
+            .ok()
+            .map(Some),
+    }
+}
+
+/// Replace the decimal comma of field `amount` by a point.
+fn with_decimal_point(record: &ByteRecord, amount: usize) -> ByteRecord {
+    let mut fixed: ByteRecord = record
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match index == amount {
+            true => field
+                .iter()
+                .map(|&byte| if byte == b',' { b'.' } else { byte })
+                .collect::<Vec<_>>(),
+            false => field.to_owned(),
+        })
+        .collect();
+    fixed.set_position(record.position().cloned());
+
+    fixed
+}
+
+/// Deserialize a row through serde as a whole, to report which field is
+/// invalid and why.
+fn diagnose(record: &ByteRecord, headers: &ByteRecord) -> Result<Transaction> {
+    record
+        .deserialize(Some(headers))
+        .map_err(|source| Error::InvalidRow {
+            line: record.position().map(|position| position.line()),
+            field: match source.kind() {
+                csv::ErrorKind::Deserialize { err, .. } => err
+                    .field()
+                    .or_else(|| failed_field(record, headers, err))
+                    .and_then(|index| headers.get(index as usize))
+                    .map(|field| String::from_utf8_lossy(field).into_owned()),
+                _ => None,
+            },
+            record: Some(record.iter().map(String::from_utf8_lossy).join(",")),
+            source,
+        })
+}
+
+/// Find the field of a deserialization error csv couldn't locate.
+///
+/// csv only knows the field of errors it raises itself, not of those raised by
+/// the field types, like an unknown transaction type. Since fields are read in
+/// order, the failing one is the last of the shortest prefix of the record
+/// that fails the same way.
+fn failed_field(
+    record: &ByteRecord,
+    headers: &ByteRecord,
+    error: &DeserializeError,
+) -> Option<u64> {
+    (1..=record.len()).find_map(|len| {
+        let prefix: ByteRecord = record.iter().take(len).collect();
+        let headers: ByteRecord = headers.iter().take(len).collect();
+
+        match prefix.deserialize::<TransactionRecord>(Some(&headers)) {
+            Err(prefix_error) => match prefix_error.kind() {
+                csv::ErrorKind::Deserialize { err, .. } if err.kind() == error.kind() => {
+                    Some(len as u64 - 1)
+                }
+                _ => None,
+            },
+            Ok(_) => None,
+        }
+    })
+}
+
+/// Parse [`Account`]s from a reader, as written by [`write`].
+pub fn read_accounts(reader: impl Read) -> impl Iterator<Item = Result<Account>> {
+    Reader::from_reader(reader)
+        .into_deserialize()
+        .map(|account| Ok(account?))
+}
+
+/// Overdraft limit of a client, as read by [`read_overdrafts`].
+#[derive(Deserialize)]
+struct Overdraft {
+    client: u16,
+    limit: Decimal,
+}
+
+/// Parse per-client overdraft limits from a reader, with `client` and `limit`
+/// columns.
+pub fn read_overdrafts(reader: impl Read) -> Result<HashMap<u16, Decimal>> {
+    Reader::from_reader(reader)
+        .into_deserialize()
+        .map_ok(|Overdraft { client, limit }| (client, limit))
+        .map(|overdraft| Ok(overdraft?))
+        .collect()
+}
+
+/// Exchange rate between two currencies, as read by [`read_rates`].
+#[derive(Deserialize)]
+struct Rate {
+    from: String,
+    to: String,
+    rate: Decimal,
+}
+
+/// Parse exchange rates from a reader, with `from`, `to` and `rate` columns,
+/// `rate` being how many units of `to` one unit of `from` is worth.
+pub fn read_rates(reader: impl Read) -> Result<HashMap<(String, String), Decimal>> {
+    Reader::from_reader(reader)
+        .into_deserialize()
+        .map_ok(|Rate { from, to, rate }| ((from, to), rate))
+        .map(|rate| Ok(rate?))
+        .collect()
+}
+
+/// Balance of a client according to someone else, as read by
+/// [`read_balances`].
+#[derive(Deserialize)]
+struct Balance {
+    client: u16,
+    balance: Decimal,
 }
 
-pub fn write(accounts: Vec<Account>, writer: impl Write) -> Result<()> {
+/// Parse the balances of clients from a reader, like a bank statement, with
+/// `client` and `balance` columns.
+pub fn read_balances(reader: impl Read) -> Result<HashMap<u16, Decimal>> {
+    Reader::from_reader(reader)
+        .into_deserialize()
+        .map_ok(|Balance { client, balance }| (client, balance))
+        .map(|balance| Ok(balance?))
+        .collect()
+}
+
+/// Serialize [`Account`]s as CSV rows, including a header, into a writer.
+pub fn write(
+    accounts: impl IntoIterator<Item = impl Borrow<Account>>,
+    writer: impl Write,
+) -> csv::Result<()> {
     let mut writer = Writer::from_writer(writer);
 
     for account in accounts {
-        writer.serialize(account)?;
+        writer.serialize(account.borrow())?;
     }
 
     writer.flush()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::transaction::TransactionKind;
+
+    #[test]
+    fn invalid_row_diagnostics() {
+        let input = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,abc\n";
+
+        let error = read(input.as_bytes()).nth(1).unwrap().unwrap_err();
+
+        let Error::InvalidRow {
+            line,
+            field,
+            record,
+            ..
+        } = error
+        else {
+            panic!("unexpected error: {error:?}");
+        };
+        assert_eq!(line, Some(3));
+        assert_eq!(field.as_deref(), Some("amount"));
+        assert_eq!(record.as_deref(), Some("deposit,1,2,abc"));
+    }
+
+    #[test]
+    fn tab_delimited() {
+        let input = "type\tclient\ttx\tamount\ndeposit\t1\t1\t1.5\n";
+        let dialect = Dialect {
+            delimiter: b'\t',
+            ..Default::default()
+        };
+
+        let tx = read_with(input.as_bytes(), dialect)
+            .next()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            tx.kind,
+            TransactionKind::Deposit {
+                amount: Decimal::new(15, 1)
+            }
+        );
+    }
+
+    #[test]
+    fn header_aliases() {
+        let input = " Type , Client_ID ,tx_id, Amount,ref\n deposit , 1 ,7, 1.5 ,\n";
+        let dialect = Dialect {
+            aliases: HashMap::from([("Ref".to_owned(), "currency".to_owned())]),
+            ..Default::default()
+        };
+
+        let tx = read_with(input.as_bytes(), dialect)
+            .next()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(tx.client, 1);
+        assert_eq!(tx.transaction_id, 7);
+        assert_eq!(
+            tx.kind,
+            TransactionKind::Deposit {
+                amount: Decimal::new(15, 1)
+            }
+        );
+        assert_eq!(tx.currency, None);
+    }
+
+    #[test]
+    fn ragged_rows() {
+        let input =
+            "type,client,tx,amount,vendor\ndeposit,1,1,1.0,x\ndispute,1,1\nresolve,1,1,,x,y\n";
+
+        let txns: Vec<_> = read(input.as_bytes()).map(Result::unwrap).collect();
+
+        assert_eq!(txns[1].kind, TransactionKind::Dispute { amount: None });
+        assert_eq!(txns[2].kind, TransactionKind::Resolve);
+    }
+
+    #[test]
+    fn decimal_comma() {
+        let input = "type;client;tx;amount\ndeposit;1;1;100,1234\ndeposit;1;2;1,x\n";
+
+        let txns: Vec<_> = read_with(input.as_bytes(), Dialect::eu()).collect();
+
+        assert_eq!(
+            txns[0].as_ref().unwrap().kind,
+            TransactionKind::Deposit {
+                amount: Decimal::new(1001234, 4)
+            }
+        );
+        let Err(Error::InvalidRow { line, field, .. }) = &txns[1] else {
+            panic!("unexpected result: {:?}", txns[1]);
+        };
+        assert_eq!(*line, Some(3));
+        assert_eq!(field.as_deref(), Some("amount"));
+    }
+
+    #[test]
+    fn exact_amounts() {
+        // More digits than a float holds.
+        let input = "type,client,tx,amount\ndeposit,1,1,1234567890123.4567\ndeposit,1,2,1e3\n";
+
+        let amounts: Vec<_> = read(input.as_bytes())
+            .map(|tx| match tx.unwrap().kind {
+                TransactionKind::Deposit { amount } => amount,
+                kind => panic!("unexpected kind: {kind:?}"),
+            })
+            .collect();
+
+        assert_eq!(
+            amounts,
+            [Decimal::new(12345678901234567, 4), Decimal::new(1000, 0)]
+        );
+    }
+
+    #[test]
+    fn unknown_type_diagnostics() {
+        let input = "type,client,tx,amount\npayout,1,1,1.0\n";
+
+        let error = read(input.as_bytes()).next().unwrap().unwrap_err();
+
+        let Error::InvalidRow { line, field, .. } = error else {
+            panic!("unexpected error: {error:?}");
+        };
+        assert_eq!(line, Some(2));
+        assert_eq!(field.as_deref(), Some("type"));
+    }
+
+    #[test]
+    fn transfer() {
+        let input = "type,client,tx,amount,to_client\ntransfer,1,1,2.5,2\ndeposit,1,2,1.0,\n";
+
+        let txns: Vec<_> = read(input.as_bytes()).map(Result::unwrap).collect();
+
+        assert_eq!(
+            txns[0].kind,
+            TransactionKind::Transfer {
+                to_client: 2,
+                amount: Decimal::new(25, 1)
+            }
+        );
+        assert_eq!(
+            txns[1].kind,
+            TransactionKind::Deposit {
+                amount: Decimal::ONE
+            }
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        let input = "type,client,tx,amount,timestamp\n\
+                     deposit,1,1,1.0,1700000000\n\
+                     deposit,1,2,1.0,2023-11-14T22:13:20Z\n\
+                     deposit,1,3,1.0,\n";
+
+        let timestamps: Vec<_> = read(input.as_bytes())
+            .map(|tx| tx.unwrap().timestamp)
+            .collect();
+
+        let timestamp = chrono::DateTime::from_timestamp(1_700_000_000, 0);
+        assert_eq!(timestamps, [timestamp, timestamp, None]);
+    }
+
+    #[test]
+    fn invalid_timestamp_diagnostics() {
+        let input = "type,client,tx,amount,timestamp\ndeposit,1,1,1.0,yesterday\n";
+
+        let error = read(input.as_bytes()).next().unwrap().unwrap_err();
+
+        let Error::InvalidRow { field, .. } = error else {
+            panic!("unexpected error: {error:?}");
+        };
+        assert_eq!(field.as_deref(), Some("timestamp"));
+    }
+
+    #[test]
+    fn accounts_round_trip() {
+        let input = "client,available,held,total,locked\n1,1.5000,0.0000,1.5000,true\n";
+
+        let accounts: Vec<_> = read_accounts(input.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+        let mut output = Vec::new();
+        write(&accounts, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), input);
+    }
+
+    #[test]
+    fn overdrafts() {
+        let input = "client,limit\n1,10.0\n2,2.5\n";
+
+        let overdrafts = read_overdrafts(input.as_bytes()).unwrap();
+
+        assert_eq!(
+            overdrafts,
+            HashMap::from([(1, Decimal::TEN), (2, Decimal::new(25, 1))])
+        );
+    }
+
+    #[test]
+    fn balances() {
+        let input = "client,balance\n1,10.0\n2,-2.5\n";
+
+        let balances = read_balances(input.as_bytes()).unwrap();
+
+        assert_eq!(
+            balances,
+            HashMap::from([(1, Decimal::TEN), (2, Decimal::new(-25, 1))])
+        );
+    }
+}
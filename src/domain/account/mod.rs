@@ -1,571 +1,354 @@
-use std::collections::{HashMap, HashSet};
-
-use itertools::Itertools;
 use rust_decimal::Decimal;
-use serde::Serialize;
+use serde::{Deserialize, Serialize, Serializer};
 
 use super::{
     error::{Error, Result},
-    transaction::{Transaction, TransactionKind},
+    PRECISION,
 };
 
-#[derive(Debug, Serialize)]
+/// Funds of a single client.
+///
+/// `total` is always `available + held`, and a locked account is one that
+/// suffered a chargeback.
+///
+/// Some columns are only reported along with the feature they belong to:
+///
+/// - `overdraft`, the overdraft limit, when limits are configured.
+/// - `adjusted`, the net amount of adjustments, when they are audited.
+/// - `fees`, the fees paid, when there are fees.
+/// - `currency` and `base_total`, the total in the base currency, when balances
+///   are converted.
+/// - `interest`, when interest is accrued.
+///
+/// Accounts can be read back from that output, e.g. to start from a
+/// snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
+    client: u16,
+    #[serde(serialize_with = "serialize_rounded")]
+    available: Decimal,
+    #[serde(serialize_with = "serialize_rounded")]
+    held: Decimal,
+    #[serde(serialize_with = "serialize_rounded")]
+    total: Decimal,
+    locked: bool,
+    #[serde(
+        serialize_with = "serialize_rounded_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    overdraft: Option<Decimal>,
+    #[serde(
+        serialize_with = "serialize_rounded_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    adjusted: Option<Decimal>,
+    #[serde(
+        serialize_with = "serialize_rounded_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    fees: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+    #[serde(
+        serialize_with = "serialize_rounded_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    base_total: Option<Decimal>,
+    #[serde(
+        serialize_with = "serialize_rounded_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    interest: Option<Decimal>,
+}
+
+/// Every field of an [`Account`] as is, unlike its usual serialization which
+/// rounds balances, to save and restore the state of an engine.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Account")]
+pub(crate) struct Exact {
     client: u16,
     available: Decimal,
     held: Decimal,
     total: Decimal,
     locked: bool,
+    overdraft: Option<Decimal>,
+    adjusted: Option<Decimal>,
+    fees: Option<Decimal>,
+    currency: Option<String>,
+    base_total: Option<Decimal>,
+    interest: Option<Decimal>,
+}
+
+/// Balances are kept with full precision while processing and only rounded
+/// when reported, be it through serialization or getters.
+///
+/// The result always has exactly [`PRECISION`] decimal places, so `1.5` is
+/// reported as `1.5000`.
+fn rounded(value: Decimal) -> Decimal {
+    let mut value = value.round_dp(PRECISION);
+    value.rescale(PRECISION);
+    value
+}
+
+fn serialize_rounded<S: Serializer>(
+    value: &Decimal,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    Serialize::serialize(&rounded(*value), serializer)
+}
+
+fn serialize_rounded_option<S: Serializer>(
+    value: &Option<Decimal>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    Serialize::serialize(&value.map(rounded), serializer)
 }
 
 impl Account {
-    pub fn from_transactions(txns: impl IntoIterator<Item = Transaction>) -> Result<Vec<Self>> {
-        let client_txns = txns.into_iter().into_group_map_by(|tx| tx.client);
+    pub(crate) fn new(client: u16) -> Self {
+        Self {
+            client,
+            available: Decimal::ZERO,
+            held: Decimal::ZERO,
+            total: Decimal::ZERO,
+            locked: false,
+            overdraft: None,
+            adjusted: None,
+            fees: None,
+            currency: None,
+            base_total: None,
+            interest: None,
+        }
+    }
 
-        client_txns
-            .into_iter()
-            .map(|(client, txns)| {
-                Self::process_client_transactions(client, txns.into_iter().collect())
-            })
-            .collect()
+    /// Let available funds go as low as `-limit`.
+    pub(crate) fn with_overdraft(mut self, limit: Decimal) -> Self {
+        self.overdraft = Some(limit);
+        self
     }
 
-    fn process_client_transactions(client: u16, txns: HashSet<Transaction>) -> Result<Account> {
-        const DEFAULT_PRECISION: u32 = 4;
+    /// Keep track of the net amount of adjustments.
+    pub(crate) fn with_adjustments(mut self) -> Self {
+        self.adjusted = Some(Decimal::ZERO);
+        self
+    }
 
-        let tx_amounts: HashMap<_, _> = txns
-            .iter()
-            .filter_map(|tx| match tx.kind {
-                TransactionKind::Deposit { amount } => Some((tx.transaction_id, amount)),
-                TransactionKind::Withdrawal { amount } => Some((tx.transaction_id, -amount)),
-                _ => None,
-            })
-            .collect();
+    /// Keep track of the fees paid.
+    pub(crate) fn with_fees(mut self) -> Self {
+        self.fees = Some(Decimal::ZERO);
+        self
+    }
 
-        let mut total: Decimal = tx_amounts.values().sum();
+    /// Client that owns this account.
+    pub fn client(&self) -> u16 {
+        self.client
+    }
 
-        if total < Decimal::ZERO {
-            return Err(Error::NoAvailableFundsToWithdraw { client });
-        }
+    /// Funds that can be withdrawn.
+    pub fn available(&self) -> Decimal {
+        rounded(self.available)
+    }
 
-        let (available, held, locked) = txns
-            .iter()
-            .filter(|tx| {
-                !matches!(
-                    tx.kind,
-                    TransactionKind::Deposit { .. } | TransactionKind::Withdrawal { .. }
-                )
-            })
-            .fold(
-                (total, Decimal::ZERO, false),
-                |(mut avail, mut held, mut locked), tx| {
-                    let amount = tx_amounts.get(&tx.transaction_id).unwrap_or(&Decimal::ZERO);
-
-                    match tx.kind {
-                        TransactionKind::Dispute => {
-                            // Disputing a withdrawal is a tricky question, but I think it should
-                            // add value in held field, subtract from avail, and leave total with
-                            // the same value.
-                            if amount < &Decimal::ZERO {
-                                held -= amount;
-                                total -= amount;
-                            } else {
-                                avail -= amount;
-                                held += amount;
-                            }
-                        }
-                        TransactionKind::Resolve => {
-                            if amount < &Decimal::ZERO {
-                                held += amount;
-                                avail -= amount;
-                            } else {
-                                avail += amount;
-                                held -= amount;
-                            }
-                        }
-                        TransactionKind::Chargeback => {
-                            locked = true;
-                        }
-                        _ => unreachable!("processed txs was filtered above"),
-                    }
-
-                    (avail, held, locked)
-                },
-            );
+    /// Funds frozen by open disputes.
+    pub fn held(&self) -> Decimal {
+        rounded(self.held)
+    }
 
-        Ok(Account {
-            client,
-            total: total.round_dp(DEFAULT_PRECISION),
-            available: available.round_dp(DEFAULT_PRECISION),
-            held: held.round_dp(DEFAULT_PRECISION),
-            locked,
-        })
+    /// Sum of available and held funds.
+    pub fn total(&self) -> Decimal {
+        rounded(self.total)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use rust_decimal_macros::dec;
-
-    use super::*;
-
-    #[test]
-    fn single_deposit() {
-        let transactions = vec![Transaction {
-            client: 1,
-            transaction_id: 1,
-            kind: TransactionKind::Deposit {
-                amount: dec!(100.0),
-            },
-        }];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        assert_eq!(accounts.len(), 1);
-
-        let account = &accounts[0];
-        assert_eq!(account.client, 1);
-        assert_eq!(account.total, dec!(100.0));
-        assert_eq!(account.available, dec!(100.0));
-        assert_eq!(account.held, dec!(0.0));
-        assert!(!account.locked);
+    /// Whether a chargeback happened on this account.
+    pub fn locked(&self) -> bool {
+        self.locked
     }
 
-    #[test]
-    fn deposit_and_withdrawal() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Withdrawal { amount: dec!(30.0) },
-            },
-        ];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
-
-        assert_eq!(account.total, dec!(70.0));
-        assert_eq!(account.available, dec!(70.0));
-        assert_eq!(account.held, dec!(0.0));
-        assert!(!account.locked);
+    /// How far below zero available funds can go, if overdrafts are
+    /// configured.
+    pub fn overdraft(&self) -> Option<Decimal> {
+        self.overdraft.map(rounded)
     }
 
-    #[test]
-    fn withdrawal_exceeds_balance() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit { amount: dec!(50.0) },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Withdrawal {
-                    amount: dec!(100.0),
-                },
-            },
-        ];
+    /// Net amount of the adjustments applied to this account, if they are
+    /// audited.
+    pub fn adjusted(&self) -> Option<Decimal> {
+        self.adjusted.map(rounded)
+    }
 
-        let result = Account::from_transactions(transactions);
-        let error = result.unwrap_err();
+    /// Fees paid so far, if there are fees.
+    pub fn fees(&self) -> Option<Decimal> {
+        self.fees.map(rounded)
+    }
 
-        // Used this to skip deriving Eq to Error.
-        assert!(matches!(
-            error,
-            Error::NoAvailableFundsToWithdraw { client: 1 }
-        ));
+    /// Currency of the balances, once converted.
+    pub fn currency(&self) -> Option<&str> {
+        self.currency.as_deref()
     }
 
-    #[test]
-    fn dispute_transaction() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Deposit { amount: dec!(50.0) },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Dispute,
-            },
-        ];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
-
-        assert_eq!(account.total, dec!(150.0));
-        assert_eq!(account.available, dec!(50.0));
-        assert_eq!(account.held, dec!(100.0));
-        assert!(!account.locked);
+    /// Total converted into the base currency, once converted.
+    pub fn base_total(&self) -> Option<Decimal> {
+        self.base_total.map(rounded)
     }
 
-    #[test]
-    fn dispute_and_resolve() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Dispute,
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Resolve,
-            },
-        ];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
-
-        assert_eq!(account.total, dec!(100.0));
-        assert_eq!(account.available, dec!(100.0));
-        assert_eq!(account.held, dec!(0.0));
-        assert!(!account.locked);
+    /// Interest credited to the account, once accrued.
+    pub fn interest(&self) -> Option<Decimal> {
+        self.interest.map(rounded)
     }
 
-    #[test]
-    fn dispute_and_chargeback() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Deposit { amount: dec!(50.0) },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Dispute,
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Chargeback,
-            },
-        ];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
-
-        assert_eq!(account.total, dec!(150.0));
-        assert_eq!(account.available, dec!(50.0));
-        assert_eq!(account.held, dec!(100.0));
-        assert!(account.locked);
+    /// Credit accrued interest to available funds.
+    pub(crate) fn accrue(&mut self, interest: Decimal) -> Result<()> {
+        self.update(0, interest, Decimal::ZERO, interest)
+            .map_err(|_| Error::InterestOverflow {
+                client: self.client,
+            })?;
+        self.interest = Some(interest);
+        Ok(())
     }
 
-    #[test]
-    fn dispute_withdrawal() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Withdrawal { amount: dec!(30.0) },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Dispute,
-            },
-        ];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
-
-        // IDK how to deal with disputed withdrawals, but this looks like it makes sense
-        // to me: although the user has 100 in account, he could use only 70,
-        // because 30 is held.
-        assert_eq!(account.total, dec!(100.0));
-        assert_eq!(account.available, dec!(70.0));
-        assert_eq!(account.held, dec!(30.0));
-        assert!(!account.locked);
+    /// Report the balances as being in `currency`, worth `rate` units of the
+    /// base currency each.
+    pub(crate) fn convert(&mut self, currency: String, rate: Decimal) -> Result<()> {
+        let base_total = self
+            .total
+            .checked_mul(rate)
+            .ok_or(Error::ConversionOverflow {
+                client: self.client,
+            })?;
+
+        self.currency = Some(currency);
+        self.base_total = Some(base_total);
+        Ok(())
     }
 
-    #[test]
-    fn dispute_and_resolve_withdrawal() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Withdrawal { amount: dec!(30.0) },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Dispute,
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Resolve,
-            },
-        ];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
-
-        dbg!(&account);
-        assert_eq!(account.total, dec!(100.0));
-        assert_eq!(account.available, dec!(100.0));
-        assert_eq!(account.held, dec!(0.0));
-        assert!(!account.locked);
+    pub(crate) fn deposit(&mut self, transaction_id: u64, amount: Decimal) -> Result<()> {
+        self.update(transaction_id, amount, Decimal::ZERO, amount)
     }
 
-    #[test]
-    fn dispute_nonexistent_transaction() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 999,
-                kind: TransactionKind::Dispute,
-            },
-        ];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
-
-        assert_eq!(account.total, dec!(100.0));
-        assert_eq!(account.available, dec!(100.0));
-        assert_eq!(account.held, dec!(0.0));
-        assert!(!account.locked);
+    /// Correct available funds by a signed amount, keeping track of it if
+    /// adjustments are audited.
+    pub(crate) fn adjust(&mut self, transaction_id: u64, amount: Decimal) -> Result<()> {
+        let adjusted = self
+            .adjusted
+            .map(|adjusted| {
+                adjusted.checked_add(amount).ok_or(Error::BalanceOverflow {
+                    client: self.client,
+                    tx: transaction_id,
+                })
+            })
+            .transpose()?;
+
+        self.update(transaction_id, amount, Decimal::ZERO, amount)?;
+        self.adjusted = adjusted;
+        Ok(())
     }
 
-    #[test]
-    fn multiple_clients() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 2,
-                transaction_id: 2,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(200.0),
+    /// Take a fee from available funds, which can go as low as `-overdraft`
+    /// like with withdrawals.
+    pub(crate) fn charge_fee(
+        &mut self,
+        transaction_id: u64,
+        fee: Decimal,
+        overdraft: Option<Decimal>,
+    ) -> Result<()> {
+        let fees = self.fees.unwrap_or_default();
+        let fees = fees.checked_add(fee).ok_or(Error::BalanceOverflow {
+            client: self.client,
+            tx: transaction_id,
+        })?;
+
+        self.withdraw(transaction_id, fee, overdraft)?;
+        self.fees = Some(fees);
+        Ok(())
+    }
+
+    /// Withdraw available funds, allowing them to go as low as `-overdraft`.
+    /// There is no limit if `overdraft` is `None`.
+    pub(crate) fn withdraw(
+        &mut self,
+        transaction_id: u64,
+        amount: Decimal,
+        overdraft: Option<Decimal>,
+    ) -> Result<()> {
+        let spendable = match overdraft {
+            Some(overdraft) => Some(self.available.checked_add(overdraft).ok_or(
+                Error::BalanceOverflow {
+                    client: self.client,
+                    tx: transaction_id,
                 },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 3,
-                kind: TransactionKind::Withdrawal { amount: dec!(20.0) },
-            },
-            Transaction {
-                client: 2,
-                transaction_id: 2,
-                kind: TransactionKind::Dispute,
-            },
-        ];
-
-        let mut accounts = Account::from_transactions(transactions).unwrap();
-        accounts.sort_by_key(|a| a.client);
-
-        assert_eq!(accounts.len(), 2);
-
-        // Client 1
-        assert_eq!(accounts[0].client, 1);
-        assert_eq!(accounts[0].total, dec!(80.0));
-        assert_eq!(accounts[0].available, dec!(80.0));
-        assert_eq!(accounts[0].held, dec!(0.0));
-        assert!(!accounts[0].locked);
-
-        // Client 2
-        assert_eq!(accounts[1].client, 2);
-        assert_eq!(accounts[1].total, dec!(200.0));
-        assert_eq!(accounts[1].available, dec!(0.0));
-        assert_eq!(accounts[1].held, dec!(200.0));
-        assert!(!accounts[1].locked);
+            )?),
+            None => None,
+        };
+        if spendable.is_some_and(|spendable| spendable < amount) {
+            return Err(Error::NoAvailableFundsToWithdraw {
+                client: self.client,
+            });
+        }
+
+        self.update(transaction_id, -amount, Decimal::ZERO, -amount)
     }
 
-    #[test]
-    fn only_disputes_and_resolves() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Dispute,
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Resolve,
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 3,
-                kind: TransactionKind::Chargeback,
-            },
-        ];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
-
-        assert_eq!(account.total, dec!(0.0));
-        assert_eq!(account.available, dec!(0.0));
-        assert_eq!(account.held, dec!(0.0));
-        assert!(account.locked);
+    /// Hold a disputed amount. Withdrawals are represented by negative
+    /// amounts.
+    pub(crate) fn hold(&mut self, transaction_id: u64, amount: Decimal) -> Result<()> {
+        // Disputing a withdrawal adds its value back to held and total, leaving
+        // avail untouched: the client can't spend what is under dispute.
+        if amount < Decimal::ZERO {
+            self.update(transaction_id, Decimal::ZERO, -amount, -amount)
+        } else {
+            self.update(transaction_id, -amount, amount, Decimal::ZERO)
+        }
     }
 
-    #[test]
-    fn complex_scenario() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(1000.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(500.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 3,
-                kind: TransactionKind::Withdrawal {
-                    amount: dec!(200.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Dispute,
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 4,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Resolve,
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 3,
-                kind: TransactionKind::Dispute,
-            },
-        ];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
-
-        assert_eq!(account.total, dec!(1600.0));
-        assert_eq!(account.available, dec!(1400.0));
-        assert_eq!(account.held, dec!(200.0));
-        assert!(!account.locked);
+    /// Release a previously held amount back to available funds.
+    pub(crate) fn release(&mut self, transaction_id: u64, amount: Decimal) -> Result<()> {
+        self.update(transaction_id, amount.abs(), -amount.abs(), Decimal::ZERO)
     }
 
-    #[test]
-    fn precision_handling() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 2,
-                kind: TransactionKind::Withdrawal {
-                    amount: dec!(50.567891),
-                },
-            },
-        ];
+    /// Remove a held amount from the account for good, locking it.
+    ///
+    /// Whether it was a deposit or a withdrawal, the disputed amount sits in
+    /// held and total, so both shrink by it.
+    pub(crate) fn charge_back(&mut self, transaction_id: u64, amount: Decimal) -> Result<()> {
+        self.remove_held(transaction_id, amount)?;
+        self.lock();
+        Ok(())
+    }
 
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
+    /// Remove a held amount from the account for good, without locking it.
+    pub(crate) fn remove_held(&mut self, transaction_id: u64, amount: Decimal) -> Result<()> {
+        self.update(transaction_id, Decimal::ZERO, -amount.abs(), -amount.abs())
+    }
 
-        assert_eq!(account.total, dec!(49.4321));
+    pub(crate) fn lock(&mut self) {
+        self.locked = true;
     }
 
-    #[test]
-    fn multiple_disputes_same_transaction() {
-        let transactions = vec![
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Deposit {
-                    amount: dec!(100.0),
-                },
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Dispute,
-            },
-            Transaction {
-                client: 1,
-                transaction_id: 1,
-                kind: TransactionKind::Dispute,
-            },
-        ];
-
-        let accounts = Account::from_transactions(transactions).unwrap();
-        let account = &accounts[0];
-
-        assert_eq!(account.total, dec!(100.0));
-        assert_eq!(account.available, dec!(0.0));
-        assert_eq!(account.held, dec!(100.0));
-        assert!(!account.locked);
+    pub(crate) fn unlock(&mut self) {
+        self.locked = false;
+    }
+
+    /// Add the given deltas to the balances, leaving them untouched if any of
+    /// them overflows.
+    fn update(
+        &mut self,
+        transaction_id: u64,
+        available: Decimal,
+        held: Decimal,
+        total: Decimal,
+    ) -> Result<()> {
+        let overflow = || Error::BalanceOverflow {
+            client: self.client,
+            tx: transaction_id,
+        };
+
+        let available = self.available.checked_add(available).ok_or_else(overflow)?;
+        let held = self.held.checked_add(held).ok_or_else(overflow)?;
+        let total = self.total.checked_add(total).ok_or_else(overflow)?;
+
+        self.available = available;
+        self.held = held;
+        self.total = total;
+        Ok(())
     }
 }
@@ -1,9 +1,144 @@
 use thiserror::Error;
 
+/// Business rule violations found while applying transactions.
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("withdrawn amount is bigger than deposited amount for client {client}")]
     NoAvailableFundsToWithdraw { client: u16 },
+    #[error("transaction {transaction_id} rejected: account of client {client} is locked")]
+    AccountLocked { client: u16, transaction_id: u64 },
+    #[error(
+        "client {client} references transaction {transaction_id}, which belongs to client {owner}"
+    )]
+    ForeignTransaction {
+        client: u16,
+        transaction_id: u64,
+        owner: u16,
+    },
+    #[error("transaction {transaction_id} of client {client} reuses an existing id")]
+    DuplicateTransaction { client: u16, transaction_id: u64 },
+    #[error("transaction {transaction_id} has a non-positive amount {amount}")]
+    NonPositiveAmount {
+        transaction_id: u64,
+        amount: rust_decimal::Decimal,
+    },
+    #[error(
+        "transaction {transaction_id} has more than {} decimal places in {amount}",
+        super::PRECISION
+    )]
+    ExcessivePrecision {
+        transaction_id: u64,
+        amount: rust_decimal::Decimal,
+    },
+    #[error("transaction {tx} overflows the balances of client {client}")]
+    BalanceOverflow { client: u16, tx: u64 },
+    #[error("transaction {transaction_id} rejected: client {client} failed earlier")]
+    ClientFailed { client: u16, transaction_id: u64 },
+    #[error("transaction {transaction_id} requires an amount")]
+    MissingAmount { transaction_id: u64 },
+    #[error("refund rejected: transaction {transaction_id} is not an undisputed deposit")]
+    NotRefundable { transaction_id: u64 },
+    #[error(
+        "refund of {amount} rejected: only {refundable} of deposit {transaction_id} is left to \
+         refund"
+    )]
+    ExcessiveRefund {
+        transaction_id: u64,
+        amount: rust_decimal::Decimal,
+        refundable: rust_decimal::Decimal,
+    },
+    #[error("unlock {transaction_id} of client {client} rejected: unlocking is not allowed")]
+    UnlockNotAllowed { client: u16, transaction_id: u64 },
+    #[error(
+        "dispute of {amount} rejected: transaction {transaction_id} only has {disputable} to \
+         dispute"
+    )]
+    ExcessiveDispute {
+        transaction_id: u64,
+        amount: rust_decimal::Decimal,
+        disputable: rust_decimal::Decimal,
+    },
+    #[error("dispute of transaction {transaction_id} by client {client} rejected: too late")]
+    DisputeExpired { client: u16, transaction_id: u64 },
+    #[error(
+        "transaction {transaction_id} rejected: it is in {currency}, but the account of client \
+         {client} is in {account_currency}"
+    )]
+    CurrencyMismatch {
+        client: u16,
+        transaction_id: u64,
+        currency: String,
+        account_currency: String,
+    },
+    #[error("converting the balances of client {client} overflows")]
+    ConversionOverflow { client: u16 },
+    #[error("accruing interest on the balances of client {client} overflows")]
+    InterestOverflow { client: u16 },
+    #[error("no rate to convert {from} into {to}")]
+    MissingRate { from: String, to: String },
+    #[error("transaction {transaction_id} has unknown type {kind}")]
+    UnknownType { transaction_id: u64, kind: String },
+    #[error("transaction {transaction_id} has invalid client {client}")]
+    InvalidClient { transaction_id: u64, client: u64 },
+    #[error("transaction {transaction_id} has invalid amount {amount}")]
+    InvalidAmount { transaction_id: u64, amount: String },
+    #[error("transaction {transaction_id} has invalid timestamp {timestamp}")]
+    InvalidTimestamp {
+        transaction_id: u64,
+        timestamp: String,
+    },
+    #[error("transaction {transaction_id} requires a timestamp")]
+    MissingTimestamp { transaction_id: u64 },
+    #[error("timestamp on {day} is out of order: it comes after one on {last_day}")]
+    UnorderedTimestamp {
+        day: chrono::NaiveDate,
+        last_day: chrono::NaiveDate,
+    },
+    #[error("transfer {transaction_id} requires a recipient")]
+    MissingRecipient { transaction_id: u64 },
+    #[error("transfer {transaction_id} rejected: client {client} can't transfer to itself")]
+    SelfTransfer { client: u16, transaction_id: u64 },
+    #[error("client {client} references transaction {transaction_id}, which doesn't exist")]
+    UnknownTransaction { client: u16, transaction_id: u64 },
+    #[error(transparent)]
+    Store(#[from] crate::engine::store::Error),
+}
+
+impl Error {
+    /// Short identifier of the violation, like `account_locked`, stable
+    /// across changes to the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NoAvailableFundsToWithdraw { .. } => "insufficient_funds",
+            Self::AccountLocked { .. } => "account_locked",
+            Self::ForeignTransaction { .. } => "foreign_transaction",
+            Self::DuplicateTransaction { .. } => "duplicate_transaction",
+            Self::NonPositiveAmount { .. } => "non_positive_amount",
+            Self::ExcessivePrecision { .. } => "excessive_precision",
+            Self::BalanceOverflow { .. } => "balance_overflow",
+            Self::ClientFailed { .. } => "client_failed",
+            Self::MissingAmount { .. } => "missing_amount",
+            Self::NotRefundable { .. } => "not_refundable",
+            Self::ExcessiveRefund { .. } => "excessive_refund",
+            Self::UnlockNotAllowed { .. } => "unlock_not_allowed",
+            Self::ExcessiveDispute { .. } => "excessive_dispute",
+            Self::DisputeExpired { .. } => "dispute_expired",
+            Self::CurrencyMismatch { .. } => "currency_mismatch",
+            Self::ConversionOverflow { .. } => "conversion_overflow",
+            Self::InterestOverflow { .. } => "interest_overflow",
+            Self::MissingRate { .. } => "missing_rate",
+            Self::UnknownType { .. } => "unknown_type",
+            Self::InvalidClient { .. } => "invalid_client",
+            Self::InvalidAmount { .. } => "invalid_amount",
+            Self::InvalidTimestamp { .. } => "invalid_timestamp",
+            Self::MissingTimestamp { .. } => "missing_timestamp",
+            Self::UnorderedTimestamp { .. } => "unordered_timestamp",
+            Self::MissingRecipient { .. } => "missing_recipient",
+            Self::SelfTransfer { .. } => "self_transfer",
+            Self::UnknownTransaction { .. } => "unknown_transaction",
+            Self::Store(_) => "store",
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+
+use super::error::{Error, Result};
+
+/// Exchange rates into a base currency.
+#[derive(Debug, Clone)]
+pub struct Rates {
+    base: String,
+    /// Units of the second currency one unit of the first is worth.
+    rates: HashMap<(String, String), Decimal>,
+}
+
+impl Rates {
+    pub fn new(base: impl Into<String>, rates: HashMap<(String, String), Decimal>) -> Self {
+        Self {
+            base: base.into(),
+            rates,
+        }
+    }
+
+    /// Currency balances are converted into.
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Units of the base currency one unit of `currency` is worth.
+    ///
+    /// A rate from the base currency into `currency` works as well, inverted.
+    pub fn rate(&self, currency: &str) -> Result<Decimal> {
+        if currency == self.base {
+            return Ok(Decimal::ONE);
+        }
+
+        let pair = |from: &str, to: &str| self.rates.get(&(from.to_owned(), to.to_owned()));
+        match (pair(currency, &self.base), pair(&self.base, currency)) {
+            (Some(rate), _) => Ok(*rate),
+            (None, Some(rate)) if !rate.is_zero() => Ok(Decimal::ONE / rate),
+            _ => Err(Error::MissingRate {
+                from: currency.to_owned(),
+                to: self.base.clone(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[test]
+    fn rates() {
+        let rates = Rates::new(
+            "EUR",
+            HashMap::from([
+                (("USD".to_owned(), "EUR".to_owned()), dec!(0.5)),
+                (("EUR".to_owned(), "GBP".to_owned()), dec!(0.8)),
+            ]),
+        );
+
+        assert_eq!(rates.rate("EUR").unwrap(), Decimal::ONE);
+        assert_eq!(rates.rate("USD").unwrap(), dec!(0.5));
+        assert_eq!(rates.rate("GBP").unwrap(), dec!(1.25));
+        assert!(matches!(
+            rates.rate("JPY"),
+            Err(Error::MissingRate { from, to }) if from == "JPY" && to == "EUR"
+        ));
+    }
+}
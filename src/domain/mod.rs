@@ -1,3 +1,9 @@
+//! Core types shared by every input format and the engine.
+
 pub mod account;
 pub mod error;
+pub mod fx;
 pub mod transaction;
+
+/// Decimal places of every amount and balance.
+pub const PRECISION: u32 = 4;
@@ -1,21 +1,459 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
-use serde::Deserialize;
+use serde::{de, Deserialize, Deserializer};
 
-#[derive(Debug, Deserialize, Hash, PartialEq, Eq)]
-#[serde(rename_all = "lowercase", tag = "type")]
+use super::{error::Error, PRECISION};
+
+/// What a transaction does, along with the data only some kinds carry.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum TransactionKind {
-    Deposit { amount: Decimal },
-    Withdrawal { amount: Decimal },
-    Dispute,
+    Deposit {
+        amount: Decimal,
+    },
+    Withdrawal {
+        amount: Decimal,
+    },
+    /// Move funds from the client of the transaction to `to_client`.
+    Transfer {
+        to_client: u16,
+        amount: Decimal,
+    },
+    /// Give back part or all of an earlier deposit to whoever paid it.
+    Refund {
+        amount: Decimal,
+    },
+    /// Back-office correction of the available funds, which can be negative.
+    Adjustment {
+        amount: Decimal,
+    },
+    /// Hold funds of an earlier transaction: `amount` of them, or all if
+    /// missing.
+    Dispute {
+        amount: Option<Decimal>,
+    },
     Resolve,
     Chargeback,
+    /// Clear the lock a chargeback left on the account.
+    Unlock,
+}
+
+impl TransactionKind {
+    /// Type of the transaction as written in the input, like `deposit`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Deposit { .. } => "deposit",
+            Self::Withdrawal { .. } => "withdrawal",
+            Self::Transfer { .. } => "transfer",
+            Self::Refund { .. } => "refund",
+            Self::Adjustment { .. } => "adjustment",
+            Self::Dispute { .. } => "dispute",
+            Self::Resolve => "resolve",
+            Self::Chargeback => "chargeback",
+            Self::Unlock => "unlock",
+        }
+    }
+
+    /// Amount the transaction moves or holds, if it carries one.
+    pub fn amount(&self) -> Option<Decimal> {
+        match *self {
+            Self::Deposit { amount }
+            | Self::Withdrawal { amount }
+            | Self::Transfer { amount, .. }
+            | Self::Refund { amount }
+            | Self::Adjustment { amount } => Some(amount),
+            Self::Dispute { amount } => amount,
+            Self::Resolve | Self::Chargeback | Self::Unlock => None,
+        }
+    }
+}
+
+/// How to handle amounts with more than [`PRECISION`] decimal places.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrecisionPolicy {
+    /// Round them to [`PRECISION`] decimal places.
+    #[default]
+    Round,
+    /// Fail with [`Error::ExcessivePrecision`].
+    Reject,
 }
 
+/// A single row of input.
+///
+/// Disputes, resolves and chargebacks reuse `transaction_id` to reference the
+/// deposit, withdrawal or transfer they apply to, and refunds to reference a
+/// deposit.
 #[derive(Debug, Deserialize, Hash, PartialEq, Eq)]
+#[serde(try_from = "TransactionRecord")]
 pub struct Transaction {
-    #[serde(rename = "tx")]
     pub transaction_id: u64,
+    /// When the transaction happened, if known.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Currency of the amount, like `EUR`. Every transaction of a client must
+    /// be in the same currency.
+    pub currency: Option<String>,
     pub client: u16,
-    #[serde(flatten)]
     pub kind: TransactionKind,
 }
+
+impl Transaction {
+    /// Deposit of a positive `amount`, or withdrawal of a negative one, as
+    /// bank statements list them.
+    pub(crate) fn signed(transaction_id: u64, client: u16, amount: Decimal) -> Result<Self, Error> {
+        let kind = if amount.is_sign_negative() {
+            TransactionKind::Withdrawal { amount: -amount }
+        } else {
+            TransactionKind::Deposit { amount }
+        };
+
+        let transaction = Self {
+            transaction_id,
+            timestamp: None,
+            currency: None,
+            client,
+            kind,
+        };
+        transaction.validate()?;
+
+        Ok(transaction)
+    }
+
+    /// Check the transaction makes sense on its own, regardless of any
+    /// account.
+    ///
+    /// Deposits, withdrawals, transfers and refunds must move a positive
+    /// amount, the direction being given by their kind, disputes must hold a
+    /// positive amount if any, and transfers must go to another client.
+    pub fn validate(&self) -> Result<(), Error> {
+        match self.kind {
+            TransactionKind::Deposit { amount }
+            | TransactionKind::Withdrawal { amount }
+            | TransactionKind::Transfer { amount, .. }
+            | TransactionKind::Refund { amount }
+            | TransactionKind::Dispute {
+                amount: Some(amount),
+            } if amount <= Decimal::ZERO => Err(Error::NonPositiveAmount {
+                transaction_id: self.transaction_id,
+                amount,
+            }),
+            TransactionKind::Transfer { to_client, .. } if to_client == self.client => {
+                Err(Error::SelfTransfer {
+                    client: self.client,
+                    transaction_id: self.transaction_id,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Make sure the amount, if any, has at most [`PRECISION`] decimal
+    /// places.
+    ///
+    /// Meant to be applied right after parsing, so the engine never sees more
+    /// precision than what is reported.
+    pub fn enforce_precision(mut self, policy: PrecisionPolicy) -> Result<Self, Error> {
+        if let TransactionKind::Deposit { amount }
+        | TransactionKind::Withdrawal { amount }
+        | TransactionKind::Transfer { amount, .. }
+        | TransactionKind::Refund { amount }
+        | TransactionKind::Adjustment { amount }
+        | TransactionKind::Dispute {
+            amount: Some(amount),
+        } = &mut self.kind
+        {
+            // Trailing zeros don't count, `1.50000` is as precise as `1.5`.
+            if amount.normalize().scale() > PRECISION {
+                match policy {
+                    PrecisionPolicy::Round => *amount = amount.round_dp(PRECISION),
+                    PrecisionPolicy::Reject => {
+                        return Err(Error::ExcessivePrecision {
+                            transaction_id: self.transaction_id,
+                            amount: *amount,
+                        })
+                    }
+                }
+            }
+        }
+
+        Ok(self)
+    }
+}
+
+/// Transaction of `client` without a timestamp or currency, as tests build
+/// them.
+#[cfg(test)]
+pub(crate) fn tx(client: u16, transaction_id: u64, kind: TransactionKind) -> Transaction {
+    Transaction {
+        transaction_id,
+        timestamp: None,
+        currency: None,
+        client,
+        kind,
+    }
+}
+
+/// Flat shape of a transaction in the input, where `amount` is only set for
+/// deposits, withdrawals, transfers, refunds and adjustments, optional for
+/// disputes, and `to_client` only set for transfers.
+///
+/// Deserializing through it instead of flattening [`TransactionKind`] into
+/// [`Transaction`] lets formats like CSV report which column is invalid.
+#[derive(Debug, Deserialize)]
+pub(crate) struct TransactionRecord {
+    #[serde(rename = "type")]
+    kind: Kind,
+    pub(crate) client: u16,
+    pub(crate) tx: u64,
+    pub(crate) amount: Option<Decimal>,
+    pub(crate) to_client: Option<u16>,
+    #[serde(default, deserialize_with = "deserialize_timestamp")]
+    pub(crate) timestamp: Option<DateTime<Utc>>,
+    pub(crate) currency: Option<String>,
+}
+
+impl TransactionRecord {
+    /// Record of transaction `tx`, whose type is given by its name in the
+    /// input, like `deposit`, and without any optional field.
+    ///
+    /// Meant for formats that don't go through serde.
+    pub(crate) fn new(kind: &str, client: u16, tx: u64) -> Result<Self, Error> {
+        let deserializer = de::value::StrDeserializer::<de::value::Error>::new(kind);
+        let kind =
+            <Kind as Deserialize>::deserialize(deserializer).map_err(|_| Error::UnknownType {
+                transaction_id: tx,
+                kind: kind.to_owned(),
+            })?;
+
+        Ok(Self {
+            kind,
+            client,
+            tx,
+            amount: None,
+            to_client: None,
+            timestamp: None,
+            currency: None,
+        })
+    }
+}
+
+/// Parse a timestamp given either as seconds since the Unix epoch or as an
+/// RFC 3339 date and time, like `2024-01-31T12:00:00Z`.
+fn deserialize_timestamp<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error> {
+    struct Timestamp(DateTime<Utc>);
+    struct Visitor;
+
+    impl de::Visitor<'_> for Visitor {
+        type Value = Timestamp;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("seconds since the Unix epoch or an RFC 3339 date and time")
+        }
+
+        fn visit_i64<E: de::Error>(self, seconds: i64) -> Result<Timestamp, E> {
+            DateTime::from_timestamp(seconds, 0)
+                .map(Timestamp)
+                .ok_or_else(|| E::invalid_value(de::Unexpected::Signed(seconds), &self))
+        }
+
+        fn visit_u64<E: de::Error>(self, seconds: u64) -> Result<Timestamp, E> {
+            let seconds = i64::try_from(seconds)
+                .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(seconds), &self))?;
+            self.visit_i64(seconds)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Timestamp, E> {
+            DateTime::parse_from_rfc3339(value)
+                .map(|timestamp| Timestamp(timestamp.to_utc()))
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Timestamp {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(Visitor)
+        }
+    }
+
+    Ok(Option::<Timestamp>::deserialize(deserializer)?.map(|Timestamp(timestamp)| timestamp))
+}
+
+/// Whether transaction types must be in lowercase, whatever the input format.
+static CASE_SENSITIVE_TYPES: AtomicBool = AtomicBool::new(false);
+
+/// Make every input format reject transaction types that aren't in
+/// lowercase, like `DEPOSIT`, instead of reading them in any case.
+pub fn set_case_sensitive_types(case_sensitive: bool) {
+    CASE_SENSITIVE_TYPES.store(case_sensitive, Ordering::Relaxed);
+}
+
+/// Type of a transaction in the input, like `deposit`, in any case unless
+/// [`set_case_sensitive_types`] says otherwise.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[serde(remote = "Self")]
+enum Kind {
+    Deposit,
+    Withdrawal,
+    Transfer,
+    Refund,
+    Adjustment,
+    Dispute,
+    Resolve,
+    Chargeback,
+    Unlock,
+}
+
+impl<'de> Deserialize<'de> for Kind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = Kind;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a transaction type")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Kind, E> {
+                Kind::parse(value, CASE_SENSITIVE_TYPES.load(Ordering::Relaxed))
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+impl Kind {
+    /// Type named `value`, which must be in lowercase if `case_sensitive`.
+    fn parse<E: de::Error>(value: &str, case_sensitive: bool) -> Result<Self, E> {
+        match Kind::deserialize(de::value::StrDeserializer::new(value)) {
+            Err(_) if !case_sensitive && value.chars().any(char::is_uppercase) => {
+                Kind::deserialize(de::value::StrDeserializer::new(&value.to_lowercase()))
+            }
+            result => result,
+        }
+    }
+}
+
+impl TryFrom<TransactionRecord> for Transaction {
+    type Error = Error;
+
+    fn try_from(record: TransactionRecord) -> Result<Self, Self::Error> {
+        let amount = || {
+            record.amount.ok_or(Error::MissingAmount {
+                transaction_id: record.tx,
+            })
+        };
+
+        let kind = match record.kind {
+            Kind::Deposit => TransactionKind::Deposit { amount: amount()? },
+            Kind::Withdrawal => TransactionKind::Withdrawal { amount: amount()? },
+            Kind::Transfer => TransactionKind::Transfer {
+                to_client: record.to_client.ok_or(Error::MissingRecipient {
+                    transaction_id: record.tx,
+                })?,
+                amount: amount()?,
+            },
+            Kind::Refund => TransactionKind::Refund { amount: amount()? },
+            Kind::Adjustment => TransactionKind::Adjustment { amount: amount()? },
+            Kind::Dispute => TransactionKind::Dispute {
+                amount: record.amount,
+            },
+            Kind::Resolve => TransactionKind::Resolve,
+            Kind::Chargeback => TransactionKind::Chargeback,
+            Kind::Unlock => TransactionKind::Unlock,
+        };
+
+        let transaction = Self {
+            transaction_id: record.tx,
+            timestamp: record.timestamp,
+            currency: record.currency,
+            client: record.client,
+            kind,
+        };
+        transaction.validate()?;
+
+        Ok(transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    fn deposit(amount: Decimal) -> Transaction {
+        Transaction {
+            transaction_id: 1,
+            timestamp: None,
+            currency: None,
+            client: 1,
+            kind: TransactionKind::Deposit { amount },
+        }
+    }
+
+    #[test]
+    fn precision_rounded() {
+        let tx = deposit(dec!(1.23456))
+            .enforce_precision(PrecisionPolicy::Round)
+            .unwrap();
+
+        assert_eq!(
+            tx.kind,
+            TransactionKind::Deposit {
+                amount: dec!(1.2346)
+            }
+        );
+    }
+
+    #[test]
+    fn precision_rejected() {
+        let error = deposit(dec!(1.23456))
+            .enforce_precision(PrecisionPolicy::Reject)
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::ExcessivePrecision {
+                transaction_id: 1,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn trailing_zeros_are_not_precision() {
+        let tx = deposit(dec!(1.500000)).enforce_precision(PrecisionPolicy::Reject);
+
+        assert!(tx.is_ok());
+    }
+
+    #[test]
+    fn type_in_any_case() {
+        for kind in ["deposit", "Deposit", "DEPOSIT"] {
+            let record = TransactionRecord::new(kind, 1, 1).unwrap();
+            assert!(matches!(record.kind, Kind::Deposit));
+        }
+
+        assert!(matches!(
+            TransactionRecord::new("PAYOUT", 1, 1),
+            Err(Error::UnknownType { kind, .. }) if kind == "PAYOUT"
+        ));
+    }
+
+    #[test]
+    fn case_sensitive_types() {
+        let parse = |kind| Kind::parse::<de::value::Error>(kind, true);
+
+        assert!(matches!(parse("deposit"), Ok(Kind::Deposit)));
+        assert!(parse("Deposit").is_err());
+        assert!(parse("DEPOSIT").is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! Transcoding of text input to UTF-8.
+
+use std::io::{self, Cursor, Read};
+
+use encoding_rs::{Encoding, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+/// How much of the input is checked to tell whether it is UTF-8.
+const SNIFF_LEN: u64 = 8 * 1024;
+
+/// Wrap `reader` so it is read as UTF-8, without its byte order mark.
+///
+/// A UTF-8 or UTF-16 byte order mark tells the encoding of the input, even
+/// over `encoding`. Without one, input is taken to be in `encoding` if any,
+/// in UTF-8 if it starts like it, or in Windows-1252, the superset of
+/// Latin-1 that Windows tools export, otherwise. Invalid sequences become
+/// U+FFFD.
+pub fn decode<'a>(
+    mut reader: impl Read + 'a,
+    encoding: Option<&'static Encoding>,
+) -> io::Result<Box<dyn Read + 'a>> {
+    let mut head = Vec::new();
+    (&mut reader).take(SNIFF_LEN).read_to_end(&mut head)?;
+
+    let encoding = encoding.or_else(|| (!is_utf8(&head)).then_some(WINDOWS_1252));
+    let reader = Cursor::new(head).chain(reader);
+
+    Ok(Box::new(
+        DecodeReaderBytesBuilder::new()
+            .encoding(encoding)
+            .bom_override(true)
+            .build(reader),
+    ))
+}
+
+/// Whether `head` is valid UTF-8, but for a sequence cut at its end.
+fn is_utf8(head: &[u8]) -> bool {
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(input: &[u8], encoding: Option<&'static Encoding>) -> String {
+        let mut output = String::new();
+        decode(input, encoding)
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        assert_eq!(read(b"\xef\xbb\xbftype,client\n", None), "type,client\n");
+        assert_eq!(
+            read(b"\xff\xfet\x00y\x00p\x00e\x00", Some(WINDOWS_1252)),
+            "type"
+        );
+    }
+
+    #[test]
+    fn latin1() {
+        assert_eq!(read("dépôt".as_bytes(), None), "dépôt");
+        assert_eq!(read(b"d\xe9p\xf4t", None), "dépôt");
+        assert_eq!(read(b"\xb1", Some(encoding_rs::ISO_8859_2)), "ą");
+    }
+}
@@ -0,0 +1,68 @@
+//! Decryption of age-encrypted input, available with the `age` feature.
+
+use std::{io::Read, path::Path};
+
+use age::{armor::ArmoredReader, Decryptor, Identity, IdentityFile};
+
+use crate::error::Result;
+
+/// Read the identities of an age identity file, like one written by
+/// `age-keygen`.
+pub fn read_identities(path: &Path) -> Result<Vec<Box<dyn Identity>>> {
+    let file = IdentityFile::from_file(path.to_string_lossy().into_owned())?;
+    Ok(file.into_identities()?)
+}
+
+/// Decrypt `reader` on the fly with one of `identities`, whether it is
+/// binary or armored.
+///
+/// Fails right away if the header can't be decrypted with any of them, while
+/// a corrupted payload only fails when read.
+pub fn decrypt<'a>(
+    reader: impl Read + 'a,
+    identities: &[Box<dyn Identity>],
+) -> Result<impl Read + 'a> {
+    let decryptor = Decryptor::new(ArmoredReader::new(reader))?;
+    Ok(decryptor.decrypt(identities.iter().map(|identity| identity.as_ref()))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use age::x25519;
+
+    use super::*;
+    use crate::error::Error;
+
+    const INPUT: &str = "type,client,tx,amount\ndeposit,1,1,1.0\n";
+
+    fn read(encrypted: &[u8], identity: x25519::Identity) -> Result<String> {
+        let identities: Vec<Box<dyn Identity>> = vec![Box::new(identity)];
+
+        let mut output = String::new();
+        decrypt(encrypted, &identities)?.read_to_string(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn binary_and_armored() {
+        let identity = x25519::Identity::generate();
+        let recipient = identity.to_public();
+
+        let binary = age::encrypt(&recipient, INPUT.as_bytes()).unwrap();
+        let armored = age::encrypt_and_armor(&recipient, INPUT.as_bytes()).unwrap();
+
+        assert_eq!(read(&binary, identity.clone()).unwrap(), INPUT);
+        assert_eq!(read(armored.as_bytes(), identity).unwrap(), INPUT);
+    }
+
+    #[test]
+    fn wrong_identity() {
+        let recipient = x25519::Identity::generate().to_public();
+        let encrypted = age::encrypt(&recipient, INPUT.as_bytes()).unwrap();
+
+        assert!(matches!(
+            read(&encrypted, x25519::Identity::generate()),
+            Err(Error::DecryptionError(_))
+        ));
+    }
+}
@@ -0,0 +1,188 @@
+//! Interest on daily balances.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use super::TransactionEngine;
+use crate::domain::{
+    error::{Error as DomainError, Result as DomainResult},
+    transaction::Transaction,
+};
+
+/// Days in a year, as far as interest goes.
+const DAYS_PER_YEAR: i64 = 365;
+
+/// Interest accrued by each client day by day, on the total balance at the end
+/// of each day.
+///
+/// Transactions must come in timestamp order: before applying one, call
+/// [`accrue_until`](Self::accrue_until) with its day so the days before it
+/// accrue on the balances they ended with.
+#[derive(Debug)]
+pub struct Accrual {
+    /// Yearly rate, like `0.05` for 5%.
+    rate: Decimal,
+    /// First day that didn't accrue yet.
+    next_day: Option<NaiveDate>,
+    /// Day of the last transaction, which the next ones can't come before.
+    last_day: Option<NaiveDate>,
+    interest: HashMap<u16, Decimal>,
+}
+
+impl Accrual {
+    /// Accrue at a yearly `rate`, starting on `since` or, if missing, on the
+    /// day of the first transaction.
+    pub fn new(rate: Decimal, since: Option<NaiveDate>) -> Self {
+        Self {
+            rate,
+            next_day: since,
+            last_day: None,
+            interest: HashMap::new(),
+        }
+    }
+
+    /// Day a transaction belongs to, which it must have a timestamp for.
+    pub fn day(tx: &Transaction) -> DomainResult<NaiveDate> {
+        tx.timestamp
+            .map(|timestamp| timestamp.date_naive())
+            .ok_or(DomainError::MissingTimestamp {
+                transaction_id: tx.transaction_id,
+            })
+    }
+
+    /// Accrue interest for every day before `day`. Balances only change with
+    /// transactions, so they are the current ones for all those days.
+    ///
+    /// Negative balances don't accrue anything. Fails if `day` is before the
+    /// day of the previous call, or if the interest of an account overflows.
+    pub fn accrue_until(&mut self, engine: &TransactionEngine, day: NaiveDate) -> DomainResult<()> {
+        if let Some(last_day) = self.last_day.filter(|last_day| day < *last_day) {
+            return Err(DomainError::UnorderedTimestamp { day, last_day });
+        }
+        self.last_day = Some(day);
+
+        self.accrue(engine, day)
+    }
+
+    /// Accrue interest for every day before `day` that didn't yet.
+    fn accrue(&mut self, engine: &TransactionEngine, day: NaiveDate) -> DomainResult<()> {
+        let next_day = *self.next_day.get_or_insert(day);
+        let days = (day - next_day).num_days();
+        if days <= 0 {
+            return Ok(());
+        }
+
+        for account in engine.accounts() {
+            let client = account.client();
+            let balance = account.total().max(Decimal::ZERO);
+            let interest = self.interest.entry(client).or_default();
+            *interest = balance
+                .checked_mul(self.rate)
+                .and_then(|amount| amount.checked_mul(Decimal::from(days)))
+                .and_then(|amount| amount.checked_div(Decimal::from(DAYS_PER_YEAR)))
+                .and_then(|amount| interest.checked_add(amount))
+                .ok_or(DomainError::InterestOverflow { client })?;
+        }
+        self.next_day = Some(day);
+
+        Ok(())
+    }
+
+    /// Accrue interest up to and including `as_of`, crediting it to every
+    /// account.
+    pub fn finish(mut self, engine: &mut TransactionEngine, as_of: NaiveDate) -> DomainResult<()> {
+        if let Some(day) = as_of.succ_opt() {
+            self.accrue(engine, day)?;
+        }
+
+        for account in engine.accounts_mut() {
+            let interest = self.interest.get(&account.client()).copied();
+            account.accrue(interest.unwrap_or_default())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::domain::transaction::TransactionKind;
+
+    #[test]
+    fn accrue() {
+        let mut engine = TransactionEngine::new();
+        let day = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let mut accrual = Accrual::new(dec!(0.365), Some(day(1)));
+
+        accrual.accrue_until(&engine, day(1)).unwrap();
+        engine
+            .process(Transaction {
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                client: 1,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            })
+            .unwrap();
+        accrual.accrue_until(&engine, day(11)).unwrap();
+        engine
+            .process(Transaction {
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                client: 1,
+                kind: TransactionKind::Withdrawal { amount: dec!(50.0) },
+            })
+            .unwrap();
+        accrual.finish(&mut engine, day(20)).unwrap();
+
+        // 10 days at 0.1% a day on 100, then 10 more on 50.
+        let account = engine.accounts().next().unwrap();
+        assert_eq!(account.interest(), Some(dec!(1.5)));
+        assert_eq!(account.total(), dec!(51.5));
+    }
+
+    #[test]
+    fn unordered() {
+        let engine = TransactionEngine::new();
+        let day = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let mut accrual = Accrual::new(dec!(0.05), None);
+
+        accrual.accrue_until(&engine, day(2)).unwrap();
+        accrual.accrue_until(&engine, day(2)).unwrap();
+        assert!(matches!(
+            accrual.accrue_until(&engine, day(1)),
+            Err(DomainError::UnorderedTimestamp { .. })
+        ));
+    }
+
+    #[test]
+    fn overflow() {
+        let mut engine = TransactionEngine::new();
+        let day = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let mut accrual = Accrual::new(Decimal::MAX, Some(day(1)));
+
+        engine
+            .process(Transaction {
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                client: 1,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            })
+            .unwrap();
+        assert!(matches!(
+            accrual.accrue_until(&engine, day(11)),
+            Err(DomainError::InterestOverflow { client: 1 })
+        ));
+    }
+}
@@ -0,0 +1,154 @@
+//! Policies that tune how the engine applies transactions.
+
+use std::{collections::HashMap, time::Duration};
+
+use rust_decimal::Decimal;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::domain::{
+    error::{Error as DomainError, Result as DomainResult},
+    transaction::{Transaction, TransactionKind},
+};
+
+/// Settings of a [`TransactionEngine`](super::TransactionEngine).
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub locked_account: LockedAccountPolicy,
+    pub duplicate_transaction: DuplicatePolicy,
+    pub negative_balance: NegativeBalancePolicy,
+    /// How far below zero withdrawals can take available funds. Unless
+    /// negative balances are allowed outright, there is no overdraft without
+    /// it.
+    pub overdraft: Option<OverdraftLimits>,
+    /// Keep track of the net amount of adjustments of each account.
+    pub audit_adjustments: bool,
+    /// Accept unlock transactions. They are administrative, so they are
+    /// rejected unless explicitly allowed.
+    pub allow_unlock: bool,
+    /// How long after a transaction it can still be disputed. Only enforced
+    /// when both the transaction and the dispute have a timestamp.
+    pub dispute_window: Option<Duration>,
+    /// Fees charged on top of deposits, withdrawals and transfers.
+    pub fees: Option<FeeSchedule>,
+}
+
+/// What to do with deposits and withdrawals for an account locked by a
+/// chargeback.
+///
+/// Disputes, resolves and chargebacks are always applied, since they settle
+/// funds that were already in the account when it got locked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LockedAccountPolicy {
+    /// Fail with [`Error::AccountLocked`](crate::domain::error::Error).
+    #[default]
+    Reject,
+    /// Keep applying them as if the account wasn't locked.
+    Allow,
+}
+
+/// What to do with a deposit or withdrawal reusing the id of a previous one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Fail with [`Error::DuplicateTransaction`](crate::domain::error::Error).
+    #[default]
+    Reject,
+    /// Silently ignore it, keeping the first transaction.
+    KeepFirst,
+}
+
+/// What to do with a withdrawal bigger than the available funds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NegativeBalancePolicy {
+    /// Fail with
+    /// [`Error::NoAvailableFundsToWithdraw`](crate::domain::error::Error),
+    /// leaving the account as it was.
+    #[default]
+    RejectWithdrawal,
+    /// Fail like [`RejectWithdrawal`](Self::RejectWithdrawal), and also give
+    /// up on the client: its account is left out of the results and any
+    /// later transaction of it fails with
+    /// [`Error::ClientFailed`](crate::domain::error::Error).
+    ErrorClient,
+    /// Apply the withdrawal, letting available funds go negative.
+    AllowOverdraft,
+}
+
+/// How far below zero each client's available funds can go.
+#[derive(Debug, Clone, Default)]
+pub struct OverdraftLimits {
+    /// Limit of clients without one of their own.
+    pub default: Decimal,
+    /// Limits of specific clients.
+    pub clients: HashMap<u16, Decimal>,
+}
+
+impl OverdraftLimits {
+    /// Overdraft limit of `client`.
+    pub fn limit(&self, client: u16) -> Decimal {
+        self.clients.get(&client).copied().unwrap_or(self.default)
+    }
+}
+
+/// Fees of each kind of transaction, paid by the client of the transaction.
+///
+/// Written in TOML as one table per kind, like:
+///
+/// ```toml
+/// [withdrawal]
+/// flat = 0.5
+/// percentage = 1.5
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeeSchedule {
+    pub deposit: Option<Fee>,
+    pub withdrawal: Option<Fee>,
+    pub transfer: Option<Fee>,
+}
+
+/// Fee of a transaction: a flat amount plus a percentage of what it moves,
+/// neither of which can be negative.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fee {
+    #[serde(deserialize_with = "non_negative")]
+    pub flat: Decimal,
+    #[serde(deserialize_with = "non_negative")]
+    pub percentage: Decimal,
+}
+
+impl FeeSchedule {
+    /// Fee of `tx`, if its kind has one, failing with
+    /// [`Error::BalanceOverflow`](crate::domain::error::Error) if it
+    /// overflows.
+    pub fn fee(&self, tx: &Transaction) -> DomainResult<Option<Decimal>> {
+        let (fee, amount) = match &tx.kind {
+            TransactionKind::Deposit { amount } => (&self.deposit, amount),
+            TransactionKind::Withdrawal { amount } => (&self.withdrawal, amount),
+            TransactionKind::Transfer { amount, .. } => (&self.transfer, amount),
+            _ => return Ok(None),
+        };
+        let Some(fee) = fee else {
+            return Ok(None);
+        };
+
+        amount
+            .checked_mul(fee.percentage)
+            .and_then(|percentage| percentage.checked_div(Decimal::ONE_HUNDRED))
+            .and_then(|percentage| fee.flat.checked_add(percentage))
+            .map(Some)
+            .ok_or(DomainError::BalanceOverflow {
+                client: tx.client,
+                tx: tx.transaction_id,
+            })
+    }
+}
+
+fn non_negative<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+    let value = <Decimal as Deserialize>::deserialize(deserializer)?;
+    if value.is_sign_negative() {
+        return Err(de::Error::custom(format!("{value} is negative")));
+    }
+
+    Ok(value)
+}
@@ -0,0 +1,422 @@
+//! Events telling how transactions change accounts, for other services to
+//! follow along, and replaying them into accounts.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::{Outcome, TransactionEngine};
+use crate::domain::{
+    account::Account,
+    error::Result as DomainResult,
+    transaction::{Transaction, TransactionKind},
+};
+
+/// A change of state caused by a transaction, or its absence, as emitted by
+/// [`TransactionEngine::apply_with_events`].
+///
+/// Serialized with the name of the variant in an `event` field, like
+/// `{"event": "FundsDeposited", "client": 1, "tx": 1, "amount": "1.5"}`.
+/// Amounts are exact, so [`Replay`] gets back to the same balances.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum Event {
+    FundsDeposited {
+        client: u16,
+        tx: u64,
+        amount: Decimal,
+    },
+    FundsWithdrawn {
+        client: u16,
+        tx: u64,
+        amount: Decimal,
+    },
+    FundsTransferred {
+        client: u16,
+        to_client: u16,
+        tx: u64,
+        amount: Decimal,
+    },
+    FundsRefunded {
+        client: u16,
+        tx: u64,
+        amount: Decimal,
+    },
+    FundsAdjusted {
+        client: u16,
+        tx: u64,
+        amount: Decimal,
+    },
+    /// Fee taken by a deposit, withdrawal or transfer, right after it.
+    FeeCharged {
+        client: u16,
+        tx: u64,
+        amount: Decimal,
+    },
+    /// Funds of `client` held by a dispute of transaction `tx`, negative when
+    /// it is a withdrawal.
+    FundsHeld {
+        client: u16,
+        tx: u64,
+        amount: Decimal,
+    },
+    /// Held funds of `client` made available again by a resolve.
+    FundsReleased {
+        client: u16,
+        tx: u64,
+        amount: Decimal,
+    },
+    /// Held funds of `client` taken away by a chargeback, given back to the
+    /// `sender` of a transfer.
+    FundsChargedBack {
+        client: u16,
+        tx: u64,
+        amount: Decimal,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sender: Option<u16>,
+    },
+    AccountLocked {
+        client: u16,
+        tx: u64,
+    },
+    AccountUnlocked {
+        client: u16,
+        tx: u64,
+    },
+    /// Transaction left without effect, with the code of
+    /// [`Ignored`](super::Ignored) telling why.
+    TransactionIgnored {
+        client: u16,
+        tx: u64,
+        reason: Cow<'static, str>,
+    },
+    /// Transaction that failed, with the code of the
+    /// [`Error`](crate::domain::error::Error) telling why.
+    TransactionRejected {
+        client: u16,
+        tx: u64,
+        reason: Cow<'static, str>,
+    },
+}
+
+impl TransactionEngine {
+    /// Like [`apply`](Self::apply), passing every event the transaction
+    /// causes to `emit`, in order.
+    ///
+    /// Applied transactions emit what they did to funds, followed by
+    /// [`Event::FeeCharged`] for their fee and [`Event::AccountLocked`] for
+    /// the accounts they locked, while other transactions emit why they
+    /// weren't applied.
+    pub fn apply_with_events(
+        &mut self,
+        tx: Transaction,
+        mut emit: impl FnMut(Event),
+    ) -> DomainResult<Outcome> {
+        let (client, transaction_id, kind) = (tx.client, tx.transaction_id, tx.kind);
+        // Disputes of transfers settle the funds of the recipient.
+        let recipient = match kind {
+            TransactionKind::Transfer { to_client, .. } => Some(to_client),
+            TransactionKind::Dispute { .. }
+            | TransactionKind::Resolve
+            // A store failing here fails the transaction below, which emits
+            // why.
+            | TransactionKind::Chargeback => self
+                .transactions
+                .get(transaction_id)
+                .ok()
+                .flatten()
+                .and_then(|stored| stored.recipient),
+            _ => None,
+        };
+        let holder = recipient.unwrap_or(client);
+        let locked = |engine: &Self, client| {
+            engine
+                .accounts
+                .get(&client)
+                .is_some_and(|account| account.locked())
+        };
+        let was_locked = [client, holder].map(|client| locked(self, client));
+        // A fee that overflows fails the transaction, which emits why.
+        let fee = match &self.config.fees {
+            Some(fees) => fees.fee(&tx).ok().flatten(),
+            None => None,
+        };
+
+        let result = self.apply(tx);
+
+        let outcome = match &result {
+            Ok(outcome) => outcome,
+            Err(error) => {
+                emit(Event::TransactionRejected {
+                    client,
+                    tx: transaction_id,
+                    reason: error.code().into(),
+                });
+                return result;
+            }
+        };
+        if let Outcome::Ignored(ignored) = outcome {
+            emit(Event::TransactionIgnored {
+                client,
+                tx: transaction_id,
+                reason: ignored.code().into(),
+            });
+            return result;
+        }
+
+        let tx = transaction_id;
+        // Resolves and chargebacks settle whatever the dispute held.
+        let disputed = self
+            .transactions
+            .get(tx)?
+            .map_or(Decimal::ZERO, |stored| stored.disputed);
+        emit(match kind {
+            TransactionKind::Deposit { amount } => Event::FundsDeposited { client, tx, amount },
+            TransactionKind::Withdrawal { amount } => Event::FundsWithdrawn { client, tx, amount },
+            TransactionKind::Transfer { to_client, amount } => Event::FundsTransferred {
+                client,
+                to_client,
+                tx,
+                amount,
+            },
+            TransactionKind::Refund { amount } => Event::FundsRefunded { client, tx, amount },
+            TransactionKind::Adjustment { amount } => Event::FundsAdjusted { client, tx, amount },
+            TransactionKind::Dispute { .. } => Event::FundsHeld {
+                client: holder,
+                tx,
+                amount: disputed,
+            },
+            TransactionKind::Resolve => Event::FundsReleased {
+                client: holder,
+                tx,
+                amount: disputed.abs(),
+            },
+            TransactionKind::Chargeback => Event::FundsChargedBack {
+                client: holder,
+                tx,
+                amount: disputed.abs(),
+                sender: recipient.map(|_| client),
+            },
+            TransactionKind::Unlock => Event::AccountUnlocked { client, tx },
+        });
+
+        if let Some(amount) = fee {
+            emit(Event::FeeCharged { client, tx, amount });
+        }
+
+        let mut clients = vec![client];
+        if holder != client {
+            clients.push(holder);
+        }
+        for (client, was_locked) in clients.into_iter().zip(was_locked) {
+            if !was_locked && locked(self, client) {
+                emit(Event::AccountLocked { client, tx });
+            }
+        }
+
+        result
+    }
+}
+
+/// Accounts rebuilt from events alone, without the transactions they came
+/// from.
+///
+/// Replaying every event emitted by an engine gets back to the balances and
+/// locks of its accounts. Overdraft limits and currencies aren't part of the
+/// events, so they aren't restored.
+#[derive(Debug, Default)]
+pub struct Replay {
+    accounts: HashMap<u16, Account>,
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the changes of `event` to the accounts it concerns. Ignored and
+    /// rejected transactions only open the account of their client, like
+    /// they do in the engine.
+    pub fn apply(&mut self, event: &Event) -> DomainResult<()> {
+        match *event {
+            Event::FundsDeposited { client, tx, amount } => {
+                self.account(client).deposit(tx, amount)
+            }
+            Event::FundsWithdrawn { client, tx, amount }
+            | Event::FundsRefunded { client, tx, amount } => {
+                self.account(client).withdraw(tx, amount, None)
+            }
+            Event::FundsTransferred {
+                client,
+                to_client,
+                tx,
+                amount,
+            } => {
+                self.account(client).withdraw(tx, amount, None)?;
+                self.account(to_client).deposit(tx, amount)
+            }
+            Event::FundsAdjusted { client, tx, amount } => self.account(client).adjust(tx, amount),
+            Event::FeeCharged { client, tx, amount } => {
+                self.account(client).charge_fee(tx, amount, None)
+            }
+            Event::FundsHeld { client, tx, amount } => self.account(client).hold(tx, amount),
+            Event::FundsReleased { client, tx, amount } => self.account(client).release(tx, amount),
+            Event::FundsChargedBack {
+                client,
+                tx,
+                amount,
+                sender,
+            } => {
+                self.account(client).remove_held(tx, amount)?;
+                match sender {
+                    Some(sender) => self.account(sender).deposit(tx, amount),
+                    None => Ok(()),
+                }
+            }
+            Event::AccountLocked { client, .. } => {
+                self.account(client).lock();
+                Ok(())
+            }
+            Event::AccountUnlocked { client, .. } => {
+                self.account(client).unlock();
+                Ok(())
+            }
+            Event::TransactionIgnored { client, .. }
+            | Event::TransactionRejected { client, .. } => {
+                self.account(client);
+                Ok(())
+            }
+        }
+    }
+
+    /// Accounts replayed so far, in no particular order.
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.values()
+    }
+
+    fn account(&mut self, client: u16) -> &mut Account {
+        self.accounts
+            .entry(client)
+            .or_insert_with(|| Account::new(client))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::domain::transaction::tx;
+
+    #[test]
+    fn events() {
+        let mut engine = TransactionEngine::new();
+        let mut events = Vec::new();
+        for (transaction_id, kind) in [
+            (1, TransactionKind::Deposit { amount: dec!(10) }),
+            (
+                1,
+                TransactionKind::Dispute {
+                    amount: Some(dec!(4)),
+                },
+            ),
+            (1, TransactionKind::Chargeback),
+            (2, TransactionKind::Deposit { amount: dec!(1) }),
+            (3, TransactionKind::Resolve),
+        ] {
+            let _ =
+                engine.apply_with_events(tx(1, transaction_id, kind), |event| events.push(event));
+        }
+
+        assert_eq!(
+            events,
+            [
+                Event::FundsDeposited {
+                    client: 1,
+                    tx: 1,
+                    amount: dec!(10)
+                },
+                Event::FundsHeld {
+                    client: 1,
+                    tx: 1,
+                    amount: dec!(4)
+                },
+                Event::FundsChargedBack {
+                    client: 1,
+                    tx: 1,
+                    amount: dec!(4),
+                    sender: None
+                },
+                Event::AccountLocked { client: 1, tx: 1 },
+                Event::TransactionRejected {
+                    client: 1,
+                    tx: 2,
+                    reason: "account_locked".into()
+                },
+                Event::TransactionIgnored {
+                    client: 1,
+                    tx: 3,
+                    reason: "unknown_transaction".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn replay() {
+        let mut engine = TransactionEngine::new();
+        let mut replay = Replay::new();
+        for (transaction_id, client, kind) in [
+            (
+                1,
+                1,
+                TransactionKind::Deposit {
+                    amount: dec!(10.12345),
+                },
+            ),
+            (2, 1, TransactionKind::Withdrawal { amount: dec!(3) }),
+            (
+                3,
+                1,
+                TransactionKind::Transfer {
+                    to_client: 2,
+                    amount: dec!(2.5),
+                },
+            ),
+            (2, 1, TransactionKind::Dispute { amount: None }),
+            (3, 1, TransactionKind::Dispute { amount: None }),
+            (3, 1, TransactionKind::Chargeback),
+            (4, 2, TransactionKind::Withdrawal { amount: dec!(9) }),
+        ] {
+            let _ = engine.apply_with_events(tx(client, transaction_id, kind), |event| {
+                // Events survive being written and read back.
+                let event = serde_json::to_string(&event).unwrap();
+                replay
+                    .apply(&serde_json::from_str(&event).unwrap())
+                    .unwrap();
+            });
+        }
+
+        let balances = |accounts: &mut dyn Iterator<Item = &Account>| {
+            let mut balances: Vec<_> = accounts
+                .map(|account| {
+                    let (available, held) = (account.available(), account.held());
+                    (
+                        account.client(),
+                        available,
+                        held,
+                        account.total(),
+                        account.locked(),
+                    )
+                })
+                .collect();
+            balances.sort();
+            balances
+        };
+        assert_eq!(
+            balances(&mut replay.accounts()),
+            balances(&mut engine.accounts())
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Hashing the state of accounts, to tell whether two runs ended up with the
+//! same accounts without comparing them.
+
+use rust_decimal::Decimal;
+
+use super::TransactionEngine;
+use crate::domain::{account::Account, PRECISION};
+
+/// Version of the encoding hashed, bumped whenever it changes so hashes of
+/// different encodings never match.
+const VERSION: u8 = 1;
+
+/// BLAKE3 hash of `accounts`, in whatever order they come.
+///
+/// Accounts are encoded in client order as the client in 2 little-endian
+/// bytes, available, held and total funds rounded like in outputs, each in
+/// the 16 bytes of [`Decimal::serialize`] once normalized, and a byte telling
+/// whether the account is locked. Equal outputs therefore always have equal
+/// hashes, on any machine.
+pub fn state_hash<'a>(accounts: impl IntoIterator<Item = &'a Account>) -> blake3::Hash {
+    let mut accounts: Vec<_> = accounts.into_iter().collect();
+    accounts.sort_by_key(|account| account.client());
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[VERSION]);
+    for account in accounts {
+        hasher.update(&account.client().to_le_bytes());
+        for funds in [account.available(), account.held(), account.total()] {
+            hasher.update(&canonical(funds).serialize());
+        }
+        hasher.update(&[account.locked() as u8]);
+    }
+
+    hasher.finalize()
+}
+
+/// Same value with a single representation, whatever its scale or the sign
+/// of zero.
+fn canonical(funds: Decimal) -> Decimal {
+    funds.round_dp(PRECISION).normalize()
+}
+
+impl TransactionEngine {
+    /// [`state_hash`] of the accounts so far.
+    pub fn state_hash(&self) -> blake3::Hash {
+        state_hash(self.accounts())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::domain::transaction::{Transaction, TransactionKind};
+
+    fn deposit(engine: &mut TransactionEngine, transaction_id: u64, client: u16, amount: Decimal) {
+        let tx = Transaction {
+            transaction_id,
+            timestamp: None,
+            currency: None,
+            client,
+            kind: TransactionKind::Deposit { amount },
+        };
+        engine.process(tx).unwrap();
+    }
+
+    #[test]
+    fn state_hashes() {
+        let mut engine = TransactionEngine::new();
+        deposit(&mut engine, 1, 1, dec!(1.5));
+        deposit(&mut engine, 2, 2, dec!(2));
+
+        // Same balances in another order and scale.
+        let mut other = TransactionEngine::new();
+        deposit(&mut other, 1, 2, dec!(2.0000));
+        deposit(&mut other, 2, 1, dec!(1.50));
+        assert_eq!(engine.state_hash(), other.state_hash());
+
+        deposit(&mut other, 3, 1, dec!(0.0001));
+        assert_ne!(engine.state_hash(), other.state_hash());
+    }
+}
@@ -0,0 +1,172 @@
+//! Write-ahead journal of the transactions given to a long-lived engine, so a
+//! restart gets back to the same state.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use super::TransactionEngine;
+use crate::{
+    domain::transaction::{Transaction, TransactionKind},
+    error::Result,
+    json,
+};
+
+/// Journal file, holding a transaction per line as JSON with the same fields
+/// as the input, so it can also be processed like any other JSON input.
+///
+/// Every transaction is [`append`](Self::append)ed before the engine applies
+/// it, and the whole journal is applied again when [`open`](Self::open)ed:
+/// a crash at any point loses nothing, and nothing is applied twice as long
+/// as transactions aren't given again after a restart.
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+}
+
+/// A transaction as written to the journal.
+#[derive(Serialize)]
+struct Entry<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    client: u16,
+    tx: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to_client: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<&'a str>,
+}
+
+impl<'a> From<&'a Transaction> for Entry<'a> {
+    fn from(tx: &'a Transaction) -> Self {
+        Self {
+            kind: tx.kind.name(),
+            client: tx.client,
+            tx: tx.transaction_id,
+            amount: tx.kind.amount(),
+            to_client: match tx.kind {
+                TransactionKind::Transfer { to_client, .. } => Some(to_client),
+                _ => None,
+            },
+            timestamp: tx.timestamp,
+            currency: tx.currency.as_deref(),
+        }
+    }
+}
+
+impl Journal {
+    /// Open the journal at `path`, creating it if needed, and apply every
+    /// transaction it holds to `engine`, returning how many there were.
+    ///
+    /// Transactions failing again is expected, as they were journaled before
+    /// being applied the first time, so only errors reading the journal are
+    /// returned. A last line cut short by a crash is dropped, as its
+    /// transaction was never applied.
+    ///
+    /// The journal is read as it is applied rather than all at once, as it
+    /// grows for as long as the engine lives.
+    pub fn open(path: &Path, engine: &mut TransactionEngine) -> Result<(Self, u64)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let complete = complete_len(&mut file)?;
+        if complete < file.metadata()?.len() {
+            file.set_len(complete)?;
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        let mut count = 0;
+        for tx in json::read(BufReader::new((&file).take(complete))) {
+            let _ = engine.apply(tx?);
+            count += 1;
+        }
+
+        Ok((Self { file }, count))
+    }
+
+    /// Durably record `tx`, to be called before applying it.
+    pub fn append(&mut self, tx: &Transaction) -> Result<()> {
+        let mut line = serde_json::to_vec(&Entry::from(tx))?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Length of the complete lines of `file`, found by reading it backwards
+/// from its end up to the last newline.
+fn complete_len(file: &mut File) -> io::Result<u64> {
+    let mut chunk = [0; 8192];
+    let mut end = file.seek(SeekFrom::End(0))?;
+    while end > 0 {
+        let start = end.saturating_sub(chunk.len() as u64);
+        let chunk = &mut chunk[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(index) = chunk.iter().rposition(|&byte| byte == b'\n') {
+            return Ok(start + index as u64 + 1);
+        }
+        end = start;
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::domain::transaction::tx;
+
+    #[test]
+    fn restart() {
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+
+        let mut engine = TransactionEngine::new();
+        let (mut journal, count) = Journal::open(&path, &mut engine).unwrap();
+        assert_eq!(count, 0);
+        for tx in [
+            tx(1, 1, TransactionKind::Deposit { amount: dec!(1.5) }),
+            tx(1, 2, TransactionKind::Withdrawal { amount: dec!(9) }),
+            tx(1, 1, TransactionKind::Dispute { amount: None }),
+        ] {
+            journal.append(&tx).unwrap();
+            let _ = engine.apply(tx);
+        }
+        drop(journal);
+        // A crash in the middle of appending.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"type\":\"depo")
+            .unwrap();
+
+        let mut restarted = TransactionEngine::new();
+        let (mut journal, count) = Journal::open(&path, &mut restarted).unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(restarted.state_hash(), engine.state_hash());
+        let account = restarted.account(1).unwrap();
+        assert_eq!(account.held(), dec!(1.5));
+
+        // Appending goes on after the last complete transaction.
+        journal.append(&tx(1, 1, TransactionKind::Resolve)).unwrap();
+        let (_, count) = Journal::open(&path, &mut TransactionEngine::new()).unwrap();
+        assert_eq!(count, 4);
+    }
+}
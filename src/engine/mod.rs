@@ -0,0 +1,2718 @@
+//! Applies transactions to client accounts.
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use config::{Config, DuplicatePolicy, LockedAccountPolicy, NegativeBalancePolicy};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use store::TransactionStore;
+
+use crate::{
+    domain::{
+        account::Account,
+        error::{Error as DomainError, Result as DomainResult},
+        fx::Rates,
+        transaction::{Transaction, TransactionKind},
+    },
+    error::{Error, Result},
+};
+
+/// Builds the hashers of the maps of the engine: aHash with the `ahash`
+/// feature, which is faster on small keys like client and transaction ids,
+/// or SipHash otherwise, which is harder to flood with colliding ids.
+#[cfg(feature = "ahash")]
+pub(crate) type RandomState = ahash::RandomState;
+#[cfg(not(feature = "ahash"))]
+pub(crate) type RandomState = std::collections::hash_map::RandomState;
+
+pub(crate) type Map<K, V> = HashMap<K, V, RandomState>;
+pub(crate) type Set<T> = HashSet<T, RandomState>;
+
+pub mod accrual;
+pub mod config;
+pub mod events;
+pub mod hash;
+pub mod journal;
+pub mod savepoint;
+pub mod shard;
+pub mod snapshot;
+pub mod statement;
+pub mod store;
+
+/// Amount of a deposit, withdrawal or transfer kept around in case it gets
+/// disputed later, as held by a [`TransactionStore`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredTransaction {
+    client: u16,
+    /// Signed amount: withdrawals are stored as negative values.
+    amount: Decimal,
+    /// Signed portion of `amount` under dispute, or that was under dispute
+    /// once settled.
+    disputed: Decimal,
+    /// Client that received the funds of a transfer.
+    recipient: Option<u16>,
+    timestamp: Option<DateTime<Utc>>,
+    state: DisputeState,
+}
+
+impl StoredTransaction {
+    fn new(client: u16, amount: Decimal, timestamp: Option<DateTime<Utc>>) -> Self {
+        Self {
+            client,
+            amount,
+            disputed: Decimal::ZERO,
+            recipient: None,
+            timestamp,
+            state: DisputeState::Undisputed,
+        }
+    }
+}
+
+/// Where a deposit, withdrawal or transfer is in its dispute lifecycle.
+///
+/// A transaction can only be disputed once, and a dispute is settled once,
+/// either by a resolve or a chargeback:
+///
+/// ```text
+/// Undisputed --dispute--> Disputed --resolve----> Resolved
+///                                  \--chargeback--> ChargedBack
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum DisputeState {
+    Undisputed,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+impl DisputeState {
+    /// State reached by applying `kind`, if the lifecycle allows it.
+    fn next(self, kind: &TransactionKind) -> Option<Self> {
+        match (self, kind) {
+            (Self::Undisputed, TransactionKind::Dispute { .. }) => Some(Self::Disputed),
+            (Self::Disputed, TransactionKind::Resolve) => Some(Self::Resolved),
+            (Self::Disputed, TransactionKind::Chargeback) => Some(Self::ChargedBack),
+            _ => None,
+        }
+    }
+}
+
+/// Counts of the transactions of a client, as given by
+/// [`TransactionEngine::metrics`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    pub deposits: usize,
+    pub withdrawals: usize,
+    /// Amount of deposits, withdrawals and transfers sent, less what was
+    /// refunded.
+    pub volume: Decimal,
+    /// Deposits, withdrawals and transfers ever disputed.
+    pub disputes: usize,
+    /// Deposits, withdrawals and transfers under dispute.
+    pub open_disputes: usize,
+    /// Deposits, withdrawals and transfers charged back.
+    pub chargebacks: usize,
+}
+
+/// What became of a transaction the engine accepted, as given by
+/// [`TransactionEngine::apply`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Applied,
+    /// Left without effect.
+    Ignored(Ignored),
+}
+
+/// Why a transaction was left without effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ignored {
+    /// A deposit, withdrawal or transfer reusing an id, under
+    /// [`DuplicatePolicy::KeepFirst`].
+    Duplicate,
+    /// A refund, dispute, resolve or chargeback of a transaction that
+    /// doesn't exist.
+    UnknownTransaction,
+    /// A dispute, resolve or chargeback the dispute lifecycle doesn't allow,
+    /// like resolving a transaction that isn't disputed.
+    DisputeState,
+}
+
+impl Ignored {
+    /// Short identifier of the reason, like `unknown_transaction`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Duplicate => "duplicate_transaction",
+            Self::UnknownTransaction => "unknown_transaction",
+            Self::DisputeState => "invalid_dispute_state",
+        }
+    }
+}
+
+/// Stateful engine applying transactions as they arrive.
+///
+/// ```
+/// # use rust_decimal::Decimal;
+/// # use txns::{domain::transaction::{Transaction, TransactionKind}, engine::TransactionEngine};
+/// let mut engine = TransactionEngine::new();
+///
+/// engine.process(Transaction {
+///     transaction_id: 1,
+///     timestamp: None,
+///     currency: None,
+///     client: 1,
+///     kind: TransactionKind::Deposit {
+///         amount: Decimal::ONE,
+///     },
+/// })?;
+///
+/// assert_eq!(engine.accounts().next().unwrap().available(), Decimal::ONE);
+/// # Ok::<(), txns::domain::error::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct TransactionEngine {
+    config: Config,
+    accounts: Map<u16, Account>,
+    transactions: Box<dyn TransactionStore>,
+    /// Clients given up on by [`NegativeBalancePolicy::ErrorClient`].
+    failed_clients: Set<u16>,
+    /// Currency of each client, as given by its transactions.
+    currencies: Map<u16, String>,
+}
+
+impl TransactionEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Keep transactions in `store` instead of in memory, picking up the ones
+    /// it already holds.
+    pub fn with_store(mut self, store: impl TransactionStore + 'static) -> Self {
+        self.transactions = Box::new(store);
+        self
+    }
+
+    /// Every account touched so far, in no particular order.
+    ///
+    /// Accounts of failed clients are left out.
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts
+            .values()
+            .filter(|account| !self.failed_clients.contains(&account.client()))
+    }
+
+    /// Counts of the transactions applied so far, by client, failing if the
+    /// store can't read them back.
+    pub fn metrics(&self) -> DomainResult<HashMap<u16, Metrics>> {
+        let mut metrics: HashMap<u16, Metrics> = HashMap::new();
+        for stored in self.transactions.iter() {
+            let (_, stored) = stored?;
+            let metrics = metrics.entry(stored.client).or_default();
+            metrics.volume += stored.amount.abs();
+            match stored.recipient {
+                Some(_) => {}
+                None if stored.amount < Decimal::ZERO => metrics.withdrawals += 1,
+                None => metrics.deposits += 1,
+            }
+            match stored.state {
+                DisputeState::Undisputed => {}
+                DisputeState::Disputed => metrics.open_disputes += 1,
+                DisputeState::Resolved => {}
+                DisputeState::ChargedBack => metrics.chargebacks += 1,
+            }
+            if stored.state != DisputeState::Undisputed {
+                metrics.disputes += 1;
+            }
+        }
+
+        Ok(metrics)
+    }
+
+    /// Account of `client`, if touched so far.
+    pub fn account(&self, client: u16) -> Option<&Account> {
+        self.accounts.get(&client)
+    }
+
+    /// Start from an existing account, e.g. read from a snapshot, replacing
+    /// the one of the same client if any.
+    pub fn insert_account(&mut self, account: Account) {
+        self.accounts.insert(account.client(), account);
+    }
+
+    /// Every account touched so far, failed clients included.
+    pub(crate) fn accounts_mut(&mut self) -> impl Iterator<Item = &mut Account> {
+        self.accounts.values_mut()
+    }
+
+    /// Apply a single transaction.
+    ///
+    /// A transaction that fails doesn't change any balance, so the engine can
+    /// keep processing after an error.
+    pub fn process(&mut self, tx: Transaction) -> DomainResult<()> {
+        self.apply(tx).map(drop)
+    }
+
+    /// Like [`process`](Self::process), telling whether the transaction had
+    /// any effect.
+    pub fn apply(&mut self, tx: Transaction) -> DomainResult<Outcome> {
+        tx.validate()?;
+
+        self.check_client(tx.client, tx.transaction_id)?;
+        self.check_currency(tx.client, &tx)?;
+        let mut clients = vec![tx.client];
+        if let TransactionKind::Transfer { to_client, .. } = tx.kind {
+            self.check_client(to_client, tx.transaction_id)?;
+            self.check_currency(to_client, &tx)?;
+            clients.push(to_client);
+        }
+
+        let currency = tx.currency.clone();
+        let outcome = self.execute(tx)?;
+        // Only transactions that went through tie their clients to a
+        // currency.
+        if let (Outcome::Applied, Some(currency)) = (outcome, currency) {
+            for client in clients {
+                self.currencies
+                    .entry(client)
+                    .or_insert_with(|| currency.clone());
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// Apply a transaction that passed the checks of [`apply`](Self::apply).
+    fn execute(&mut self, tx: Transaction) -> DomainResult<Outcome> {
+        let locked_recipient = match tx.kind {
+            TransactionKind::Transfer { to_client, .. } => self
+                .accounts
+                .get(&to_client)
+                .filter(|recipient| recipient.locked())
+                .map(Account::client),
+            _ => None,
+        };
+        let account = open_account(&mut self.accounts, &self.config, tx.client);
+
+        let moves_funds = matches!(
+            tx.kind,
+            TransactionKind::Deposit { .. }
+                | TransactionKind::Withdrawal { .. }
+                | TransactionKind::Transfer { .. }
+        );
+        if moves_funds && self.transactions.contains(tx.transaction_id)? {
+            return match self.config.duplicate_transaction {
+                DuplicatePolicy::Reject => Err(DomainError::DuplicateTransaction {
+                    client: tx.client,
+                    transaction_id: tx.transaction_id,
+                }),
+                DuplicatePolicy::KeepFirst => Ok(Outcome::Ignored(Ignored::Duplicate)),
+            };
+        }
+        let is_refund = matches!(tx.kind, TransactionKind::Refund { .. });
+        if account.locked()
+            && (moves_funds || is_refund)
+            && self.config.locked_account == LockedAccountPolicy::Reject
+        {
+            return Err(DomainError::AccountLocked {
+                client: tx.client,
+                transaction_id: tx.transaction_id,
+            });
+        }
+        // Nor can funds be moved into a locked account.
+        if let Some(client) = locked_recipient {
+            if self.config.locked_account == LockedAccountPolicy::Reject {
+                return Err(DomainError::AccountLocked {
+                    client,
+                    transaction_id: tx.transaction_id,
+                });
+            }
+        }
+
+        match tx.kind {
+            TransactionKind::Deposit { amount } => {
+                let before = account.clone();
+                let deposited = charged(account, &self.config, &tx, |account| {
+                    account.deposit(tx.transaction_id, amount)
+                });
+                self.fail_overdrawn(tx.client, deposited)?;
+
+                self.store(
+                    tx.transaction_id,
+                    StoredTransaction::new(tx.client, amount, tx.timestamp),
+                    [before],
+                )?;
+            }
+            TransactionKind::Withdrawal { amount } => {
+                let before = account.clone();
+                let withdrawn = charged(account, &self.config, &tx, |account| {
+                    account.withdraw(tx.transaction_id, amount, overdraft(&self.config, account))
+                });
+                self.fail_overdrawn(tx.client, withdrawn)?;
+
+                self.store(
+                    tx.transaction_id,
+                    StoredTransaction::new(tx.client, -amount, tx.timestamp),
+                    [before],
+                )?;
+            }
+            TransactionKind::Transfer { to_client, amount } => {
+                let before = account.clone();
+                let withdrawn = charged(account, &self.config, &tx, |account| {
+                    account.withdraw(tx.transaction_id, amount, overdraft(&self.config, account))
+                });
+                self.fail_overdrawn(tx.client, withdrawn)?;
+
+                let recipient = open_account(&mut self.accounts, &self.config, to_client);
+                let recipient_before = recipient.clone();
+                if let Err(error) = recipient.deposit(tx.transaction_id, amount) {
+                    self.accounts.insert(tx.client, before);
+                    return Err(error);
+                }
+
+                self.store(
+                    tx.transaction_id,
+                    StoredTransaction {
+                        recipient: Some(to_client),
+                        ..StoredTransaction::new(tx.client, amount, tx.timestamp)
+                    },
+                    [recipient_before, before],
+                )?;
+            }
+            // Adjustments are back-office corrections, so neither the lock nor
+            // the overdraft limit apply to them.
+            TransactionKind::Adjustment { amount } => {
+                account.adjust(tx.transaction_id, amount)?;
+            }
+            TransactionKind::Unlock if self.config.allow_unlock => account.unlock(),
+            TransactionKind::Unlock => {
+                return Err(DomainError::UnlockNotAllowed {
+                    client: tx.client,
+                    transaction_id: tx.transaction_id,
+                })
+            }
+            TransactionKind::Refund { amount } => {
+                // Like disputes, refunds of unknown transactions are ignored.
+                let Some(mut stored) = self.transactions.get(tx.transaction_id)? else {
+                    return Ok(Outcome::Ignored(Ignored::UnknownTransaction));
+                };
+                if stored.client != tx.client {
+                    return Err(DomainError::ForeignTransaction {
+                        client: tx.client,
+                        transaction_id: tx.transaction_id,
+                        owner: stored.client,
+                    });
+                }
+                let refundable = stored.recipient.is_none()
+                    && stored.amount > Decimal::ZERO
+                    && matches!(
+                        stored.state,
+                        DisputeState::Undisputed | DisputeState::Resolved
+                    );
+                if !refundable {
+                    return Err(DomainError::NotRefundable {
+                        transaction_id: tx.transaction_id,
+                    });
+                }
+                if amount > stored.amount {
+                    return Err(DomainError::ExcessiveRefund {
+                        transaction_id: tx.transaction_id,
+                        amount,
+                        refundable: stored.amount,
+                    });
+                }
+
+                let before = account.clone();
+                let overdraft = overdraft(&self.config, account);
+                let refunded = account.withdraw(tx.transaction_id, amount, overdraft);
+                self.fail_overdrawn(tx.client, refunded)?;
+
+                // Only what is left of the deposit can be disputed later.
+                stored.amount -= amount;
+                self.store(tx.transaction_id, stored, [before])?;
+            }
+            TransactionKind::Dispute { .. }
+            | TransactionKind::Resolve
+            | TransactionKind::Chargeback => {
+                // References to unknown transactions, or transitions the dispute
+                // lifecycle doesn't allow, are ignored.
+                let Some(mut stored) = self.transactions.get(tx.transaction_id)? else {
+                    return Ok(Outcome::Ignored(Ignored::UnknownTransaction));
+                };
+                if stored.client != tx.client {
+                    return Err(DomainError::ForeignTransaction {
+                        client: tx.client,
+                        transaction_id: tx.transaction_id,
+                        owner: stored.client,
+                    });
+                }
+                let Some(state) = stored.state.next(&tx.kind) else {
+                    return Ok(Outcome::Ignored(Ignored::DisputeState));
+                };
+                if state == DisputeState::Disputed
+                    && expired(self.config.dispute_window, stored.timestamp, tx.timestamp)
+                {
+                    return Err(DomainError::DisputeExpired {
+                        client: tx.client,
+                        transaction_id: tx.transaction_id,
+                    });
+                }
+
+                // Resolves and chargebacks settle whatever the dispute held.
+                let disputed = match tx.kind {
+                    TransactionKind::Dispute {
+                        amount: Some(amount),
+                    } if amount > stored.amount.abs() => {
+                        return Err(DomainError::ExcessiveDispute {
+                            transaction_id: tx.transaction_id,
+                            amount,
+                            disputable: stored.amount.abs(),
+                        });
+                    }
+                    TransactionKind::Dispute {
+                        amount: Some(amount),
+                    } if stored.amount.is_sign_negative() => -amount,
+                    TransactionKind::Dispute {
+                        amount: Some(amount),
+                    } => amount,
+                    TransactionKind::Dispute { amount: None } => stored.amount,
+                    _ => stored.disputed,
+                };
+
+                let mut before = vec![account.clone()];
+                match (state, stored.recipient) {
+                    (DisputeState::Disputed, None) => account.hold(tx.transaction_id, disputed)?,
+                    (DisputeState::Resolved, None) => {
+                        account.release(tx.transaction_id, disputed)?
+                    }
+                    (DisputeState::ChargedBack, None) => {
+                        account.charge_back(tx.transaction_id, disputed)?
+                    }
+                    (state, Some(recipient)) => {
+                        before.extend(self.accounts.get(&recipient).cloned());
+                        settle_transfer(
+                            &mut self.accounts,
+                            tx.transaction_id,
+                            tx.client,
+                            recipient,
+                            disputed,
+                            state,
+                        )?
+                    }
+                    (DisputeState::Undisputed, None) => {
+                        unreachable!("no transition leads back to undisputed")
+                    }
+                }
+                stored.state = state;
+                stored.disputed = disputed;
+                self.store(tx.transaction_id, stored, before)?;
+            }
+        }
+
+        Ok(Outcome::Applied)
+    }
+
+    /// Store `stored` as transaction `transaction_id`, putting the accounts
+    /// it changed back as they were `before` if the store fails, so the
+    /// transaction changes nothing.
+    fn store(
+        &mut self,
+        transaction_id: u64,
+        stored: StoredTransaction,
+        before: impl IntoIterator<Item = Account>,
+    ) -> DomainResult<()> {
+        let stored = self.transactions.insert(transaction_id, stored);
+        if stored.is_err() {
+            for account in before {
+                self.accounts.insert(account.client(), account);
+            }
+        }
+
+        Ok(stored?)
+    }
+
+    /// Fail if `client` was given up on by
+    /// [`NegativeBalancePolicy::ErrorClient`].
+    fn check_client(&self, client: u16, transaction_id: u64) -> DomainResult<()> {
+        if self.failed_clients.contains(&client) {
+            return Err(DomainError::ClientFailed {
+                client,
+                transaction_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Fail if `tx` is in another currency than the account of `client`, if
+    /// it has one yet.
+    fn check_currency(&self, client: u16, tx: &Transaction) -> DomainResult<()> {
+        let (Some(currency), Some(account_currency)) = (&tx.currency, self.currencies.get(&client))
+        else {
+            return Ok(());
+        };
+
+        if account_currency != currency {
+            return Err(DomainError::CurrencyMismatch {
+                client,
+                transaction_id: tx.transaction_id,
+                currency: currency.clone(),
+                account_currency: account_currency.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Convert the total of every account into the base currency of `rates`.
+    ///
+    /// Accounts without transactions in a given currency are taken to be in
+    /// the base currency already.
+    pub fn convert(&mut self, rates: &Rates) -> DomainResult<()> {
+        for account in self.accounts.values_mut() {
+            let currency = self
+                .currencies
+                .get(&account.client())
+                .map_or(rates.base(), String::as_str);
+
+            account.convert(currency.to_owned(), rates.rate(currency)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Give up on `client` if `withdrawn` failed for lack of funds and the
+    /// policy says so.
+    fn fail_overdrawn(&mut self, client: u16, withdrawn: DomainResult<()>) -> DomainResult<()> {
+        if let Err(DomainError::NoAvailableFundsToWithdraw { .. }) = withdrawn {
+            if self.config.negative_balance == NegativeBalancePolicy::ErrorClient {
+                self.failed_clients.insert(client);
+            }
+        }
+
+        withdrawn
+    }
+
+    /// Apply every transaction in order, stopping at the first one that can't
+    /// be parsed or applied.
+    pub fn process_all<E>(
+        &mut self,
+        txns: impl IntoIterator<Item = std::result::Result<Transaction, E>>,
+    ) -> Result<()>
+    where
+        Error: From<E>,
+    {
+        for tx in txns {
+            self.process(tx?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply every transaction in order, skipping the ones that can't be
+    /// parsed or applied.
+    ///
+    /// Returns why each skipped transaction was skipped, in input order.
+    pub fn process_all_lenient<E>(
+        &mut self,
+        txns: impl IntoIterator<Item = std::result::Result<Transaction, E>>,
+    ) -> Vec<Error>
+    where
+        Error: From<E>,
+    {
+        txns.into_iter()
+            .filter_map(|tx| {
+                tx.map_err(Error::from)
+                    .and_then(|tx| Ok(self.process(tx)?))
+                    .err()
+            })
+            .collect()
+    }
+
+    /// Consume the engine, returning every account touched so far, except
+    /// for the ones of failed clients.
+    pub fn into_accounts(self) -> impl Iterator<Item = Account> {
+        let failed_clients = self.failed_clients;
+
+        self.accounts
+            .into_values()
+            .filter(move |account| !failed_clients.contains(&account.client()))
+    }
+}
+
+/// Account of `client`, opening it if this is its first transaction.
+fn open_account<'a>(
+    accounts: &'a mut Map<u16, Account>,
+    config: &Config,
+    client: u16,
+) -> &'a mut Account {
+    accounts.entry(client).or_insert_with(|| {
+        let mut account = Account::new(client);
+        if let Some(limits) = &config.overdraft {
+            account = account.with_overdraft(limits.limit(client));
+        }
+        if config.audit_adjustments {
+            account = account.with_adjustments();
+        }
+        if config.fees.is_some() {
+            account = account.with_fees();
+        }
+        account
+    })
+}
+
+/// Apply a transaction to `account` along with its fee, if any, leaving the
+/// account untouched if either fails.
+fn charged(
+    account: &mut Account,
+    config: &Config,
+    tx: &Transaction,
+    apply: impl FnOnce(&mut Account) -> DomainResult<()>,
+) -> DomainResult<()> {
+    let before = account.clone();
+    let fee = match &config.fees {
+        Some(fees) => fees.fee(tx)?,
+        None => None,
+    };
+
+    let result = apply(account).and_then(|()| match fee {
+        Some(fee) => account.charge_fee(tx.transaction_id, fee, overdraft(config, account)),
+        None => Ok(()),
+    });
+    if result.is_err() {
+        *account = before;
+    }
+
+    result
+}
+
+/// How far below zero `account` can go, or `None` if there is no limit.
+fn overdraft(config: &Config, account: &Account) -> Option<Decimal> {
+    match config.negative_balance {
+        NegativeBalancePolicy::AllowOverdraft => None,
+        _ => Some(account.overdraft().unwrap_or_default()),
+    }
+}
+
+/// Whether a dispute at `disputed_at` comes too late for a transaction at
+/// `happened_at`. Without both timestamps there is no telling, so it doesn't.
+fn expired(
+    window: Option<Duration>,
+    happened_at: Option<DateTime<Utc>>,
+    disputed_at: Option<DateTime<Utc>>,
+) -> bool {
+    match (window, happened_at, disputed_at) {
+        (Some(window), Some(happened_at), Some(disputed_at)) => (disputed_at - happened_at)
+            .to_std()
+            .is_ok_and(|elapsed| elapsed > window),
+        _ => false,
+    }
+}
+
+/// Apply a dispute lifecycle `state` to a transfer.
+///
+/// The transferred funds are held in the recipient's account while disputed.
+/// A chargeback gives them back to the sender, whose account gets locked like
+/// on any other chargeback.
+fn settle_transfer(
+    accounts: &mut Map<u16, Account>,
+    transaction_id: u64,
+    sender: u16,
+    recipient: u16,
+    amount: Decimal,
+    state: DisputeState,
+) -> DomainResult<()> {
+    fn account(accounts: &mut Map<u16, Account>, client: u16) -> &mut Account {
+        accounts
+            .get_mut(&client)
+            .expect("both sides of a transfer have an account")
+    }
+
+    match state {
+        DisputeState::Disputed => account(accounts, recipient).hold(transaction_id, amount),
+        DisputeState::Resolved => account(accounts, recipient).release(transaction_id, amount),
+        DisputeState::ChargedBack => {
+            account(accounts, sender).deposit(transaction_id, amount)?;
+            if let Err(error) = account(accounts, recipient).remove_held(transaction_id, amount) {
+                account(accounts, sender).withdraw(transaction_id, amount, None)?;
+                return Err(error);
+            }
+            account(accounts, sender).lock();
+            Ok(())
+        }
+        DisputeState::Undisputed => unreachable!("no transition leads back to undisputed"),
+    }
+}
+
+/// Process transactions one at a time, in the order they are yielded.
+///
+/// Nothing is buffered besides each client's account and the amounts that may
+/// still be disputed, so `txns` can be a lazy reader over an arbitrarily large
+/// input.
+pub fn process<E>(
+    txns: impl IntoIterator<Item = std::result::Result<Transaction, E>>,
+) -> Result<Vec<Account>>
+where
+    Error: From<E>,
+{
+    let mut engine = TransactionEngine::new();
+    engine.process_all(txns)?;
+
+    Ok(engine.into_accounts().collect())
+}
+
+/// Outcome of [`process_lenient`].
+#[derive(Debug)]
+pub struct Report {
+    pub accounts: Vec<Account>,
+    /// Why each skipped transaction couldn't be parsed or applied, in input
+    /// order.
+    pub skipped: Vec<Error>,
+}
+
+/// Same as [`process`], but transactions that can't be parsed or applied are
+/// skipped instead of aborting, leaving the accounts untouched.
+pub fn process_lenient<E>(
+    txns: impl IntoIterator<Item = std::result::Result<Transaction, E>>,
+) -> Report
+where
+    Error: From<E>,
+{
+    let mut engine = TransactionEngine::new();
+    let skipped = engine.process_all_lenient(txns);
+
+    Report {
+        accounts: engine.into_accounts().collect(),
+        skipped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::{
+        config::{FeeSchedule, OverdraftLimits},
+        *,
+    };
+    use crate::domain::transaction::tx;
+
+    fn run(transactions: Vec<Transaction>) -> Result<Vec<Account>> {
+        process(transactions.into_iter().map(Ok::<_, Error>))
+    }
+
+    #[test]
+    fn withdrawal_checked_against_current_balance() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(50.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+        ];
+
+        let error = run(transactions).unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::BusinessError(DomainError::NoAvailableFundsToWithdraw { client: 1 })
+        ));
+    }
+
+    #[test]
+    fn incremental_processing() {
+        let mut engine = TransactionEngine::new();
+
+        engine
+            .process(Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            })
+            .unwrap();
+        engine
+            .process(Transaction {
+                client: 2,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(50.0) },
+            })
+            .unwrap();
+
+        assert_eq!(engine.accounts().count(), 2);
+
+        let error = engine
+            .process(Transaction {
+                client: 2,
+                transaction_id: 3,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(60.0) },
+            })
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            DomainError::NoAvailableFundsToWithdraw { client: 2 }
+        ));
+
+        engine
+            .process(Transaction {
+                client: 2,
+                transaction_id: 4,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(10.0) },
+            })
+            .unwrap();
+
+        let account = engine.accounts().find(|a| a.client() == 2).unwrap();
+        assert_eq!(account.total(), dec!(40.0));
+        assert_eq!(account.available(), dec!(40.0));
+    }
+
+    #[test]
+    fn lenient_skips_invalid_withdrawal() {
+        let transactions = vec![
+            Ok(Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(20.0) },
+            }),
+            Ok(Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(50.0) },
+            }),
+            Err(Error::FileError(std::io::ErrorKind::InvalidData.into())),
+            Ok(Transaction {
+                client: 1,
+                transaction_id: 3,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(5.0) },
+            }),
+        ];
+
+        let report = process_lenient(transactions);
+        let account = &report.accounts[0];
+
+        assert_eq!(report.skipped.len(), 2);
+        assert!(matches!(
+            report.skipped[0],
+            Error::BusinessError(DomainError::NoAvailableFundsToWithdraw { client: 1 })
+        ));
+        assert_eq!(account.total(), dec!(15.0));
+        assert_eq!(account.available(), dec!(15.0));
+    }
+
+    #[test]
+    fn dispute_after_resolve_is_ignored() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Resolve,
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(100.0));
+        assert_eq!(account.available(), dec!(100.0));
+        assert_eq!(account.held(), dec!(0.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn chargeback_after_resolve_is_ignored() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Resolve,
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Chargeback,
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(100.0));
+        assert_eq!(account.available(), dec!(100.0));
+        assert_eq!(account.held(), dec!(0.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn resolve_without_dispute_is_ignored() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Resolve,
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Chargeback,
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(100.0));
+        assert_eq!(account.available(), dec!(100.0));
+        assert_eq!(account.held(), dec!(0.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn single_deposit() {
+        let transactions = vec![Transaction {
+            client: 1,
+            transaction_id: 1,
+            timestamp: None,
+            currency: None,
+            kind: TransactionKind::Deposit {
+                amount: dec!(100.0),
+            },
+        }];
+
+        let accounts = run(transactions).unwrap();
+        assert_eq!(accounts.len(), 1);
+
+        let account = &accounts[0];
+        assert_eq!(account.client(), 1);
+        assert_eq!(account.total(), dec!(100.0));
+        assert_eq!(account.available(), dec!(100.0));
+        assert_eq!(account.held(), dec!(0.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn deposit_and_withdrawal() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(30.0) },
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(70.0));
+        assert_eq!(account.available(), dec!(70.0));
+        assert_eq!(account.held(), dec!(0.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn withdrawal_exceeds_balance() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(50.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal {
+                    amount: dec!(100.0),
+                },
+            },
+        ];
+
+        let result = run(transactions);
+        let error = result.unwrap_err();
+
+        // Used this to skip deriving Eq to Error.
+        assert!(matches!(
+            error,
+            Error::BusinessError(DomainError::NoAvailableFundsToWithdraw { client: 1 })
+        ));
+    }
+
+    #[test]
+    fn dispute_transaction() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(50.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(150.0));
+        assert_eq!(account.available(), dec!(50.0));
+        assert_eq!(account.held(), dec!(100.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn dispute_and_resolve() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Resolve,
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(100.0));
+        assert_eq!(account.available(), dec!(100.0));
+        assert_eq!(account.held(), dec!(0.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn outcomes() {
+        let mut engine = TransactionEngine::new();
+        let mut apply = |transaction_id, kind| {
+            engine.apply(Transaction {
+                client: 1,
+                transaction_id,
+                timestamp: None,
+                currency: None,
+                kind,
+            })
+        };
+
+        assert_eq!(
+            apply(1, TransactionKind::Deposit { amount: dec!(1) }).unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            apply(2, TransactionKind::Dispute { amount: None }).unwrap(),
+            Outcome::Ignored(Ignored::UnknownTransaction)
+        );
+        assert_eq!(
+            apply(1, TransactionKind::Resolve).unwrap(),
+            Outcome::Ignored(Ignored::DisputeState)
+        );
+        assert!(apply(3, TransactionKind::Withdrawal { amount: dec!(2) }).is_err());
+    }
+
+    #[test]
+    fn metrics() {
+        let mut engine = TransactionEngine::new();
+        for (transaction_id, kind) in [
+            (1, TransactionKind::Deposit { amount: dec!(1) }),
+            (2, TransactionKind::Deposit { amount: dec!(2) }),
+            (3, TransactionKind::Deposit { amount: dec!(3) }),
+            (4, TransactionKind::Withdrawal { amount: dec!(1) }),
+            (1, TransactionKind::Dispute { amount: None }),
+            (2, TransactionKind::Dispute { amount: None }),
+            (2, TransactionKind::Resolve),
+            (3, TransactionKind::Dispute { amount: None }),
+            (3, TransactionKind::Chargeback),
+        ] {
+            engine
+                .process(Transaction {
+                    client: 1,
+                    transaction_id,
+                    timestamp: None,
+                    currency: None,
+                    kind,
+                })
+                .unwrap();
+        }
+
+        assert_eq!(
+            engine.metrics().unwrap(),
+            HashMap::from([(
+                1,
+                Metrics {
+                    deposits: 3,
+                    withdrawals: 1,
+                    volume: dec!(7),
+                    disputes: 3,
+                    open_disputes: 1,
+                    chargebacks: 1,
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn dispute_and_chargeback() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(50.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Chargeback,
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(50.0));
+        assert_eq!(account.available(), dec!(50.0));
+        assert_eq!(account.held(), dec!(0.0));
+        assert!(account.locked());
+    }
+
+    #[test]
+    fn dispute_and_chargeback_withdrawal() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(30.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Chargeback,
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Resolve,
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        // The withdrawn amount held by the dispute is taken back out, leaving the
+        // balances as they were right after the withdrawal.
+        assert_eq!(account.total(), dec!(70.0));
+        assert_eq!(account.available(), dec!(70.0));
+        assert_eq!(account.held(), dec!(0.0));
+        assert!(account.locked());
+    }
+
+    #[test]
+    fn dispute_withdrawal() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(30.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        // IDK how to deal with disputed withdrawals, but this looks like it makes sense
+        // to me: although the user has 100 in account, he could use only 70,
+        // because 30 is held.
+        assert_eq!(account.total(), dec!(100.0));
+        assert_eq!(account.available(), dec!(70.0));
+        assert_eq!(account.held(), dec!(30.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn dispute_and_resolve_withdrawal() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(30.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Resolve,
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(100.0));
+        assert_eq!(account.available(), dec!(100.0));
+        assert_eq!(account.held(), dec!(0.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn dispute_nonexistent_transaction() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 999,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(100.0));
+        assert_eq!(account.available(), dec!(100.0));
+        assert_eq!(account.held(), dec!(0.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn multiple_clients() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 2,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(200.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 3,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(20.0) },
+            },
+            Transaction {
+                client: 2,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+        ];
+
+        let mut accounts = run(transactions).unwrap();
+        accounts.sort_by_key(|a| a.client());
+
+        assert_eq!(accounts.len(), 2);
+
+        // Client 1
+        assert_eq!(accounts[0].client(), 1);
+        assert_eq!(accounts[0].total(), dec!(80.0));
+        assert_eq!(accounts[0].available(), dec!(80.0));
+        assert_eq!(accounts[0].held(), dec!(0.0));
+        assert!(!accounts[0].locked());
+
+        // Client 2
+        assert_eq!(accounts[1].client(), 2);
+        assert_eq!(accounts[1].total(), dec!(200.0));
+        assert_eq!(accounts[1].available(), dec!(0.0));
+        assert_eq!(accounts[1].held(), dec!(200.0));
+        assert!(!accounts[1].locked());
+    }
+
+    #[test]
+    fn only_disputes_and_resolves() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Resolve,
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 3,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Chargeback,
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(0.0));
+        assert_eq!(account.available(), dec!(0.0));
+        assert_eq!(account.held(), dec!(0.0));
+        // Chargebacks only apply to disputed transactions.
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn complex_scenario() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(1000.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(500.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 3,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal {
+                    amount: dec!(200.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 4,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Resolve,
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 3,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(1600.0));
+        assert_eq!(account.available(), dec!(1400.0));
+        assert_eq!(account.held(), dec!(200.0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn precision_handling() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal {
+                    amount: dec!(50.567891),
+                },
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(49.4321));
+    }
+
+    #[test]
+    fn multiple_disputes_same_transaction() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+        ];
+
+        let accounts = run(transactions).unwrap();
+        let account = &accounts[0];
+
+        assert_eq!(account.total(), dec!(100.0));
+        assert_eq!(account.available(), dec!(0.0));
+        assert_eq!(account.held(), dec!(100.0));
+        assert!(!account.locked());
+    }
+
+    fn locked_account_transactions() -> Vec<Transaction> {
+        vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(50.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Chargeback,
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 3,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(20.0) },
+            },
+        ]
+    }
+
+    #[test]
+    fn locked_account_rejects_withdrawal() {
+        let mut engine = TransactionEngine::new();
+
+        let errors = engine.process_all_lenient(
+            locked_account_transactions()
+                .into_iter()
+                .map(Ok::<_, Error>),
+        );
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors[0],
+            Error::BusinessError(DomainError::AccountLocked {
+                client: 1,
+                transaction_id: 3
+            })
+        ));
+
+        let account = engine.accounts().next().unwrap();
+        assert_eq!(account.available(), dec!(50.0));
+        assert!(account.locked());
+    }
+
+    #[test]
+    fn locked_account_allowed_by_policy() {
+        let mut engine = TransactionEngine::with_config(Config {
+            locked_account: LockedAccountPolicy::Allow,
+            ..Config::default()
+        });
+
+        engine
+            .process_all(
+                locked_account_transactions()
+                    .into_iter()
+                    .map(Ok::<_, Error>),
+            )
+            .unwrap();
+
+        let account = engine.accounts().next().unwrap();
+        assert_eq!(account.available(), dec!(30.0));
+        assert!(account.locked());
+    }
+
+    #[test]
+    fn dispute_of_another_client_transaction() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 2,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(10.0) },
+            },
+            Transaction {
+                client: 2,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            },
+        ];
+
+        let mut engine = TransactionEngine::new();
+        let errors = engine.process_all_lenient(transactions.into_iter().map(Ok::<_, Error>));
+
+        assert!(matches!(
+            errors[..],
+            [Error::BusinessError(DomainError::ForeignTransaction {
+                client: 2,
+                transaction_id: 1,
+                owner: 1
+            })]
+        ));
+        assert!(engine
+            .accounts()
+            .all(|account| account.held() == Decimal::ZERO));
+    }
+
+    fn duplicate_deposits() -> Vec<Transaction> {
+        vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(100.0),
+                },
+            },
+            Transaction {
+                client: 2,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(10.0) },
+            },
+        ]
+    }
+
+    #[test]
+    fn duplicate_transaction_rejected() {
+        let error = run(duplicate_deposits()).unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::BusinessError(DomainError::DuplicateTransaction {
+                client: 2,
+                transaction_id: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn duplicate_transaction_keeps_first() {
+        let mut engine = TransactionEngine::with_config(Config {
+            duplicate_transaction: DuplicatePolicy::KeepFirst,
+            ..Config::default()
+        });
+
+        engine
+            .process_all(duplicate_deposits().into_iter().map(Ok::<_, Error>))
+            .unwrap();
+
+        let account = engine.accounts().find(|a| a.client() == 2).unwrap();
+        assert_eq!(account.total(), dec!(0.0));
+    }
+
+    #[test]
+    fn non_positive_amounts_rejected() {
+        let mut engine = TransactionEngine::new();
+
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: dec!(-50.0),
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(0) },
+            },
+        ];
+        let errors = engine.process_all_lenient(transactions.into_iter().map(Ok::<_, Error>));
+
+        assert!(matches!(
+            errors[..],
+            [
+                Error::BusinessError(DomainError::NonPositiveAmount {
+                    transaction_id: 1,
+                    ..
+                }),
+                Error::BusinessError(DomainError::NonPositiveAmount {
+                    transaction_id: 2,
+                    ..
+                })
+            ]
+        ));
+        assert_eq!(engine.accounts().count(), 0);
+    }
+
+    #[test]
+    fn balance_overflow() {
+        let transactions = vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit {
+                    amount: Decimal::MAX,
+                },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(1) },
+            },
+        ];
+
+        let mut engine = TransactionEngine::new();
+        let errors = engine.process_all_lenient(transactions.into_iter().map(Ok::<_, Error>));
+
+        assert!(matches!(
+            errors[..],
+            [Error::BusinessError(DomainError::BalanceOverflow {
+                client: 1,
+                tx: 2
+            })]
+        ));
+        assert_eq!(engine.accounts().next().unwrap().total(), Decimal::MAX);
+    }
+
+    fn overdrawing_transactions() -> Vec<Transaction> {
+        vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(10.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Withdrawal { amount: dec!(30.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 3,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(5.0) },
+            },
+            Transaction {
+                client: 2,
+                transaction_id: 4,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(1.0) },
+            },
+        ]
+    }
+
+    #[test]
+    fn negative_balance_errors_client() {
+        let mut engine = TransactionEngine::with_config(Config {
+            negative_balance: NegativeBalancePolicy::ErrorClient,
+            ..Config::default()
+        });
+
+        let errors =
+            engine.process_all_lenient(overdrawing_transactions().into_iter().map(Ok::<_, Error>));
+
+        assert!(matches!(
+            errors[..],
+            [
+                Error::BusinessError(DomainError::NoAvailableFundsToWithdraw { client: 1 }),
+                Error::BusinessError(DomainError::ClientFailed {
+                    client: 1,
+                    transaction_id: 3
+                })
+            ]
+        ));
+        let clients: Vec<_> = engine.accounts().map(Account::client).collect();
+        assert_eq!(clients, [2]);
+    }
+
+    #[test]
+    fn negative_balance_allows_overdraft() {
+        let mut engine = TransactionEngine::with_config(Config {
+            negative_balance: NegativeBalancePolicy::AllowOverdraft,
+            ..Config::default()
+        });
+
+        engine
+            .process_all(overdrawing_transactions().into_iter().map(Ok::<_, Error>))
+            .unwrap();
+
+        let account = engine.accounts().find(|a| a.client() == 1).unwrap();
+        assert_eq!(account.available(), dec!(-15.0));
+        assert_eq!(account.total(), dec!(-15.0));
+    }
+
+    #[test]
+    fn overdraft_limits() {
+        let mut engine = TransactionEngine::with_config(Config {
+            overdraft: Some(OverdraftLimits {
+                default: dec!(10.0),
+                clients: HashMap::from([(2, dec!(50.0)), (3, Decimal::MAX)]),
+            }),
+            ..Config::default()
+        });
+        let withdrawal = |client, transaction_id| Transaction {
+            client,
+            transaction_id,
+            timestamp: None,
+            currency: None,
+            kind: TransactionKind::Withdrawal { amount: dec!(20.0) },
+        };
+
+        assert!(matches!(
+            engine.process(withdrawal(1, 1)),
+            Err(DomainError::NoAvailableFundsToWithdraw { client: 1 })
+        ));
+        engine.process(withdrawal(2, 2)).unwrap();
+        engine.process(withdrawal(2, 3)).unwrap();
+        engine
+            .process(Transaction {
+                client: 3,
+                transaction_id: 4,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(1.0) },
+            })
+            .unwrap();
+        // Funds plus the limit don't fit in a decimal.
+        assert!(matches!(
+            engine.process(withdrawal(3, 5)),
+            Err(DomainError::BalanceOverflow { client: 3, tx: 5 })
+        ));
+
+        let mut accounts: Vec<_> = engine.accounts().collect();
+        accounts.sort_by_key(|account| account.client());
+        assert_eq!(accounts[0].available(), dec!(0));
+        assert_eq!(accounts[0].overdraft(), Some(dec!(10.0)));
+        assert_eq!(accounts[1].available(), dec!(-40.0));
+        assert_eq!(accounts[1].overdraft(), Some(dec!(50.0)));
+    }
+
+    fn transfer_transactions() -> Vec<Transaction> {
+        vec![
+            Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(10.0) },
+            },
+            Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Transfer {
+                    to_client: 2,
+                    amount: dec!(4.0),
+                },
+            },
+        ]
+    }
+
+    fn balances(engine: &TransactionEngine, client: u16) -> (Decimal, Decimal, bool) {
+        let account = engine.accounts().find(|a| a.client() == client).unwrap();
+        (account.available(), account.held(), account.locked())
+    }
+
+    #[test]
+    fn transfer() {
+        let mut engine = TransactionEngine::new();
+
+        engine
+            .process_all(transfer_transactions().into_iter().map(Ok::<_, Error>))
+            .unwrap();
+
+        assert_eq!(balances(&engine, 1), (dec!(6.0), dec!(0), false));
+        assert_eq!(balances(&engine, 2), (dec!(4.0), dec!(0), false));
+    }
+
+    #[test]
+    fn transfer_without_funds() {
+        let mut engine = TransactionEngine::new();
+
+        let result = engine.process(Transaction {
+            client: 1,
+            transaction_id: 1,
+            timestamp: None,
+            currency: None,
+            kind: TransactionKind::Transfer {
+                to_client: 2,
+                amount: dec!(4.0),
+            },
+        });
+
+        assert!(matches!(
+            result,
+            Err(DomainError::NoAvailableFundsToWithdraw { client: 1 })
+        ));
+        assert!(engine.accounts().all(|account| account.client() != 2));
+    }
+
+    #[test]
+    fn transfer_to_locked_account() {
+        let mut engine = TransactionEngine::new();
+        for tx in [
+            tx(1, 1, TransactionKind::Deposit { amount: dec!(10.0) }),
+            tx(2, 2, TransactionKind::Deposit { amount: dec!(1.0) }),
+            tx(2, 2, TransactionKind::Dispute { amount: None }),
+            tx(2, 2, TransactionKind::Chargeback),
+        ] {
+            engine.process(tx).unwrap();
+        }
+
+        let result = engine.process(tx(
+            1,
+            3,
+            TransactionKind::Transfer {
+                to_client: 2,
+                amount: dec!(4.0),
+            },
+        ));
+
+        assert!(matches!(
+            result,
+            Err(DomainError::AccountLocked {
+                client: 2,
+                transaction_id: 3
+            })
+        ));
+        assert_eq!(balances(&engine, 1), (dec!(10.0), dec!(0), false));
+        assert_eq!(balances(&engine, 2), (dec!(0), dec!(0), true));
+    }
+
+    #[test]
+    fn transfer_dispute_and_chargeback() {
+        let mut engine = TransactionEngine::new();
+        let mut transactions = transfer_transactions();
+        transactions.push(Transaction {
+            client: 1,
+            transaction_id: 2,
+            timestamp: None,
+            currency: None,
+            kind: TransactionKind::Dispute { amount: None },
+        });
+
+        engine
+            .process_all(transactions.into_iter().map(Ok::<_, Error>))
+            .unwrap();
+
+        assert_eq!(balances(&engine, 1), (dec!(6.0), dec!(0), false));
+        assert_eq!(balances(&engine, 2), (dec!(0), dec!(4.0), false));
+
+        engine
+            .process(Transaction {
+                client: 1,
+                transaction_id: 2,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Chargeback,
+            })
+            .unwrap();
+
+        assert_eq!(balances(&engine, 1), (dec!(10.0), dec!(0), true));
+        assert_eq!(balances(&engine, 2), (dec!(0), dec!(0), false));
+    }
+
+    #[test]
+    fn refund() {
+        let mut engine = TransactionEngine::new();
+        let refund = |amount| Transaction {
+            client: 1,
+            transaction_id: 1,
+            timestamp: None,
+            currency: None,
+            kind: TransactionKind::Refund { amount },
+        };
+
+        engine
+            .process(Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(10.0) },
+            })
+            .unwrap();
+        engine.process(refund(dec!(4.0))).unwrap();
+
+        assert!(matches!(
+            engine.process(refund(dec!(7.0))),
+            Err(DomainError::ExcessiveRefund {
+                transaction_id: 1,
+                ..
+            })
+        ));
+        assert_eq!(balances(&engine, 1), (dec!(6.0), dec!(0), false));
+
+        // Only what wasn't refunded can be disputed.
+        engine
+            .process(Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Dispute { amount: None },
+            })
+            .unwrap();
+
+        assert_eq!(balances(&engine, 1), (dec!(0), dec!(6.0), false));
+        assert!(matches!(
+            engine.process(refund(dec!(1.0))),
+            Err(DomainError::NotRefundable { transaction_id: 1 })
+        ));
+    }
+
+    #[test]
+    fn refund_of_withdrawal() {
+        let mut engine = TransactionEngine::new();
+
+        let result = engine.process_all(
+            [
+                Transaction {
+                    client: 1,
+                    transaction_id: 1,
+                    timestamp: None,
+                    currency: None,
+                    kind: TransactionKind::Deposit { amount: dec!(10.0) },
+                },
+                Transaction {
+                    client: 1,
+                    transaction_id: 2,
+                    timestamp: None,
+                    currency: None,
+                    kind: TransactionKind::Withdrawal { amount: dec!(4.0) },
+                },
+                Transaction {
+                    client: 1,
+                    transaction_id: 2,
+                    timestamp: None,
+                    currency: None,
+                    kind: TransactionKind::Refund { amount: dec!(4.0) },
+                },
+            ]
+            .into_iter()
+            .map(Ok::<_, Error>),
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::BusinessError(DomainError::NotRefundable {
+                transaction_id: 2
+            }))
+        ));
+    }
+
+    #[test]
+    fn adjustments() {
+        let mut engine = TransactionEngine::with_config(Config {
+            audit_adjustments: true,
+            ..Config::default()
+        });
+        let adjustment = |transaction_id, amount| Transaction {
+            client: 1,
+            transaction_id,
+            timestamp: None,
+            currency: None,
+            kind: TransactionKind::Adjustment { amount },
+        };
+
+        engine
+            .process_all(
+                [
+                    Transaction {
+                        client: 1,
+                        transaction_id: 1,
+                        timestamp: None,
+                        currency: None,
+                        kind: TransactionKind::Deposit { amount: dec!(10.0) },
+                    },
+                    adjustment(2, dec!(-12.5)),
+                    adjustment(3, dec!(1.0)),
+                ]
+                .into_iter()
+                .map(Ok::<_, Error>),
+            )
+            .unwrap();
+
+        let account = engine.accounts().next().unwrap();
+        assert_eq!(account.available(), dec!(-1.5));
+        assert_eq!(account.total(), dec!(-1.5));
+        assert_eq!(account.adjusted(), Some(dec!(-11.5)));
+    }
+
+    #[test]
+    fn unlock() {
+        let unlock = || Transaction {
+            client: 1,
+            transaction_id: 6,
+            timestamp: None,
+            currency: None,
+            kind: TransactionKind::Unlock,
+        };
+        let locked_engine = |config| {
+            let mut engine = TransactionEngine::with_config(config);
+            engine.process_all_lenient(
+                locked_account_transactions()
+                    .into_iter()
+                    .map(Ok::<_, Error>),
+            );
+            engine
+        };
+
+        let mut engine = locked_engine(Config::default());
+        assert!(matches!(
+            engine.process(unlock()),
+            Err(DomainError::UnlockNotAllowed { client: 1, .. })
+        ));
+
+        let mut engine = locked_engine(Config {
+            allow_unlock: true,
+            ..Config::default()
+        });
+        engine.process(unlock()).unwrap();
+        engine
+            .process(Transaction {
+                client: 1,
+                transaction_id: 7,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(1.0) },
+            })
+            .unwrap();
+
+        let account = engine.accounts().next().unwrap();
+        assert!(!account.locked());
+        assert_eq!(account.available(), dec!(51.0));
+    }
+
+    #[test]
+    fn partial_dispute_and_chargeback() {
+        let mut engine = TransactionEngine::new();
+        let dispute = |amount| Transaction {
+            client: 1,
+            transaction_id: 1,
+            timestamp: None,
+            currency: None,
+            kind: TransactionKind::Dispute { amount },
+        };
+
+        engine
+            .process(Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Deposit { amount: dec!(10.0) },
+            })
+            .unwrap();
+        assert!(matches!(
+            engine.process(dispute(Some(dec!(11.0)))),
+            Err(DomainError::ExcessiveDispute {
+                transaction_id: 1,
+                ..
+            })
+        ));
+        engine.process(dispute(Some(dec!(3.0)))).unwrap();
+
+        assert_eq!(balances(&engine, 1), (dec!(7.0), dec!(3.0), false));
+
+        engine
+            .process(Transaction {
+                client: 1,
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                kind: TransactionKind::Chargeback,
+            })
+            .unwrap();
+
+        assert_eq!(balances(&engine, 1), (dec!(7.0), dec!(0), true));
+    }
+
+    #[test]
+    fn partial_dispute_of_withdrawal() {
+        let mut engine = TransactionEngine::new();
+
+        engine
+            .process_all(
+                [
+                    Transaction {
+                        client: 1,
+                        transaction_id: 1,
+                        timestamp: None,
+                        currency: None,
+                        kind: TransactionKind::Deposit { amount: dec!(10.0) },
+                    },
+                    Transaction {
+                        client: 1,
+                        transaction_id: 2,
+                        timestamp: None,
+                        currency: None,
+                        kind: TransactionKind::Withdrawal { amount: dec!(6.0) },
+                    },
+                    Transaction {
+                        client: 1,
+                        transaction_id: 2,
+                        timestamp: None,
+                        currency: None,
+                        kind: TransactionKind::Dispute {
+                            amount: Some(dec!(2.0)),
+                        },
+                    },
+                    Transaction {
+                        client: 1,
+                        transaction_id: 2,
+                        timestamp: None,
+                        currency: None,
+                        kind: TransactionKind::Resolve,
+                    },
+                ]
+                .into_iter()
+                .map(Ok::<_, Error>),
+            )
+            .unwrap();
+
+        assert_eq!(balances(&engine, 1), (dec!(6.0), dec!(0), false));
+    }
+
+    #[test]
+    fn dispute_window() {
+        const DAY: i64 = 24 * 60 * 60;
+        let mut engine = TransactionEngine::with_config(Config {
+            dispute_window: Some(Duration::from_secs(90 * DAY as u64)),
+            ..Config::default()
+        });
+        let transaction = |transaction_id, timestamp, kind| Transaction {
+            transaction_id,
+            timestamp: DateTime::from_timestamp(timestamp, 0),
+            currency: None,
+            client: 1,
+            kind,
+        };
+        let deposit = TransactionKind::Deposit { amount: dec!(10.0) };
+        let dispute = TransactionKind::Dispute { amount: None };
+
+        engine.process(transaction(1, 0, deposit)).unwrap();
+        engine.process(transaction(2, 0, deposit)).unwrap();
+
+        assert!(matches!(
+            engine.process(transaction(1, 91 * DAY, dispute)),
+            Err(DomainError::DisputeExpired {
+                client: 1,
+                transaction_id: 1
+            })
+        ));
+        engine.process(transaction(2, 90 * DAY, dispute)).unwrap();
+    }
+
+    #[test]
+    fn currencies() {
+        let mut engine = TransactionEngine::new();
+        let deposit = |client, transaction_id, currency: &str| Transaction {
+            transaction_id,
+            timestamp: None,
+            currency: Some(currency.to_owned()),
+            client,
+            kind: TransactionKind::Deposit { amount: dec!(10.0) },
+        };
+
+        // A rejected transaction doesn't tie its client to a currency.
+        assert!(engine
+            .process(Transaction {
+                kind: TransactionKind::Withdrawal { amount: dec!(1.0) },
+                ..deposit(1, 1, "GBP")
+            })
+            .is_err());
+        engine.process(deposit(1, 1, "USD")).unwrap();
+        engine.process(deposit(2, 2, "EUR")).unwrap();
+        assert!(matches!(
+            engine.process(deposit(1, 3, "EUR")),
+            Err(DomainError::CurrencyMismatch { client: 1, .. })
+        ));
+
+        let rates = Rates::new(
+            "EUR",
+            HashMap::from([(("USD".to_owned(), "EUR".to_owned()), dec!(0.9))]),
+        );
+        engine.convert(&rates).unwrap();
+
+        let mut accounts: Vec<_> = engine.accounts().collect();
+        accounts.sort_by_key(|account| account.client());
+        assert_eq!(accounts[0].currency(), Some("USD"));
+        assert_eq!(accounts[0].total(), dec!(10.0));
+        assert_eq!(accounts[0].base_total(), Some(dec!(9.0)));
+        assert_eq!(accounts[1].currency(), Some("EUR"));
+        assert_eq!(accounts[1].base_total(), Some(dec!(10.0)));
+
+        let rates = Rates::new("GBP", HashMap::new());
+        assert!(matches!(
+            engine.convert(&rates),
+            Err(DomainError::MissingRate { .. })
+        ));
+    }
+
+    #[test]
+    fn fees() {
+        let fees = toml::from_str(
+            r#"
+            [deposit]
+            flat = 1
+
+            [withdrawal]
+            flat = "0.5"
+            percentage = 10
+            "#,
+        )
+        .unwrap();
+        let mut engine = TransactionEngine::with_config(Config {
+            fees: Some(fees),
+            ..Config::default()
+        });
+        let transaction = |transaction_id, kind| Transaction {
+            transaction_id,
+            timestamp: None,
+            currency: None,
+            client: 1,
+            kind,
+        };
+
+        engine
+            .process(transaction(
+                1,
+                TransactionKind::Deposit { amount: dec!(10.0) },
+            ))
+            .unwrap();
+        engine
+            .process(transaction(
+                2,
+                TransactionKind::Withdrawal { amount: dec!(5.0) },
+            ))
+            .unwrap();
+        // 8.0 plus its 1.3 fee is more than the 8.0 left.
+        assert!(matches!(
+            engine.process(transaction(
+                3,
+                TransactionKind::Withdrawal { amount: dec!(8.0) },
+            )),
+            Err(DomainError::NoAvailableFundsToWithdraw { client: 1 })
+        ));
+
+        let account = engine.accounts().next().unwrap();
+        assert_eq!(account.available(), dec!(3.0));
+        assert_eq!(account.fees(), Some(dec!(2.0)));
+    }
+
+    #[test]
+    fn fee_overflow() {
+        let mut engine = TransactionEngine::with_config(Config {
+            fees: Some(toml::from_str("[deposit]\npercentage = 200").unwrap()),
+            ..Config::default()
+        });
+
+        assert!(matches!(
+            engine.process(Transaction {
+                transaction_id: 1,
+                timestamp: None,
+                currency: None,
+                client: 1,
+                kind: TransactionKind::Deposit {
+                    amount: Decimal::MAX,
+                },
+            }),
+            Err(DomainError::BalanceOverflow { client: 1, tx: 1 })
+        ));
+    }
+
+    #[test]
+    fn negative_fees() {
+        for fees in [
+            "[withdrawal]\nflat = -1",
+            "[transfer]\npercentage = \"-0.5\"",
+        ] {
+            let error = toml::from_str::<FeeSchedule>(fees).unwrap_err();
+            assert!(error.message().contains("is negative"), "{error}");
+        }
+        assert!(toml::from_str::<FeeSchedule>("[deposit]\nflat = 0").is_ok());
+    }
+
+    /// Store keeping transactions in memory until `writes` of them, after
+    /// which it fails like a full disk.
+    #[derive(Debug)]
+    struct Full {
+        transactions: Map<u64, StoredTransaction>,
+        writes: usize,
+    }
+
+    impl TransactionStore for Full {
+        fn get(&self, id: u64) -> store::Result<Option<StoredTransaction>> {
+            TransactionStore::get(&self.transactions, id)
+        }
+
+        fn insert(&mut self, id: u64, stored: StoredTransaction) -> store::Result<()> {
+            if self.writes == 0 {
+                return Err(std::io::Error::other("disk full").into());
+            }
+            self.writes -= 1;
+            TransactionStore::insert(&mut self.transactions, id, stored)
+        }
+
+        fn remove(&mut self, id: u64) -> store::Result<()> {
+            TransactionStore::remove(&mut self.transactions, id)
+        }
+
+        fn iter(&self) -> Box<dyn Iterator<Item = store::Result<(u64, StoredTransaction)>> + '_> {
+            TransactionStore::iter(&self.transactions)
+        }
+
+        fn clear(&mut self) -> store::Result<()> {
+            TransactionStore::clear(&mut self.transactions)
+        }
+    }
+
+    #[test]
+    fn store_failure_changes_nothing() {
+        let mut engine = TransactionEngine::new().with_store(Full {
+            transactions: Map::default(),
+            writes: 2,
+        });
+        for (client, transaction_id) in [(1, 1), (2, 2)] {
+            engine
+                .process(tx(
+                    client,
+                    transaction_id,
+                    TransactionKind::Deposit { amount: dec!(10) },
+                ))
+                .unwrap();
+        }
+        let before: Vec<_> = [1, 2].map(|client| engine.account(client).cloned()).into();
+
+        for tx in [
+            tx(1, 3, TransactionKind::Withdrawal { amount: dec!(1) }),
+            tx(
+                1,
+                4,
+                TransactionKind::Transfer {
+                    to_client: 2,
+                    amount: dec!(2),
+                },
+            ),
+            tx(1, 1, TransactionKind::Dispute { amount: None }),
+        ] {
+            assert!(matches!(engine.process(tx), Err(DomainError::Store(_))));
+        }
+        let after: Vec<_> = [1, 2].map(|client| engine.account(client).cloned()).into();
+        assert_eq!(format!("{after:?}"), format!("{before:?}"));
+    }
+}
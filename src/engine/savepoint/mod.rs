@@ -0,0 +1,128 @@
+//! Undoing a run of transactions, for inputs applied all or nothing.
+
+use super::{Map, Outcome, StoredTransaction, TransactionEngine};
+use crate::domain::{
+    account::Account,
+    error::Result as DomainResult,
+    transaction::{Transaction, TransactionKind},
+};
+
+/// What the transactions applied with
+/// [`apply_saved`](TransactionEngine::apply_saved) changed, as it was before
+/// the first of them, for [`rollback`](TransactionEngine::rollback) to put it
+/// back.
+///
+/// Only what they touch is kept, so it takes memory for them rather than for
+/// the whole engine, unlike a snapshot.
+#[derive(Debug, Default)]
+pub struct Savepoint {
+    accounts: Map<u16, Option<Account>>,
+    failed_clients: Map<u16, bool>,
+    currencies: Map<u16, Option<String>>,
+    transactions: Map<u64, Option<StoredTransaction>>,
+}
+
+impl TransactionEngine {
+    /// Like [`apply`](Self::apply), recording in `savepoint` what `tx` may
+    /// change unless it already holds it.
+    pub fn apply_saved(
+        &mut self,
+        tx: Transaction,
+        savepoint: &mut Savepoint,
+    ) -> DomainResult<Outcome> {
+        let stored = self.transactions.get(tx.transaction_id)?;
+        // Transfers, and disputes of transfers, change the recipient too.
+        let recipient = match tx.kind {
+            TransactionKind::Transfer { to_client, .. } => Some(to_client),
+            _ => stored.as_ref().and_then(|stored| stored.recipient),
+        };
+
+        for client in [Some(tx.client), recipient].into_iter().flatten() {
+            savepoint
+                .accounts
+                .entry(client)
+                .or_insert_with(|| self.accounts.get(&client).cloned());
+            savepoint
+                .failed_clients
+                .entry(client)
+                .or_insert_with(|| self.failed_clients.contains(&client));
+            savepoint
+                .currencies
+                .entry(client)
+                .or_insert_with(|| self.currencies.get(&client).cloned());
+        }
+        savepoint
+            .transactions
+            .entry(tx.transaction_id)
+            .or_insert(stored);
+
+        self.apply(tx)
+    }
+
+    /// Put back what the transactions applied with `savepoint` changed,
+    /// failing if the store can't be written to.
+    pub fn rollback(&mut self, savepoint: Savepoint) -> DomainResult<()> {
+        for (client, account) in savepoint.accounts {
+            match account {
+                Some(account) => self.accounts.insert(client, account),
+                None => self.accounts.remove(&client),
+            };
+        }
+        for (client, failed) in savepoint.failed_clients {
+            if !failed {
+                self.failed_clients.remove(&client);
+            }
+        }
+        for (client, currency) in savepoint.currencies {
+            match currency {
+                Some(currency) => self.currencies.insert(client, currency),
+                None => self.currencies.remove(&client),
+            };
+        }
+        for (transaction_id, stored) in savepoint.transactions {
+            match stored {
+                Some(stored) => self.transactions.insert(transaction_id, stored)?,
+                None => self.transactions.remove(transaction_id)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::domain::transaction::tx;
+
+    #[test]
+    fn rollback() {
+        let mut engine = TransactionEngine::new();
+        let transfer = TransactionKind::Transfer {
+            to_client: 2,
+            amount: dec!(4),
+        };
+        engine
+            .process(tx(1, 1, TransactionKind::Deposit { amount: dec!(10) }))
+            .unwrap();
+        let before = engine.state_hash();
+
+        let mut savepoint = Savepoint::default();
+        for tx in [
+            tx(1, 2, transfer),
+            tx(1, 2, TransactionKind::Dispute { amount: None }),
+            tx(3, 3, TransactionKind::Deposit { amount: dec!(1) }),
+        ] {
+            engine.apply_saved(tx, &mut savepoint).unwrap();
+        }
+        engine.rollback(savepoint).unwrap();
+
+        assert_eq!(engine.state_hash(), before);
+        assert!(engine.account(2).is_none());
+        assert!(engine.account(3).is_none());
+        // The transfer is forgotten, so it can be applied again.
+        engine.process(tx(1, 2, transfer)).unwrap();
+    }
+}
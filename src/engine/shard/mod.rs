@@ -0,0 +1,607 @@
+//! Transactions applied in parallel by engines each owning a shard of the
+//! clients.
+
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender, SyncSender},
+        Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use super::{Map, Outcome, StoredTransaction, TransactionEngine};
+use crate::{
+    domain::{
+        account::Account,
+        error::Error as DomainError,
+        transaction::{Transaction, TransactionKind},
+    },
+    error::{Error, Result},
+};
+
+/// How many transactions are sent to a shard at once.
+const BATCH: usize = 256;
+/// How many batches can wait for each shard before reading blocks.
+const CAPACITY: usize = 16;
+
+/// Account of a client before and after a transaction, if it had one.
+pub type Change = (Option<Account>, Option<Account>);
+
+/// Work for the thread of a shard, done in input order.
+enum Job {
+    /// Transactions of clients of the shard, with their index in the input.
+    Apply(Vec<(usize, Transaction)>),
+    /// Hand the transaction stored with this id, if any, over to another
+    /// shard.
+    Give(u64, Sender<Option<StoredTransaction>>),
+    /// Lend what the shard knows of a client to another shard, waiting for
+    /// it to be handed back.
+    Lend(u16, Sender<Client>, Receiver<Client>),
+    /// Apply a transaction of a client of the shard once other shards handed
+    /// over the transaction stored with its id and the clients it involves,
+    /// then hand the clients back.
+    Cross {
+        index: usize,
+        tx: Transaction,
+        stored: Option<Receiver<Option<StoredTransaction>>>,
+        lent: Vec<(u16, Receiver<Client>, Sender<Client>)>,
+    },
+}
+
+/// What an engine knows of a client, as lent to another shard.
+struct Client {
+    account: Option<Account>,
+    failed: bool,
+    currency: Option<String>,
+}
+
+/// Apply `txns` with `engine` split into `workers` shards, the transactions
+/// of `client` going to shard `client % workers`, each applied by its own
+/// thread, and return the engine they make up once done.
+///
+/// The transactions of a client are applied in input order, but those of
+/// different shards aren't ordered with one another, but for the ones
+/// involving several shards: transfers to clients of another shard, and
+/// transactions whose id was last used on another shard. Those wait for the
+/// other shards to reach them and hand over what they hold of the clients
+/// and the id, so the accounts and errors are the same as with a single
+/// thread. Transactions are read on the calling thread, which waits whenever
+/// the queue of a shard is full.
+///
+/// Errors are handed to `error` as they happen, along with the index of
+/// their transaction in the input, so possibly out of order. The input stops
+/// being read as soon as it returns `true`. Fails if the store of `engine`
+/// can't read its transactions.
+pub fn process<E>(
+    engine: TransactionEngine,
+    workers: usize,
+    txns: impl IntoIterator<Item = std::result::Result<Transaction, E>>,
+    mut error: impl FnMut(usize, Error) -> bool,
+) -> Result<TransactionEngine>
+where
+    Error: From<E>,
+{
+    let workers = workers.max(1);
+    let shard = |client: u16| usize::from(client) % workers;
+
+    let engines = engine.split(workers)?;
+    // Shard each id was last used on, and recipients of transfers with it,
+    // whose accounts disputes of those transfers involve.
+    let mut owners: Map<u64, usize> = Map::default();
+    let mut recipients: Map<u64, Vec<u16>> = Map::default();
+    for (shard, engine) in engines.iter().enumerate() {
+        for stored in engine.transactions.iter() {
+            let (id, stored) = stored.expect("shards keep transactions in memory");
+            owners.insert(id, shard);
+            recipients.entry(id).or_default().extend(stored.recipient);
+        }
+    }
+
+    let engines = thread::scope(|scope| {
+        let (report, reported) = mpsc::channel();
+        let (senders, handles): (Vec<SyncSender<_>>, Vec<_>) = engines
+            .into_iter()
+            .map(|mut engine| {
+                let (sender, receiver) = mpsc::sync_channel::<Job>(CAPACITY);
+                let report = report.clone();
+                let handle = scope.spawn(move || {
+                    for job in receiver {
+                        for (index, error) in engine.work(job) {
+                            // The reader stops listening once it is done.
+                            let _ = report.send((index, Error::BusinessError(error)));
+                        }
+                    }
+                    engine
+                });
+                (sender, handle)
+            })
+            .unzip();
+        drop(report);
+
+        let mut batches: Vec<Vec<_>> = (0..workers).map(|_| Vec::with_capacity(BATCH)).collect();
+        let send = |shard: usize, job: Job| {
+            senders[shard]
+                .send(job)
+                .expect("shards run until the input ends");
+        };
+        let flush = |shard: usize, batches: &mut Vec<Vec<_>>| {
+            if !batches[shard].is_empty() {
+                let batch = std::mem::replace(&mut batches[shard], Vec::with_capacity(BATCH));
+                send(shard, Job::Apply(batch));
+            }
+        };
+        let mut stopped = false;
+        for (index, tx) in txns.into_iter().enumerate() {
+            let failed = match tx.map_err(Error::from) {
+                Ok(tx) => {
+                    let home = shard(tx.client);
+                    let id = tx.transaction_id;
+                    let owner = owners.get(&id).copied().filter(|&owner| owner != home);
+                    let mut clients = recipients.get(&id).cloned().unwrap_or_default();
+                    if let TransactionKind::Transfer { to_client, .. } = tx.kind {
+                        clients.push(to_client);
+                        let recipients = recipients.entry(id).or_default();
+                        if !recipients.contains(&to_client) {
+                            recipients.push(to_client);
+                        }
+                    }
+                    clients.retain(|&client| shard(client) != home);
+                    clients.sort_unstable();
+                    clients.dedup();
+                    let moves_funds = matches!(
+                        tx.kind,
+                        TransactionKind::Deposit { .. }
+                            | TransactionKind::Withdrawal { .. }
+                            | TransactionKind::Transfer { .. }
+                    );
+                    if moves_funds || owner.is_some() {
+                        owners.insert(id, home);
+                    }
+
+                    if owner.is_none() && clients.is_empty() {
+                        batches[home].push((index, tx));
+                        if batches[home].len() == BATCH {
+                            flush(home, &mut batches);
+                        }
+                    } else {
+                        // Earlier transactions of every shard involved go
+                        // first.
+                        flush(home, &mut batches);
+                        let stored = owner.map(|owner| {
+                            flush(owner, &mut batches);
+                            let (give, stored) = mpsc::channel();
+                            send(owner, Job::Give(id, give));
+                            stored
+                        });
+                        let lent = clients
+                            .into_iter()
+                            .map(|client| {
+                                flush(shard(client), &mut batches);
+                                let (lend, lent) = mpsc::channel();
+                                let (give_back, given_back) = mpsc::channel();
+                                send(shard(client), Job::Lend(client, lend, given_back));
+                                (client, lent, give_back)
+                            })
+                            .collect();
+                        send(
+                            home,
+                            Job::Cross {
+                                index,
+                                tx,
+                                stored,
+                                lent,
+                            },
+                        );
+                    }
+                    None
+                }
+                Err(error) => Some((index, error)),
+            };
+
+            for (index, failed) in failed.into_iter().chain(reported.try_iter()) {
+                stopped |= error(index, failed);
+            }
+            if stopped {
+                break;
+            }
+        }
+
+        for shard in 0..workers {
+            flush(shard, &mut batches);
+        }
+        drop(senders);
+        let engines: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().expect("shards don't panic"))
+            .collect();
+        for (index, failed) in reported {
+            error(index, failed);
+        }
+
+        engines
+    });
+
+    Ok(TransactionEngine::merge(engines))
+}
+
+/// Engine that can be shared between threads, like the connections of a
+/// server, applying the transactions of clients of different shards in
+/// parallel.
+///
+/// Clients are sharded like with [`process`], each shard being an engine
+/// behind its own lock. Unlike with [`process`], transfers between clients
+/// of different shards are rejected, and ids are only checked to be unique
+/// within a shard.
+#[derive(Debug)]
+pub struct ConcurrentEngine {
+    shards: Vec<Mutex<TransactionEngine>>,
+}
+
+impl ConcurrentEngine {
+    /// Split `engine` into `shards` engines, at least one, failing if its
+    /// store can't read its transactions.
+    pub fn new(engine: TransactionEngine, shards: usize) -> Result<Self> {
+        Ok(Self {
+            shards: engine
+                .split(shards.max(1))?
+                .into_iter()
+                .map(Mutex::new)
+                .collect(),
+        })
+    }
+
+    /// Apply a transaction, waiting for the other transactions of its shard.
+    pub fn apply(&self, tx: Transaction) -> Result<Outcome> {
+        if let TransactionKind::Transfer { to_client, .. } = tx.kind {
+            if self.shard(to_client) != self.shard(tx.client) {
+                return Err(Error::ShardedTransfer(tx.transaction_id));
+            }
+        }
+
+        Ok(self.lock(tx.client).apply(tx)?)
+    }
+
+    /// Apply a transaction like [`apply`](Self::apply), returning as well the
+    /// accounts of the clients it involves before and after it, with no other
+    /// transaction applied in between.
+    pub fn apply_with_accounts(&self, tx: Transaction) -> Result<(Outcome, Vec<Change>)> {
+        let mut clients = vec![tx.client];
+        if let TransactionKind::Transfer { to_client, .. } = tx.kind {
+            if self.shard(to_client) != self.shard(tx.client) {
+                return Err(Error::ShardedTransfer(tx.transaction_id));
+            }
+            clients.push(to_client);
+        }
+
+        let mut engine = self.lock(tx.client);
+        let accounts = |engine: &TransactionEngine| -> Vec<_> {
+            clients
+                .iter()
+                .map(|&client| engine.account(client).cloned())
+                .collect()
+        };
+        let before = accounts(&engine);
+        let outcome = engine.apply(tx)?;
+
+        Ok((outcome, before.into_iter().zip(accounts(&engine)).collect()))
+    }
+
+    /// Account of `client`, as it is now.
+    pub fn account(&self, client: u16) -> Option<Account> {
+        self.lock(client).account(client).cloned()
+    }
+
+    /// Every account touched so far, in no particular order, each shard
+    /// being read in turn.
+    pub fn accounts(&self) -> Vec<Account> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.lock().unwrap_or_else(|error| error.into_inner());
+                shard.accounts().cloned().collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Engine made up of every shard.
+    pub fn into_engine(self) -> TransactionEngine {
+        TransactionEngine::merge(
+            self.shards
+                .into_iter()
+                .map(|shard| {
+                    shard
+                        .into_inner()
+                        .unwrap_or_else(|error| error.into_inner())
+                })
+                .collect(),
+        )
+    }
+
+    fn shard(&self, client: u16) -> usize {
+        usize::from(client) % self.shards.len()
+    }
+
+    /// Engine of the shard of `client`, even if a thread panicked while
+    /// holding it, as a failing transaction leaves accounts untouched.
+    fn lock(&self, client: u16) -> MutexGuard<'_, TransactionEngine> {
+        self.shards[self.shard(client)]
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl TransactionEngine {
+    /// Engines with the same configuration, holding the accounts and
+    /// transactions of the clients `client % shards` gives them, in memory.
+    /// Fails if the store of the engine can't read its transactions.
+    fn split(self, shards: usize) -> Result<Vec<TransactionEngine>> {
+        let mut engines: Vec<_> = (0..shards)
+            .map(|_| TransactionEngine::with_config(self.config.clone()))
+            .collect();
+        let shard = |client: u16| usize::from(client) % shards;
+
+        for stored in self.transactions.iter() {
+            let (id, stored) = stored.map_err(DomainError::from)?;
+            engines[shard(stored.client)]
+                .transactions
+                .insert(id, stored)
+                .expect("shards keep transactions in memory");
+        }
+        for (client, account) in self.accounts {
+            engines[shard(client)].accounts.insert(client, account);
+        }
+        for client in self.failed_clients {
+            engines[shard(client)].failed_clients.insert(client);
+        }
+        for (client, currency) in self.currencies {
+            engines[shard(client)].currencies.insert(client, currency);
+        }
+
+        Ok(engines)
+    }
+
+    /// Do `job` as the engine of a shard, returning the errors of its
+    /// transactions with their index in the input.
+    fn work(&mut self, job: Job) -> Vec<(usize, DomainError)> {
+        match job {
+            Job::Apply(txns) => txns
+                .into_iter()
+                .filter_map(|(index, tx)| Some((index, self.process(tx).err()?)))
+                .collect(),
+            Job::Give(id, give) => {
+                let stored = self
+                    .transactions
+                    .get(id)
+                    .expect("shards keep transactions in memory");
+                self.transactions
+                    .remove(id)
+                    .expect("shards keep transactions in memory");
+                // The other shard only stops listening if it panicked.
+                let _ = give.send(stored);
+                Vec::new()
+            }
+            Job::Lend(client, lend, given_back) => {
+                let _ = lend.send(self.lend(client));
+                self.take_back(client, given_back.recv().expect("shards don't panic"));
+                Vec::new()
+            }
+            Job::Cross {
+                index,
+                tx,
+                stored,
+                lent,
+            } => {
+                let stored = stored.and_then(|stored| stored.recv().expect("shards don't panic"));
+                if let Some(stored) = stored {
+                    self.transactions
+                        .insert(tx.transaction_id, stored)
+                        .expect("shards keep transactions in memory");
+                }
+                for (client, lent, _) in &lent {
+                    self.take_back(*client, lent.recv().expect("shards don't panic"));
+                }
+                let error = self.process(tx).err();
+                for (client, _, give_back) in lent {
+                    let _ = give_back.send(self.lend(client));
+                }
+
+                error.map(|error| (index, error)).into_iter().collect()
+            }
+        }
+    }
+
+    /// Take out what the engine knows of `client`.
+    fn lend(&mut self, client: u16) -> Client {
+        Client {
+            account: self.accounts.remove(&client),
+            failed: self.failed_clients.remove(&client),
+            currency: self.currencies.remove(&client),
+        }
+    }
+
+    /// Put back what [`lend`](Self::lend) took out of an engine.
+    fn take_back(&mut self, client: u16, lent: Client) {
+        self.accounts
+            .extend(lent.account.map(|account| (client, account)));
+        if lent.failed {
+            self.failed_clients.insert(client);
+        }
+        self.currencies
+            .extend(lent.currency.map(|currency| (client, currency)));
+    }
+
+    /// Engine holding the accounts and transactions of every engine of
+    /// [`split`](Self::split).
+    fn merge(engines: Vec<TransactionEngine>) -> TransactionEngine {
+        let mut engines = engines.into_iter();
+        let mut merged = engines.next().unwrap_or_default();
+
+        for engine in engines {
+            for stored in engine.transactions.iter() {
+                let (id, stored) = stored.expect("shards keep transactions in memory");
+                merged
+                    .transactions
+                    .insert(id, stored)
+                    .expect("shards keep transactions in memory");
+            }
+            merged.accounts.extend(engine.accounts);
+            merged.failed_clients.extend(engine.failed_clients);
+            merged.currencies.extend(engine.currencies);
+        }
+
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::domain::{error::Error as DomainError, transaction::tx};
+
+    fn txns() -> Vec<Transaction> {
+        let mut txns = Vec::new();
+        for id in 0..1000u64 {
+            let client = (id % 7) as u16;
+            let amount = Decimal::new(id as i64 % 50 + 1, 1);
+            txns.push(tx(client, id, TransactionKind::Deposit { amount }));
+            txns.push(tx(
+                client,
+                id + 10_000,
+                TransactionKind::Withdrawal { amount },
+            ));
+            if id % 3 == 0 {
+                txns.push(tx(client, id, TransactionKind::Dispute { amount: None }));
+            }
+            if id % 9 == 0 {
+                txns.push(tx(client, id, TransactionKind::Chargeback));
+            }
+        }
+        txns
+    }
+
+    /// Engine `process` makes of `txns` with `workers` shards, and its errors
+    /// in input order.
+    fn processed(
+        workers: usize,
+        txns: impl IntoIterator<Item = std::result::Result<Transaction, DomainError>>,
+    ) -> (TransactionEngine, Vec<String>) {
+        let mut errors = Vec::new();
+        let engine = process(TransactionEngine::new(), workers, txns, |index, error| {
+            errors.push((index, error.to_string()));
+            false
+        })
+        .unwrap();
+        errors.sort();
+
+        (engine, errors.into_iter().map(|(_, error)| error).collect())
+    }
+
+    #[test]
+    fn sharded() {
+        let mut sequential = TransactionEngine::new();
+        let sequential_errors: Vec<_> = txns()
+            .into_iter()
+            .filter_map(|tx| sequential.process(tx).err())
+            .map(|error| error.to_string())
+            .collect();
+        let (engine, errors) = processed(3, txns().into_iter().map(Ok));
+
+        assert_eq!(engine.state_hash(), sequential.state_hash());
+        assert_eq!(engine.metrics().unwrap(), sequential.metrics().unwrap());
+        assert_eq!(errors, sequential_errors);
+    }
+
+    #[test]
+    fn concurrent() {
+        let engine = ConcurrentEngine::new(TransactionEngine::new(), 4).unwrap();
+        let mut sequential = TransactionEngine::new();
+        for tx in txns() {
+            let _ = sequential.process(tx);
+        }
+
+        thread::scope(|scope| {
+            // One thread per client, as with a connection per client.
+            for client in 0..7 {
+                let engine = &engine;
+                scope.spawn(move || {
+                    for tx in txns().into_iter().filter(|tx| tx.client == client) {
+                        let _ = engine.apply(tx);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(engine.accounts().len(), 7);
+        assert_eq!(
+            engine.account(3).unwrap().total(),
+            sequential.account(3).unwrap().total()
+        );
+        let engine = engine.into_engine();
+        assert_eq!(engine.state_hash(), sequential.state_hash());
+    }
+
+    #[test]
+    fn across_shards() {
+        let transfer = |client, transaction_id, to_client| {
+            tx(
+                client,
+                transaction_id,
+                TransactionKind::Transfer {
+                    to_client,
+                    amount: Decimal::ONE,
+                },
+            )
+        };
+        let txns = || {
+            let mut txns = Vec::new();
+            for client in 0..8 {
+                txns.push(tx(
+                    client,
+                    u64::from(client),
+                    TransactionKind::Deposit {
+                        amount: Decimal::TEN,
+                    },
+                ));
+            }
+            for id in 100..300u64 {
+                let client = (id % 8) as u16;
+                let other = ((id * 3 + 1) % 8) as u16;
+                txns.push(match id % 5 {
+                    0 => transfer(client, id, other),
+                    // Ids of other clients, taken or disputed.
+                    1 => tx(
+                        client,
+                        id - 1,
+                        TransactionKind::Deposit {
+                            amount: Decimal::ONE,
+                        },
+                    ),
+                    2 => tx(client, id - 2, TransactionKind::Dispute { amount: None }),
+                    // Disputes of transfers, which involve their recipient.
+                    3 => tx(
+                        (id - 3) as u16 % 8,
+                        id - 3,
+                        TransactionKind::Dispute { amount: None },
+                    ),
+                    _ => tx((id - 4) as u16 % 8, id - 4, TransactionKind::Chargeback),
+                });
+            }
+            txns.into_iter().map(Ok::<_, DomainError>)
+        };
+
+        let (single, single_errors) = processed(1, txns());
+        let (sharded, errors) = processed(4, txns());
+
+        assert_eq!(sharded.state_hash(), single.state_hash());
+        assert_eq!(sharded.metrics().unwrap(), single.metrics().unwrap());
+        assert_eq!(errors, single_errors);
+        assert!(
+            single_errors
+                .iter()
+                .any(|error| error.contains("belongs to")),
+            "{single_errors:?}"
+        );
+    }
+}
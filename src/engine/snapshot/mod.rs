@@ -0,0 +1,288 @@
+//! Exporting and importing the state of an engine, to resume long runs or
+//! hand it over to another process.
+
+use std::fmt;
+
+use rmp_serde::{decode, encode};
+use serde::{
+    de::{self, DeserializeSeed, IgnoredAny, MapAccess, Visitor},
+    ser::{self, SerializeMap},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::{
+    store::{self, TransactionStore},
+    Map, Set, StoredTransaction, TransactionEngine,
+};
+use crate::{
+    domain::{
+        account::{Account, Exact},
+        error::Error as DomainError,
+    },
+    error::{Error, Result},
+};
+
+/// Version of the snapshot format, bumped whenever it changes.
+const VERSION: u32 = 1;
+
+/// State of an engine as written to a snapshot, borrowing from it.
+#[derive(Serialize)]
+struct Saved<'a> {
+    version: u32,
+    accounts: Vec<SavedAccount<'a>>,
+    transactions: SavedTransactions<'a>,
+    failed_clients: &'a Set<u16>,
+    currencies: &'a Map<u16, String>,
+}
+
+struct SavedAccount<'a>(&'a Account);
+
+/// Transactions of the store, saved as a map whatever the store.
+struct SavedTransactions<'a>(&'a dyn TransactionStore);
+
+impl Serialize for SavedTransactions<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for stored in self.0.iter() {
+            let (transaction_id, stored) = stored.map_err(ser::Error::custom)?;
+            map.serialize_entry(&transaction_id, &stored)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for SavedAccount<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        Exact::serialize(self.0, serializer)
+    }
+}
+
+/// Version of a snapshot, read before the rest which may not be readable.
+#[derive(Serialize, Deserialize)]
+struct Version {
+    version: u32,
+}
+
+/// State of an engine as read from a snapshot, but for its transactions,
+/// which go to the store as they are read.
+#[derive(Default)]
+struct Restored {
+    accounts: Vec<RestoredAccount>,
+    failed_clients: Set<u16>,
+    currencies: Map<u16, String>,
+}
+
+#[derive(Deserialize)]
+struct RestoredAccount(#[serde(with = "Exact")] Account);
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum Field {
+    Accounts,
+    Transactions,
+    FailedClients,
+    Currencies,
+    #[serde(other)]
+    Other,
+}
+
+/// Reads a snapshot, inserting its transactions into `store` one at a time
+/// rather than holding them all, and keeping the error of the store if it
+/// fails.
+struct Restoring<'a> {
+    store: &'a mut dyn TransactionStore,
+    failed: Option<store::Error>,
+}
+
+impl<'de> DeserializeSeed<'de> for &mut Restoring<'_> {
+    type Value = Restored;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Restored, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for &mut Restoring<'_> {
+    type Value = Restored;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an engine snapshot")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Restored, A::Error> {
+        let mut restored = Restored::default();
+        while let Some(field) = map.next_key()? {
+            match field {
+                Field::Accounts => restored.accounts = map.next_value()?,
+                Field::Transactions => map.next_value_seed(RestoredTransactions(&mut *self))?,
+                Field::FailedClients => restored.failed_clients = map.next_value()?,
+                Field::Currencies => restored.currencies = map.next_value()?,
+                Field::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(restored)
+    }
+}
+
+/// Transactions of a snapshot, inserted into the store as they are read.
+struct RestoredTransactions<'r, 'a>(&'r mut Restoring<'a>);
+
+impl<'de> DeserializeSeed<'de> for RestoredTransactions<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for RestoredTransactions<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("transactions by id")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        while let Some((transaction_id, stored)) = map.next_entry::<u64, StoredTransaction>()? {
+            if let Err(error) = self.0.store.insert(transaction_id, stored) {
+                let message = error.to_string();
+                self.0.failed = Some(error);
+                return Err(de::Error::custom(message));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl TransactionEngine {
+    /// State of the engine as versioned MessagePack, for
+    /// [`restore`](Self::restore) to pick up from, possibly in another
+    /// process.
+    ///
+    /// Unlike accounts output, balances are saved with full precision, and
+    /// so are the transactions that could still be disputed, whatever their
+    /// store, which fails the snapshot if it can't read them. The
+    /// configuration isn't.
+    pub fn snapshot(&self) -> Result<Vec<u8>> {
+        let saved = Saved {
+            version: VERSION,
+            accounts: self.accounts.values().map(SavedAccount).collect(),
+            transactions: SavedTransactions(&*self.transactions),
+            failed_clients: &self.failed_clients,
+            currencies: &self.currencies,
+        };
+
+        Ok(encode::to_vec_named(&saved)?)
+    }
+
+    /// Replace the state of the engine with a [`snapshot`](Self::snapshot).
+    ///
+    /// The configuration and the store of the engine are kept, so the
+    /// configuration should be the one of the engine the snapshot was taken
+    /// from. Transactions are written to the store as they are read, so one
+    /// that fails part way leaves the store with some of them, to be
+    /// restored again. Snapshots of another version fail with
+    /// [`Error::SnapshotVersion`].
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<()> {
+        let Version { version } = decode::from_slice(snapshot)?;
+        if version != VERSION {
+            return Err(Error::SnapshotVersion(version));
+        }
+
+        self.transactions.clear().map_err(DomainError::from)?;
+        let mut restoring = Restoring {
+            store: &mut *self.transactions,
+            failed: None,
+        };
+        let restored = restoring.deserialize(&mut decode::Deserializer::from_read_ref(snapshot));
+        if let Some(error) = restoring.failed {
+            return Err(DomainError::from(error).into());
+        }
+        let restored = restored?;
+
+        self.accounts = restored
+            .accounts
+            .into_iter()
+            .map(|RestoredAccount(account)| (account.client(), account))
+            .collect();
+        self.failed_clients = restored.failed_clients;
+        self.currencies = restored.currencies;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        domain::transaction::{tx, TransactionKind},
+        engine::store::spill::SpillStore,
+    };
+
+    #[test]
+    fn round_trip() {
+        let mut engine = TransactionEngine::new();
+        engine
+            .process(tx(1, 1, TransactionKind::Deposit { amount: dec!(1.5) }))
+            .unwrap();
+        engine
+            .process(tx(1, 2, TransactionKind::Withdrawal { amount: dec!(0.5) }))
+            .unwrap();
+
+        let mut restored = TransactionEngine::new();
+        restored.restore(&engine.snapshot().unwrap()).unwrap();
+
+        // Disputes still find the transactions seen before the snapshot.
+        restored
+            .process(tx(1, 1, TransactionKind::Dispute { amount: None }))
+            .unwrap();
+        let account = restored.accounts().next().unwrap();
+        assert_eq!(account.available(), dec!(-0.5));
+        assert_eq!(account.held(), dec!(1.5));
+        assert_eq!(account.total(), dec!(1));
+    }
+
+    #[test]
+    fn restored_into_store() {
+        let mut engine = TransactionEngine::new();
+        for transaction_id in 1..=10 {
+            engine
+                .process(tx(
+                    1,
+                    transaction_id,
+                    TransactionKind::Deposit { amount: dec!(1) },
+                ))
+                .unwrap();
+        }
+
+        // Room for a single transaction, so restoring spills as it goes.
+        let mut restored = TransactionEngine::new().with_store(SpillStore::new(0));
+        restored.restore(&engine.snapshot().unwrap()).unwrap();
+
+        assert_eq!(restored.state_hash(), engine.state_hash());
+        assert_eq!(restored.metrics().unwrap(), engine.metrics().unwrap());
+    }
+
+    #[test]
+    fn other_version() {
+        let snapshot = encode::to_vec_named(&Version { version: 0 }).unwrap();
+
+        assert!(matches!(
+            TransactionEngine::new().restore(&snapshot),
+            Err(Error::SnapshotVersion(0))
+        ));
+    }
+}
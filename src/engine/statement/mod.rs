@@ -0,0 +1,225 @@
+//! Per-client statements over a period.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+use super::TransactionEngine;
+use crate::domain::{
+    error::{Error as DomainError, Result as DomainResult},
+    transaction::{Transaction, TransactionKind},
+    PRECISION,
+};
+
+/// Statements of every client over the days from `from` to `to`, both
+/// included, built while applying timestamped transactions.
+///
+/// Transactions are expected in timestamp order: opening balances are the
+/// ones found when the first transaction of the period arrives.
+#[derive(Debug)]
+pub struct Statements {
+    from: NaiveDate,
+    to: NaiveDate,
+    /// Totals before the period, once it started.
+    opening: Option<HashMap<u16, Decimal>>,
+    items: HashMap<u16, Vec<Item>>,
+}
+
+/// Transactions of a client over a period, between its total balance before
+/// and after it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Statement {
+    pub client: u16,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub opening: Decimal,
+    pub items: Vec<Item>,
+    pub closing: Decimal,
+}
+
+/// A transaction on a statement.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Item {
+    pub timestamp: DateTime<Utc>,
+    pub transaction_id: u64,
+    /// Type of the transaction, like `deposit`.
+    pub kind: &'static str,
+    /// Change of the total balance, negative for withdrawals.
+    pub amount: Decimal,
+    /// Total balance right after the transaction.
+    pub balance: Decimal,
+}
+
+impl Statements {
+    pub fn new(from: NaiveDate, to: NaiveDate) -> Self {
+        Self {
+            from,
+            to,
+            opening: None,
+            items: HashMap::new(),
+        }
+    }
+
+    /// Apply `tx` to `engine`, listing it on the statements of its client
+    /// and, for transfers, of the recipient if it falls within the period.
+    ///
+    /// Transactions after the period are left out, and failed ones are on no
+    /// statement.
+    pub fn process(&mut self, engine: &mut TransactionEngine, tx: Transaction) -> DomainResult<()> {
+        let Some(timestamp) = tx.timestamp else {
+            return Err(DomainError::MissingTimestamp {
+                transaction_id: tx.transaction_id,
+            });
+        };
+        let day = timestamp.date_naive();
+        if day > self.to {
+            return Ok(());
+        }
+        if day >= self.from && self.opening.is_none() {
+            self.opening = Some(totals(engine));
+        }
+
+        let mut clients = vec![tx.client];
+        if let TransactionKind::Transfer { to_client, .. } = tx.kind {
+            clients.push(to_client);
+        }
+        let before: Vec<_> = clients
+            .iter()
+            .map(|&client| (client, total(engine, client)))
+            .collect();
+        let (transaction_id, kind) = (tx.transaction_id, tx.kind.name());
+
+        engine.process(tx)?;
+
+        if day >= self.from {
+            for (client, before) in before {
+                let balance = total(engine, client);
+                self.items.entry(client).or_default().push(Item {
+                    timestamp,
+                    transaction_id,
+                    kind,
+                    amount: balance - before,
+                    balance,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Statement of every account of `engine`, in client order.
+    pub fn finish(mut self, engine: &TransactionEngine) -> impl Iterator<Item = Statement> {
+        let closing = totals(engine);
+        let opening = self.opening.take().unwrap_or_else(|| closing.clone());
+
+        let clients: BTreeMap<_, _> = closing.into_iter().collect();
+        clients.into_iter().map(move |(client, closing)| Statement {
+            client,
+            from: self.from,
+            to: self.to,
+            opening: opening
+                .get(&client)
+                .copied()
+                .unwrap_or(Decimal::new(0, PRECISION)),
+            items: self.items.remove(&client).unwrap_or_default(),
+            closing,
+        })
+    }
+}
+
+fn totals(engine: &TransactionEngine) -> HashMap<u16, Decimal> {
+    engine
+        .accounts()
+        .map(|account| (account.client(), account.total()))
+        .collect()
+}
+
+/// Total balance of `client`, zero before its first transaction.
+fn total(engine: &TransactionEngine, client: u16) -> Decimal {
+    engine
+        .account(client)
+        .map_or(Decimal::new(0, PRECISION), |account| account.total())
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[test]
+    fn statements() {
+        let mut engine = TransactionEngine::new();
+        let day = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let timestamp = |day: u32| {
+            NaiveDate::from_ymd_opt(2024, 1, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        let mut statements = Statements::new(day(10), day(20));
+
+        for (transaction_id, date, kind) in [
+            (1, 1, TransactionKind::Deposit { amount: dec!(100) }),
+            (
+                2,
+                15,
+                TransactionKind::Transfer {
+                    to_client: 2,
+                    amount: dec!(30),
+                },
+            ),
+            (3, 25, TransactionKind::Withdrawal { amount: dec!(10) }),
+        ] {
+            statements
+                .process(
+                    &mut engine,
+                    Transaction {
+                        transaction_id,
+                        timestamp: Some(timestamp(date)),
+                        currency: None,
+                        client: 1,
+                        kind,
+                    },
+                )
+                .unwrap();
+        }
+
+        let statements: Vec<_> = statements.finish(&engine).collect();
+        assert_eq!(
+            statements,
+            [
+                Statement {
+                    client: 1,
+                    from: day(10),
+                    to: day(20),
+                    opening: dec!(100),
+                    items: vec![Item {
+                        timestamp: timestamp(15),
+                        transaction_id: 2,
+                        kind: "transfer",
+                        amount: dec!(-30),
+                        balance: dec!(70),
+                    }],
+                    closing: dec!(70),
+                },
+                Statement {
+                    client: 2,
+                    from: day(10),
+                    to: day(20),
+                    opening: dec!(0),
+                    items: vec![Item {
+                        timestamp: timestamp(15),
+                        transaction_id: 2,
+                        kind: "transfer",
+                        amount: dec!(30),
+                        balance: dec!(30),
+                    }],
+                    closing: dec!(30),
+                },
+            ]
+        );
+    }
+}
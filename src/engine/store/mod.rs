@@ -0,0 +1,182 @@
+//! Where an engine keeps the transactions that may still be disputed, which
+//! is most of its memory on large inputs.
+
+use std::{collections::HashMap, fmt::Debug, hash::BuildHasher};
+
+use thiserror::Error;
+
+use super::{Map, StoredTransaction};
+
+#[cfg(feature = "sled")]
+pub mod sled;
+pub mod spill;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Failure of a store to read or write transactions, like a full disk or a
+/// database that was corrupted.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("could not access stored transactions: {0}")]
+    FileError(#[from] std::io::Error),
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+    #[cfg(feature = "sled")]
+    #[error("sled error: {0}")]
+    SledError(#[from] ::sled::Error),
+    #[error("invalid stored transaction: {0}")]
+    InvalidTransaction(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Storage for the transactions an engine keeps, keyed by id.
+///
+/// Stores on disk can fail, in which case the engine fails the transaction
+/// being applied, leaving accounts as they were.
+pub trait TransactionStore: Debug + Send {
+    fn get(&self, transaction_id: u64) -> Result<Option<StoredTransaction>>;
+
+    /// Store `stored`, replacing the transaction with the same id if any.
+    fn insert(&mut self, transaction_id: u64, stored: StoredTransaction) -> Result<()>;
+
+    /// Remove the transaction with id `transaction_id`, if any.
+    fn remove(&mut self, transaction_id: u64) -> Result<()>;
+
+    fn contains(&self, transaction_id: u64) -> Result<bool> {
+        Ok(self.get(transaction_id)?.is_some())
+    }
+
+    /// Every transaction stored, in no particular order.
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(u64, StoredTransaction)>> + '_>;
+
+    fn clear(&mut self) -> Result<()>;
+}
+
+/// Transactions kept in memory, the default, which never fails.
+impl<S: BuildHasher + Debug + Send> TransactionStore for HashMap<u64, StoredTransaction, S> {
+    fn get(&self, transaction_id: u64) -> Result<Option<StoredTransaction>> {
+        Ok(HashMap::get(self, &transaction_id).cloned())
+    }
+
+    fn insert(&mut self, transaction_id: u64, stored: StoredTransaction) -> Result<()> {
+        HashMap::insert(self, transaction_id, stored);
+        Ok(())
+    }
+
+    fn remove(&mut self, transaction_id: u64) -> Result<()> {
+        HashMap::remove(self, &transaction_id);
+        Ok(())
+    }
+
+    fn contains(&self, transaction_id: u64) -> Result<bool> {
+        Ok(self.contains_key(&transaction_id))
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(u64, StoredTransaction)>> + '_> {
+        Box::new(HashMap::iter(self).map(|(&id, stored)| Ok((id, stored.clone()))))
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        HashMap::clear(self);
+        Ok(())
+    }
+}
+
+impl Default for Box<dyn TransactionStore> {
+    fn default() -> Self {
+        Box::new(Map::<u64, StoredTransaction>::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use rust_decimal_macros::dec;
+
+    use super::{spill::SpillStore, *};
+    use crate::{
+        domain::transaction::{tx, Transaction, TransactionKind},
+        engine::TransactionEngine,
+    };
+
+    /// Check `store` keeps transactions like the default one does, on its
+    /// own and under an engine, which every store must.
+    fn conforms(mut store: impl TransactionStore + 'static) {
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 5);
+        let stored = |client, amount| StoredTransaction {
+            recipient: Some(2),
+            ..StoredTransaction::new(client, amount, timestamp)
+        };
+        store.insert(1, stored(1, dec!(1.5))).unwrap();
+        store.insert(2, stored(1, dec!(-2))).unwrap();
+        store.insert(1, stored(3, dec!(3))).unwrap();
+        let replaced = store.get(1).unwrap().unwrap();
+        assert_eq!((replaced.client, replaced.amount), (3, dec!(3)));
+        assert_eq!(
+            (replaced.recipient, replaced.timestamp),
+            (Some(2), timestamp)
+        );
+        assert!(store.contains(2).unwrap());
+        assert!(!store.contains(3).unwrap());
+        store.remove(2).unwrap();
+        store.remove(3).unwrap();
+        assert!(store.get(2).unwrap().is_none());
+        let ids: Vec<_> = store.iter().map(|stored| stored.unwrap().0).collect();
+        assert_eq!(ids, [1]);
+        store.clear().unwrap();
+        assert_eq!(store.iter().count(), 0);
+
+        let mut engine = TransactionEngine::new().with_store(store);
+        let mut memory = TransactionEngine::new();
+        for tx in [
+            || tx(1, 3, TransactionKind::Deposit { amount: dec!(3) }),
+            || tx(1, 1, TransactionKind::Deposit { amount: dec!(1.25) }),
+            || tx(1, 2, TransactionKind::Withdrawal { amount: dec!(0.5) }),
+            || tx(1, 1, TransactionKind::Deposit { amount: dec!(9) }),
+            || tx(1, 1, TransactionKind::Dispute { amount: None }),
+            || tx(1, 2, TransactionKind::Dispute { amount: None }),
+            || tx(1, 1, TransactionKind::Resolve),
+            || tx(1, 3, TransactionKind::Refund { amount: dec!(1) }),
+            || tx(1, 3, TransactionKind::Dispute { amount: None }),
+            || Transaction {
+                timestamp: DateTime::from_timestamp(1_700_000_000, 5),
+                ..tx(2, 4, TransactionKind::Deposit { amount: dec!(2) })
+            },
+        ] {
+            let stored = engine.apply(tx());
+            assert_eq!(format!("{stored:?}"), format!("{:?}", memory.apply(tx())));
+        }
+
+        assert_eq!(engine.state_hash(), memory.state_hash());
+        assert_eq!(engine.metrics().unwrap(), memory.metrics().unwrap());
+        assert_eq!(engine.account(1).unwrap().held(), dec!(2.5));
+    }
+
+    #[test]
+    fn hash_map() {
+        conforms(Map::<u64, StoredTransaction>::default());
+    }
+
+    #[test]
+    fn spill() {
+        // Room for a single transaction, so every insert spills.
+        conforms(SpillStore::new(0));
+        conforms(SpillStore::new(1 << 20));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite() {
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        conforms(super::sqlite::SqliteStore::open(&path).unwrap());
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled() {
+        let dir = tempfile::tempdir().unwrap();
+        conforms(super::sled::SledStore::open(dir.path()).unwrap());
+    }
+}
@@ -0,0 +1,99 @@
+//! Transactions kept in a sled database, for inputs with more transactions
+//! than fit in memory.
+
+use std::path::Path;
+
+use rmp_serde::{decode, encode};
+
+use super::{Error as StoreError, Result as StoreResult, TransactionStore};
+use crate::{engine::StoredTransaction, error::Result};
+
+/// Transactions in a sled database, keyed by their id in big-endian bytes
+/// and encoded as MessagePack.
+///
+/// sled flushes writes in the background and once the store is dropped.
+#[derive(Debug)]
+pub struct SledStore {
+    db: ::sled::Db,
+}
+
+impl SledStore {
+    /// Open the database in directory `path`, creating it if needed.
+    /// Transactions it already holds are kept.
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            db: ::sled::open(path)?,
+        })
+    }
+}
+
+impl TransactionStore for SledStore {
+    fn get(&self, transaction_id: u64) -> StoreResult<Option<StoredTransaction>> {
+        self.db
+            .get(transaction_id.to_be_bytes())?
+            .map(|stored| decoded(&stored))
+            .transpose()
+    }
+
+    fn insert(&mut self, transaction_id: u64, stored: StoredTransaction) -> StoreResult<()> {
+        let stored = encode::to_vec(&stored)
+            .map_err(|error| StoreError::InvalidTransaction(error.to_string()))?;
+        self.db.insert(transaction_id.to_be_bytes(), stored)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, transaction_id: u64) -> StoreResult<()> {
+        self.db.remove(transaction_id.to_be_bytes())?;
+        Ok(())
+    }
+
+    fn contains(&self, transaction_id: u64) -> StoreResult<bool> {
+        Ok(self.db.contains_key(transaction_id.to_be_bytes())?)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = StoreResult<(u64, StoredTransaction)>> + '_> {
+        Box::new(self.db.iter().map(|entry| {
+            let (id, stored) = entry?;
+            let id = id
+                .as_ref()
+                .try_into()
+                .map_err(|_| StoreError::InvalidTransaction(format!("id of {} bytes", id.len())))?;
+
+            Ok((u64::from_be_bytes(id), decoded(&stored)?))
+        }))
+    }
+
+    fn clear(&mut self) -> StoreResult<()> {
+        Ok(self.db.clear()?)
+    }
+}
+
+/// Transaction encoded as `stored`, failing if it isn't one.
+fn decoded(stored: &[u8]) -> StoreResult<StoredTransaction> {
+    decode::from_slice(stored).map_err(|error| StoreError::InvalidTransaction(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[test]
+    fn reopened() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = SledStore::open(dir.path()).unwrap();
+        store
+            .insert(1, StoredTransaction::new(1, dec!(1.5), None))
+            .unwrap();
+        store
+            .insert(2, StoredTransaction::new(2, dec!(-2), None))
+            .unwrap();
+        store.remove(2).unwrap();
+        drop(store);
+
+        let store = SledStore::open(dir.path()).unwrap();
+        assert_eq!(store.get(1).unwrap().unwrap().amount, dec!(1.5));
+        assert!(!store.contains(2).unwrap());
+    }
+}
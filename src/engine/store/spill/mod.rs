@@ -0,0 +1,395 @@
+//! Transactions kept in memory up to a budget, and spilled to temporary files
+//! beyond it.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    mem,
+};
+
+use chrono::DateTime;
+use rust_decimal::Decimal;
+
+use super::{Error, Result, TransactionStore};
+use crate::engine::{DisputeState, Map, StoredTransaction};
+
+/// Size of a spilled transaction: its id, client, amount, disputed amount,
+/// recipient, timestamp and state, which also marks removed ones.
+const RECORD: usize = 8 + 2 + 16 + 16 + 3 + 13 + 1;
+
+/// State byte of the record of a removed transaction.
+const REMOVED: u8 = 4;
+
+/// How many spilled transactions are read at once when reading them in
+/// order.
+const BLOCK: u64 = 1024;
+
+/// Transactions in memory until they take about `max_memory` bytes, at which
+/// point they are all spilled to a new temporary file, sorted by id.
+///
+/// Spilled transactions are found back by a binary search over each file
+/// whose ids could hold them, newest first, and paged back into memory when
+/// updated, like by a dispute. As ids mostly grow with the input, a lookup
+/// usually searches a few files. Files are merged as they pile up, the newest
+/// two whenever the older one isn't bigger, so there are only about as many
+/// as the log of how many spills there were, and as many open files.
+///
+/// Removing a spilled transaction keeps a tombstone in its place, spilled
+/// like transactions are, until a merge into the oldest file leaves nothing
+/// for it to hide.
+#[derive(Debug)]
+pub struct SpillStore {
+    /// Transactions in memory, `None` for removed ones that may be spilled.
+    hot: Map<u64, Option<StoredTransaction>>,
+    /// How many transactions are kept in memory.
+    capacity: usize,
+    /// Spilled files, oldest first.
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug)]
+struct Segment {
+    file: File,
+    first: u64,
+    last: u64,
+    len: u64,
+}
+
+/// Transaction of a spilled record, `None` if it was removed.
+type Record = (u64, Option<StoredTransaction>);
+
+impl SpillStore {
+    pub fn new(max_memory: u64) -> Self {
+        // Hash maps take about twice the size of their entries.
+        let entry = 2 * mem::size_of::<Record>() as u64;
+        Self {
+            hot: Map::default(),
+            capacity: (max_memory / entry).max(1) as usize,
+            segments: Vec::new(),
+        }
+    }
+
+    /// Write every transaction in memory to a new file, then merge the
+    /// newest files while the one before isn't bigger.
+    fn spill(&mut self) -> Result<()> {
+        let mut spilled: Vec<_> = self.hot.iter().collect();
+        spilled.sort_unstable_by_key(|(id, _)| **id);
+        let spilled = spilled
+            .into_iter()
+            .map(|(&id, stored)| Ok((id, stored.clone())));
+        let segment = Segment::write(spilled, self.segments.is_empty())?;
+        self.segments.push(segment);
+        self.hot.clear();
+
+        while let [.., older, newer] = &self.segments[..] {
+            if older.len > newer.len {
+                break;
+            }
+            let segments = self.segments.len() - 2;
+            let merged = Segment::write(merged(&self.segments[segments..]), segments == 0)?;
+            self.segments.truncate(segments);
+            self.segments.push(merged);
+        }
+
+        Ok(())
+    }
+
+    fn spilled(&self, transaction_id: u64) -> Result<Option<StoredTransaction>> {
+        let segments = self
+            .segments
+            .iter()
+            .rev()
+            .filter(|segment| (segment.first..=segment.last).contains(&transaction_id));
+        for segment in segments {
+            if let Some(stored) = segment.find(transaction_id)? {
+                return Ok(stored);
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Records of `segments`, in id order, those of newer segments replacing
+/// those of older ones with the same id.
+fn merged(segments: &[Segment]) -> impl Iterator<Item = Result<Record>> + '_ {
+    let mut heads: Vec<_> = segments
+        .iter()
+        .map(|segment| segment.records().peekable())
+        .collect();
+
+    std::iter::from_fn(move || {
+        // Errors come out first, which ends the merge for its readers.
+        for head in &mut heads {
+            if let Some(Err(_)) = head.peek() {
+                return head.next();
+            }
+        }
+        let id = heads
+            .iter_mut()
+            .filter_map(|head| match head.peek() {
+                Some(Ok((id, _))) => Some(*id),
+                _ => None,
+            })
+            .min()?;
+        heads
+            .iter_mut()
+            .filter_map(|head| head.next_if(|next| matches!(next, Ok((next, _)) if *next == id)))
+            .last()
+    })
+}
+
+impl Segment {
+    /// Write `records`, sorted by id, to a new file, leaving out tombstones
+    /// if it is the `oldest`, as there is nothing older for them to hide.
+    fn write(records: impl Iterator<Item = Result<Record>>, oldest: bool) -> Result<Self> {
+        let mut writer = BufWriter::new(tempfile::tempfile()?);
+        let (mut first, mut last, mut len) = (None, 0, 0);
+        for record in records {
+            let (id, stored) = record?;
+            if oldest && stored.is_none() {
+                continue;
+            }
+            writer.write_all(&encode(id, stored.as_ref()))?;
+            first.get_or_insert(id);
+            last = id;
+            len += 1;
+        }
+        let file = writer.into_inner().map_err(|error| error.into_error())?;
+
+        Ok(Self {
+            file,
+            first: first.unwrap_or_default(),
+            last,
+            len,
+        })
+    }
+
+    /// Read `count` records from the one at `index` on.
+    fn read(&self, index: u64, count: u64) -> Result<Vec<u8>> {
+        let mut records = vec![0; count as usize * RECORD];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(index * RECORD as u64))?;
+        file.read_exact(&mut records)?;
+        Ok(records)
+    }
+
+    /// Record of `transaction_id`, if the segment has one.
+    fn find(&self, transaction_id: u64) -> Result<Option<Option<StoredTransaction>>> {
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let (id, stored) = decode(&self.read(middle, 1)?)?;
+            match id.cmp(&transaction_id) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return Ok(Some(stored)),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Every record, in id order, read a block at a time.
+    fn records(&self) -> impl Iterator<Item = Result<Record>> + '_ {
+        (0..self.len)
+            .step_by(BLOCK as usize)
+            .flat_map(
+                move |index| match self.read(index, BLOCK.min(self.len - index)) {
+                    Ok(block) => block.chunks_exact(RECORD).map(decode).collect(),
+                    Err(error) => vec![Err(error)],
+                },
+            )
+    }
+}
+
+impl TransactionStore for SpillStore {
+    fn get(&self, transaction_id: u64) -> Result<Option<StoredTransaction>> {
+        match self.hot.get(&transaction_id) {
+            Some(stored) => Ok(stored.clone()),
+            None => self.spilled(transaction_id),
+        }
+    }
+
+    fn insert(&mut self, transaction_id: u64, stored: StoredTransaction) -> Result<()> {
+        // Shadows any spilled version, as memory is looked up first.
+        self.hot.insert(transaction_id, Some(stored));
+        if self.hot.len() >= self.capacity {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, transaction_id: u64) -> Result<()> {
+        if self.segments.is_empty() {
+            self.hot.remove(&transaction_id);
+            return Ok(());
+        }
+
+        self.hot.insert(transaction_id, None);
+        if self.hot.len() >= self.capacity {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Transactions in memory, then spilled ones read back a block at a
+    /// time, rather than all at once.
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(u64, StoredTransaction)>> + '_> {
+        let hot = self
+            .hot
+            .iter()
+            .filter_map(|(&id, stored)| Some(Ok((id, stored.clone()?))));
+        // Spilled versions are older than those in memory.
+        let spilled = merged(&self.segments)
+            .filter(|record| {
+                record
+                    .as_ref()
+                    .map_or(true, |(id, _)| !self.hot.contains_key(id))
+            })
+            .filter_map(|record| match record {
+                Ok((id, stored)) => Some(Ok((id, stored?))),
+                Err(error) => Some(Err(error)),
+            });
+
+        Box::new(hot.chain(spilled))
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.hot.clear();
+        self.segments.clear();
+        Ok(())
+    }
+}
+
+/// Record of transaction `id`, a tombstone if `stored` is `None`.
+fn encode(id: u64, stored: Option<&StoredTransaction>) -> [u8; RECORD] {
+    let mut record = [0; RECORD];
+    let mut cursor = &mut record[..];
+    let mut put = |bytes: &[u8]| {
+        let (head, tail) = mem::take(&mut cursor).split_at_mut(bytes.len());
+        head.copy_from_slice(bytes);
+        cursor = tail;
+    };
+
+    put(&id.to_le_bytes());
+    let Some(stored) = stored else {
+        record[RECORD - 1] = REMOVED;
+        return record;
+    };
+    put(&stored.client.to_le_bytes());
+    put(&stored.amount.serialize());
+    put(&stored.disputed.serialize());
+    put(&[stored.recipient.is_some() as u8]);
+    put(&stored.recipient.unwrap_or_default().to_le_bytes());
+    put(&[stored.timestamp.is_some() as u8]);
+    put(&stored.timestamp.map_or(0, |t| t.timestamp()).to_le_bytes());
+    put(&stored
+        .timestamp
+        .map_or(0, |t| t.timestamp_subsec_nanos())
+        .to_le_bytes());
+    put(&[match stored.state {
+        DisputeState::Undisputed => 0,
+        DisputeState::Disputed => 1,
+        DisputeState::Resolved => 2,
+        DisputeState::ChargedBack => 3,
+    }]);
+
+    record
+}
+
+fn decode(record: &[u8]) -> Result<Record> {
+    let mut cursor = record;
+
+    let id = u64::from_le_bytes(take(&mut cursor));
+    let client = u16::from_le_bytes(take(&mut cursor));
+    let amount = Decimal::deserialize(take(&mut cursor));
+    let disputed = Decimal::deserialize(take(&mut cursor));
+    let [has_recipient] = take(&mut cursor);
+    let recipient = u16::from_le_bytes(take(&mut cursor));
+    let [has_timestamp] = take(&mut cursor);
+    let seconds = i64::from_le_bytes(take(&mut cursor));
+    let nanos = u32::from_le_bytes(take(&mut cursor));
+    let state = match take(&mut cursor) {
+        [0] => DisputeState::Undisputed,
+        [1] => DisputeState::Disputed,
+        [2] => DisputeState::Resolved,
+        [3] => DisputeState::ChargedBack,
+        [REMOVED] => return Ok((id, None)),
+        [state] => return Err(Error::InvalidTransaction(format!("state {state}"))),
+    };
+    let timestamp =
+        match has_timestamp {
+            1 => Some(DateTime::from_timestamp(seconds, nanos).ok_or_else(|| {
+                Error::InvalidTransaction(format!("timestamp {seconds}.{nanos:09}"))
+            })?),
+            _ => None,
+        };
+
+    let stored = StoredTransaction {
+        client,
+        amount,
+        disputed,
+        recipient: (has_recipient == 1).then_some(recipient),
+        timestamp,
+        state,
+    };
+    Ok((id, Some(stored)))
+}
+
+/// Next `N` bytes of `cursor`.
+fn take<const N: usize>(cursor: &mut &[u8]) -> [u8; N] {
+    let (head, tail) = cursor.split_at(N);
+    *cursor = tail;
+    head.try_into().expect("split at N")
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[test]
+    fn merged_spills() {
+        let mut store = SpillStore::new(0);
+        let stored = |amount| StoredTransaction {
+            client: 1,
+            amount,
+            disputed: Decimal::ZERO,
+            recipient: None,
+            timestamp: None,
+            state: DisputeState::Undisputed,
+        };
+        for id in 0..1000 {
+            store.insert(id, stored(Decimal::from(id))).unwrap();
+        }
+        store.insert(500, stored(dec!(-1))).unwrap();
+
+        // No more files than bits in the number of spills.
+        assert!(store.segments.len() <= 10, "{}", store.segments.len());
+        assert_eq!(store.get(500).unwrap().unwrap().amount, dec!(-1));
+        assert_eq!(store.get(999).unwrap().unwrap().amount, dec!(999));
+        let mut all: Vec<_> = store.iter().collect::<Result<_>>().unwrap();
+        all.sort_by_key(|(id, _)| *id);
+        assert_eq!(all.len(), 1000);
+        assert_eq!(all[500].1.amount, dec!(-1));
+    }
+
+    #[test]
+    fn removed_spills() {
+        let mut store = SpillStore::new(0);
+        for id in 0..1000 {
+            let stored = StoredTransaction::new(1, Decimal::from(id), None);
+            store.insert(id, stored).unwrap();
+            store.remove(id).unwrap();
+        }
+
+        // Tombstones go once merged into the oldest file.
+        let spilled: u64 = store.segments.iter().map(|segment| segment.len).sum();
+        assert!(spilled < 20, "{spilled}");
+        assert!(store.hot.len() <= 1);
+        assert!(store.get(500).unwrap().is_none());
+        assert_eq!(store.iter().count(), 0);
+    }
+}
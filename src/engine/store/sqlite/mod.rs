@@ -0,0 +1,327 @@
+//! Transactions kept in a SQLite database, for inputs with more transactions
+//! than fit in memory.
+
+use std::{
+    iter,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use chrono::DateTime;
+use rmp_serde::{decode, encode};
+use rusqlite::{params, types::Type, Connection, OptionalExtension, Row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::{Result as StoreResult, TransactionStore};
+use crate::{
+    domain::account::{Account, Exact},
+    engine::{DisputeState, StoredTransaction},
+    error::Result,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS transactions (
+        tx INTEGER PRIMARY KEY,
+        client INTEGER NOT NULL,
+        amount TEXT NOT NULL,
+        disputed TEXT NOT NULL,
+        recipient INTEGER,
+        timestamp TEXT,
+        state TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS accounts (
+        client INTEGER PRIMARY KEY,
+        available TEXT NOT NULL,
+        held TEXT NOT NULL,
+        total TEXT NOT NULL,
+        locked INTEGER NOT NULL,
+        exact BLOB NOT NULL
+    );
+";
+
+/// Transactions in the `transactions` table of a SQLite database, along
+/// with an `accounts` table for [`save_accounts`](Self::save_accounts).
+///
+/// Amounts are exact decimal text, states are like `charged_back` and ids
+/// above [`i64::MAX`] wrap around to negative ones, as SQLite integers are
+/// signed. Balances of accounts are rounded like in outputs, the exact
+/// account being encoded in `exact`. Writes are only committed along with
+/// the accounts, by [`save_accounts`](Self::save_accounts), and rolled back
+/// if the store is dropped before, so a failed run leaves the database as it
+/// was.
+///
+/// Clones share the same connection, so one can be kept to save accounts
+/// while the engine owns the other.
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+/// Account with every field as is.
+#[derive(Serialize, Deserialize)]
+struct ExactAccount(#[serde(with = "Exact")] Account);
+
+#[derive(Debug)]
+struct Inner {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    /// Open the database at `path`, creating it and its tables if needed.
+    /// Transactions it already holds are kept.
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        connection.execute_batch("BEGIN")?;
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Inner { connection })),
+        })
+    }
+
+    /// Replace the content of the `accounts` table with `accounts`, and
+    /// commit them along with every transaction written so far.
+    pub fn save_accounts<'a>(&self, accounts: impl IntoIterator<Item = &'a Account>) -> Result<()> {
+        let inner = self.lock();
+        inner.connection.execute("DELETE FROM accounts", [])?;
+        {
+            let mut statement = inner.connection.prepare_cached(
+                "INSERT INTO accounts (client, available, held, total, locked, exact)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for account in accounts {
+                let exact = encode::to_vec(&ExactAccount(account.clone()))?;
+                statement.execute(params![
+                    account.client(),
+                    account.available().to_string(),
+                    account.held().to_string(),
+                    account.total().to_string(),
+                    account.locked(),
+                    exact,
+                ])?;
+            }
+        }
+        inner.connection.execute_batch("COMMIT; BEGIN")?;
+
+        Ok(())
+    }
+
+    /// Accounts last saved with [`save_accounts`](Self::save_accounts), to
+    /// start an engine from.
+    pub fn accounts(&self) -> Result<Vec<Account>> {
+        let inner = self.lock();
+        let mut statement = inner
+            .connection
+            .prepare_cached("SELECT exact FROM accounts")?;
+        let exact: Vec<Vec<u8>> = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        exact
+            .iter()
+            .map(|exact| {
+                let ExactAccount(account) = decode::from_slice(exact)?;
+                Ok(account)
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // Only writes saved along with accounts are kept.
+        let _ = self.connection.execute_batch("ROLLBACK");
+    }
+}
+
+impl TransactionStore for SqliteStore {
+    fn get(&self, transaction_id: u64) -> StoreResult<Option<StoredTransaction>> {
+        let inner = self.lock();
+        let mut statement = inner.connection.prepare_cached(
+            "SELECT client, amount, disputed, recipient, timestamp, state
+             FROM transactions WHERE tx = ?1",
+        )?;
+
+        Ok(statement
+            .query_row([transaction_id as i64], stored)
+            .optional()?)
+    }
+
+    fn insert(&mut self, transaction_id: u64, stored: StoredTransaction) -> StoreResult<()> {
+        let inner = self.lock();
+        let mut statement = inner.connection.prepare_cached(
+            "INSERT OR REPLACE INTO transactions
+             (tx, client, amount, disputed, recipient, timestamp, state)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        statement.execute(params![
+            transaction_id as i64,
+            stored.client,
+            stored.amount.to_string(),
+            stored.disputed.to_string(),
+            stored.recipient,
+            stored.timestamp.map(|timestamp| timestamp.to_rfc3339()),
+            state_name(stored.state),
+        ])?;
+
+        Ok(())
+    }
+
+    fn remove(&mut self, transaction_id: u64) -> StoreResult<()> {
+        let inner = self.lock();
+        let mut statement = inner
+            .connection
+            .prepare_cached("DELETE FROM transactions WHERE tx = ?1")?;
+        statement.execute([transaction_id as i64])?;
+
+        Ok(())
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = StoreResult<(u64, StoredTransaction)>> + '_> {
+        let inner = self.lock();
+        let transactions = inner
+            .connection
+            .prepare_cached(
+                "SELECT client, amount, disputed, recipient, timestamp, state, tx
+                 FROM transactions",
+            )
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| Ok((row.get::<_, i64>(6)? as u64, stored(row)?)))?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            });
+
+        match transactions {
+            Ok(transactions) => Box::new(transactions.into_iter().map(Ok)),
+            Err(error) => Box::new(iter::once(Err(error.into()))),
+        }
+    }
+
+    fn clear(&mut self) -> StoreResult<()> {
+        self.lock()
+            .connection
+            .execute("DELETE FROM transactions", [])?;
+
+        Ok(())
+    }
+}
+
+/// Transaction of a row selecting the columns of `transactions` in order,
+/// failing on values SQLite doesn't check, as they mean the database was
+/// written by something else.
+fn stored(row: &Row) -> rusqlite::Result<StoredTransaction> {
+    let invalid = |index, error: Box<dyn std::error::Error + Send + Sync>| {
+        rusqlite::Error::FromSqlConversionFailure(index, Type::Text, error)
+    };
+    let decimal = |index| -> rusqlite::Result<Decimal> {
+        let text: String = row.get(index)?;
+        Decimal::from_str(&text).map_err(|error| invalid(index, error.into()))
+    };
+    let timestamp: Option<String> = row.get(4)?;
+    let state: String = row.get(5)?;
+
+    Ok(StoredTransaction {
+        client: row.get(0)?,
+        amount: decimal(1)?,
+        disputed: decimal(2)?,
+        recipient: row.get(3)?,
+        timestamp: timestamp
+            .map(|timestamp| DateTime::parse_from_rfc3339(&timestamp))
+            .transpose()
+            .map_err(|error| invalid(4, error.into()))?
+            .map(|timestamp| timestamp.to_utc()),
+        state: match state.as_str() {
+            "undisputed" => DisputeState::Undisputed,
+            "disputed" => DisputeState::Disputed,
+            "resolved" => DisputeState::Resolved,
+            "charged_back" => DisputeState::ChargedBack,
+            _ => return Err(invalid(5, format!("invalid state {state}").into())),
+        },
+    })
+}
+
+fn state_name(state: DisputeState) -> &'static str {
+    match state {
+        DisputeState::Undisputed => "undisputed",
+        DisputeState::Disputed => "disputed",
+        DisputeState::Resolved => "resolved",
+        DisputeState::ChargedBack => "charged_back",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        domain::transaction::{tx, TransactionKind},
+        engine::TransactionEngine,
+    };
+
+    #[test]
+    fn sqlite_store() {
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let store = SqliteStore::open(&path).unwrap();
+
+        let mut engine = TransactionEngine::new().with_store(store.clone());
+        engine
+            .process(tx(1, 1, TransactionKind::Deposit { amount: dec!(1.5) }))
+            .unwrap();
+        engine
+            .process(tx(1, 1, TransactionKind::Dispute { amount: None }))
+            .unwrap();
+        store.save_accounts(engine.accounts()).unwrap();
+        drop((engine, store));
+
+        // Another engine picks up where the first left, disputes included.
+        let store = SqliteStore::open(&path).unwrap();
+        let mut engine = TransactionEngine::new().with_store(store.clone());
+        for account in store.accounts().unwrap() {
+            engine.insert_account(account);
+        }
+        engine
+            .process(tx(1, 1, TransactionKind::Deposit { amount: dec!(2) }))
+            .unwrap_err();
+        engine.process(tx(1, 1, TransactionKind::Resolve)).unwrap();
+
+        let held: String = store
+            .lock()
+            .connection
+            .query_row("SELECT held FROM accounts WHERE client = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(held, "1.5000");
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.available(), dec!(1.5));
+        assert_eq!(account.held(), dec!(0));
+    }
+
+    #[test]
+    fn rolled_back_unless_saved() {
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let store = SqliteStore::open(&path).unwrap();
+        let mut engine = TransactionEngine::new().with_store(store.clone());
+        engine
+            .process(tx(1, 1, TransactionKind::Deposit { amount: dec!(1) }))
+            .unwrap();
+        store.save_accounts(engine.accounts()).unwrap();
+        engine
+            .process(tx(1, 2, TransactionKind::Deposit { amount: dec!(2) }))
+            .unwrap();
+        // A failed run ends without saving its accounts.
+        drop((engine, store));
+
+        let store = SqliteStore::open(&path).unwrap();
+        assert!(store.get(1).unwrap().is_some());
+        assert!(store.get(2).unwrap().is_none());
+        assert_eq!(store.accounts().unwrap()[0].total(), dec!(1));
+    }
+}
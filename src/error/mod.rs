@@ -1,13 +1,116 @@
+//! Errors surfaced by the library entry points.
+
 use thiserror::Error;
 
+/// Any failure while reading, processing or writing transactions.
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("could not open transactions file")]
     FileError(#[from] std::io::Error),
     #[error("could not parse CSV rows to transaction")]
     CsvError(#[from] csv::Error),
+    #[error("invalid row{}: {}", location(*line, field, record), reason(source))]
+    InvalidRow {
+        /// Line of the row in the input, starting at 1 for the header.
+        line: Option<u64>,
+        /// Header of the column that couldn't be deserialized.
+        field: Option<String>,
+        /// The row as it was read.
+        record: Option<String>,
+        source: csv::Error,
+    },
+    #[error("invalid JSON transaction: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("invalid MessagePack transaction: {0}")]
+    MsgpackDecodeError(#[from] rmp_serde::decode::Error),
+    #[error("could not write MessagePack")]
+    MsgpackEncodeError(#[from] rmp_serde::encode::Error),
+    #[error("invalid protobuf message: {0}")]
+    ProtobufError(#[from] prost::DecodeError),
+    #[cfg(feature = "arrow")]
+    #[error("could not write Arrow data: {0}")]
+    ArrowError(#[from] arrow_schema::ArrowError),
+    #[cfg(feature = "avro")]
+    #[error("invalid Avro data: {0}")]
+    AvroError(#[from] apache_avro::Error),
+    #[cfg(feature = "camt")]
+    #[error("invalid XML: {0}")]
+    XmlError(#[from] quick_xml::Error),
+    #[cfg(feature = "xlsx")]
+    #[error("could not read spreadsheet: {0}")]
+    XlsxError(#[from] calamine::Error),
+    #[cfg(feature = "age")]
+    #[error("could not decrypt input: {0}")]
+    DecryptionError(#[from] age::DecryptError),
+    #[cfg(feature = "object-store")]
+    #[error("could not read object: {0}")]
+    ObjectStoreError(#[from] object_store::Error),
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+    #[cfg(feature = "sled")]
+    #[error("sled error: {0}")]
+    SledError(#[from] sled::Error),
+    #[cfg(feature = "postgres")]
+    #[error("Postgres error: {0}")]
+    PostgresError(#[from] ::postgres::Error),
+    #[cfg(feature = "grpc")]
+    #[error("gRPC server error: {0}")]
+    GrpcError(#[from] tonic::transport::Error),
+    #[cfg(feature = "amqp")]
+    #[error("AMQP error: {0}")]
+    AmqpError(#[from] lapin::Error),
+    #[cfg(feature = "kafka")]
+    #[error("Kafka error: {0}")]
+    KafkaError(#[from] rdkafka::error::KafkaError),
+    #[cfg(feature = "nats")]
+    #[error("NATS error: {0}")]
+    NatsError(async_nats::Error),
+    #[cfg(feature = "redis")]
+    #[error("Redis error: {0}")]
+    RedisError(#[from] redis::RedisError),
+    #[cfg(feature = "watch")]
+    #[error("could not watch directory: {0}")]
+    WatchError(#[from] notify::Error),
+    #[error("transaction {0} transfers funds to a client of another shard")]
+    ShardedTransfer(u64),
+    #[cfg(feature = "otlp")]
+    #[error("could not export traces: {0}")]
+    TraceError(#[from] opentelemetry::trace::TraceError),
+    #[error("unsupported engine snapshot version {0}")]
+    SnapshotVersion(u32),
+    #[error("could not parse TOML configuration")]
+    TomlError(#[from] toml::de::Error),
     #[error(transparent)]
     BusinessError(#[from] crate::domain::error::Error),
 }
 
+fn location(line: Option<u64>, field: &Option<String>, record: &Option<String>) -> String {
+    let mut location = String::new();
+
+    if let Some(line) = line {
+        location += &format!(" at line {line}");
+    }
+    if let Some(field) = field {
+        location += &format!(", field `{field}`");
+    }
+    if let Some(record) = record {
+        location += &format!(" (`{record}`)");
+    }
+
+    location
+}
+
+/// Describe a CSV error without its position, which [`Error::InvalidRow`]
+/// already reports.
+fn reason(error: &csv::Error) -> String {
+    match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.kind().to_string(),
+        csv::ErrorKind::UnequalLengths {
+            expected_len, len, ..
+        } => format!("found {len} fields, but the header has {expected_len}"),
+        _ => error.to_string(),
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,160 @@
+//! JSON input and output.
+
+use std::io::{Read, Write};
+
+use serde::Serialize;
+use serde_json::Deserializer;
+
+use crate::{
+    domain::{account::Account, transaction::Transaction},
+    error::{Error, Result},
+};
+
+/// Parse [`Transaction`]s from a reader of JSON objects, one per line, with
+/// the same fields as CSV rows.
+///
+/// Like [`csv::read`](crate::csv::read), objects are deserialized lazily, and
+/// each item is an error if the object is not a valid transaction.
+///
+/// A syntax error leaves the rest of the input unreadable, so nothing follows
+/// it.
+pub fn read(reader: impl Read) -> impl Iterator<Item = Result<Transaction>> {
+    let mut txns = Deserializer::from_reader(reader).into_iter();
+    let mut failed = false;
+
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+
+        let tx: Result<Transaction> = txns.next()?.map_err(Into::into);
+        failed = matches!(&tx, Err(Error::JsonError(error)) if !error.is_data());
+        Some(tx)
+    })
+}
+
+/// An item of JSON output: either an account, with the same fields as CSV
+/// output, or an `{"error": ...}` object describing a skipped transaction.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Record<'a> {
+    Account(&'a Account),
+    Error { error: String },
+}
+
+impl<'a> From<&'a Account> for Record<'a> {
+    fn from(account: &'a Account) -> Self {
+        Self::Account(account)
+    }
+}
+
+impl From<&Error> for Record<'_> {
+    fn from(error: &Error) -> Self {
+        Self::Error {
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Write `records` as a single JSON array.
+pub fn write(
+    records: impl IntoIterator<Item = impl Serialize>,
+    mut writer: impl Write,
+) -> serde_json::Result<()> {
+    writer.write_all(b"[").map_err(serde_json::Error::io)?;
+    for (i, record) in records.into_iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",").map_err(serde_json::Error::io)?;
+        }
+        serde_json::to_writer(&mut writer, &record)?;
+    }
+    writer.write_all(b"]\n").map_err(serde_json::Error::io)?;
+
+    writer.flush().map_err(serde_json::Error::io)
+}
+
+/// Write `records` as newline-delimited JSON, one object per line.
+pub fn write_lines(
+    records: impl IntoIterator<Item = impl Serialize>,
+    mut writer: impl Write,
+) -> serde_json::Result<()> {
+    for record in records {
+        serde_json::to_writer(&mut writer, &record)?;
+        writer.write_all(b"\n").map_err(serde_json::Error::io)?;
+    }
+
+    writer.flush().map_err(serde_json::Error::io)
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::domain::transaction::TransactionKind;
+
+    #[test]
+    fn read_transactions() {
+        let input = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}
+            {"type": "dispute", "client": 1, "tx": 1, "amount": null}
+            {"type": "withdrawal", "client": 1, "tx": 2, "amount": 0.5}
+            {"type": "deposit", "client": 1, "tx": 3}
+        "#;
+
+        let txns: Vec<_> = read(input.as_bytes()).collect();
+
+        assert_eq!(
+            txns[0].as_ref().unwrap().kind,
+            TransactionKind::Deposit {
+                amount: Decimal::new(15, 1)
+            }
+        );
+        assert_eq!(
+            txns[1].as_ref().unwrap().kind,
+            TransactionKind::Dispute { amount: None }
+        );
+        assert_eq!(
+            txns[2].as_ref().unwrap().kind,
+            TransactionKind::Withdrawal {
+                amount: Decimal::new(5, 1)
+            }
+        );
+        assert!(matches!(&txns[3], Err(Error::JsonError(error)) if error.is_data()));
+    }
+
+    #[test]
+    fn syntax_error_ends_input() {
+        let input = "{\"type\": \"deposit\", \"client\": 1,\n{}\n";
+
+        let txns: Vec<_> = read(input.as_bytes()).collect();
+
+        assert_eq!(txns.len(), 1);
+        assert!(txns[0].is_err());
+    }
+
+    #[test]
+    fn write_records() {
+        let mut account = Account::new(1);
+        account.deposit(1, Decimal::new(15, 1)).unwrap();
+        let error =
+            Error::BusinessError(crate::domain::error::Error::MissingAmount { transaction_id: 2 });
+        let records = || [Record::from(&account), Record::from(&error)];
+
+        let mut array = Vec::new();
+        write(records(), &mut array).unwrap();
+        let mut lines = Vec::new();
+        write_lines(records(), &mut lines).unwrap();
+
+        let account =
+            r#"{"client":1,"available":"1.5000","held":"0.0000","total":"1.5000","locked":false}"#;
+        let error = r#"{"error":"transaction 2 requires an amount"}"#;
+        assert_eq!(
+            String::from_utf8(array).unwrap(),
+            format!("[{account},{error}]\n")
+        );
+        assert_eq!(
+            String::from_utf8(lines).unwrap(),
+            format!("{account}\n{error}\n")
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Settlement logic for client transactions.
+//!
+//! Transactions are read from one of the supported formats (see [`csv`],
+//! [`json`], [`msgpack`], [`protobuf`] and, with the `avro` and `xlsx`
+//! features, `avro` and `xlsx`), possibly [compressed](compression) or, with
+//! the `age` feature, encrypted, [memory-mapped](mmap) with the `mmap`
+//! feature, streamed from object stores like S3 with the `object-store`
+//! feature, or imported from bank statements (see
+//! [`ofx`], [`qif`] and, with the `camt` feature, `camt`). Sorted streams can
+//! be [merged](merge) into one. Transactions are applied in order by the
+//! [`engine`] and reported back as
+//! one [`Account`](domain::account::Account) per client, which the `arrow`
+//! feature can also write as Arrow IPC, and the `postgres` feature upsert
+//! into a table. Long-running engines can expose Prometheus metrics with the
+//! `prometheus` feature, be [served](server) over HTTP with the `server`
+//! feature, and consume transactions from a Kafka, NATS JetStream, Redis
+//! streams or RabbitMQ [source](source) with the `kafka`, `nats`, `redis` and
+//! `amqp` features.
+//!
+//! ```no_run
+//! # fn main() -> txns::error::Result<()> {
+//! let file = std::fs::File::open("transactions.csv")?;
+//! let accounts = txns::engine::process(txns::csv::read(file))?;
+//! txns::csv::write(accounts, std::io::stdout())?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "avro")]
+pub mod avro;
+#[cfg(feature = "camt")]
+pub mod camt;
+pub mod compression;
+pub mod csv;
+pub mod domain;
+pub mod encoding;
+#[cfg(feature = "age")]
+pub mod encryption;
+pub mod engine;
+pub mod error;
+pub mod json;
+pub mod merge;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod msgpack;
+#[cfg(feature = "object-store")]
+pub mod object;
+pub mod ofx;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+pub mod protobuf;
+pub mod qif;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(any(
+    feature = "amqp",
+    feature = "kafka",
+    feature = "nats",
+    feature = "redis"
+))]
+pub mod source;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
@@ -0,0 +1,207 @@
+//! Merging of transaction streams that are each already in order.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, VecDeque},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    domain::transaction::{Transaction, TransactionKind},
+    error::{Error, Result},
+};
+
+/// What the merged streams are sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// Transactions referencing another one, like disputes, come after the
+    /// one with the same id.
+    TransactionId,
+    /// Transactions without a timestamp come first.
+    Timestamp,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    /// Id, and whether the transaction references an earlier one.
+    TransactionId(u64, bool),
+    Timestamp(Option<DateTime<Utc>>),
+}
+
+impl Order {
+    fn key(self, tx: &Transaction) -> Key {
+        match self {
+            Order::TransactionId => Key::TransactionId(
+                tx.transaction_id,
+                matches!(
+                    tx.kind,
+                    TransactionKind::Refund { .. }
+                        | TransactionKind::Dispute { .. }
+                        | TransactionKind::Resolve
+                        | TransactionKind::Chargeback
+                ),
+            ),
+            Order::Timestamp => Key::Timestamp(tx.timestamp),
+        }
+    }
+}
+
+/// Interleave `streams`, each sorted by `order`, into a single sorted
+/// stream, reading only one transaction ahead in each.
+///
+/// Transactions with the same key come in the order of their streams.
+/// Errors have no key, so they are yielded as soon as they are read.
+pub fn merge<I>(streams: Vec<I>, order: Order) -> impl Iterator<Item = Result<Transaction>>
+where
+    I: Iterator<Item = Result<Transaction>>,
+{
+    let mut merge = Merge {
+        streams,
+        order,
+        heads: BinaryHeap::new(),
+        errors: VecDeque::new(),
+    };
+    for index in 0..merge.streams.len() {
+        merge.advance(index);
+    }
+
+    merge
+}
+
+struct Merge<I> {
+    streams: Vec<I>,
+    order: Order,
+    /// Next transaction of each stream that isn't over, smallest first.
+    heads: BinaryHeap<Reverse<Head>>,
+    /// Errors read ahead of the transactions they precede.
+    errors: VecDeque<Error>,
+}
+
+struct Head {
+    key: Key,
+    stream: usize,
+    tx: Transaction,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.key, self.stream).cmp(&(&other.key, other.stream))
+    }
+}
+
+impl<I: Iterator<Item = Result<Transaction>>> Merge<I> {
+    /// Read the next transaction of a stream, keeping the errors before it.
+    fn advance(&mut self, stream: usize) {
+        for tx in self.streams[stream].by_ref() {
+            match tx {
+                Ok(tx) => {
+                    let key = self.order.key(&tx);
+                    self.heads.push(Reverse(Head { key, stream, tx }));
+                    return;
+                }
+                Err(error) => self.errors.push_back(error),
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Transaction>>> Iterator for Merge<I> {
+    type Item = Result<Transaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.errors.pop_front() {
+            return Some(Err(error));
+        }
+
+        let Reverse(head) = self.heads.pop()?;
+        self.advance(head.stream);
+
+        Some(Ok(head.tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::domain::error::Error as DomainError;
+
+    fn deposit(transaction_id: u64, timestamp: Option<i64>) -> Result<Transaction> {
+        let mut tx = Transaction::signed(transaction_id, 1, dec!(1))?;
+        tx.timestamp = timestamp.and_then(|seconds| DateTime::from_timestamp(seconds, 0));
+        Ok(tx)
+    }
+
+    fn dispute(transaction_id: u64) -> Result<Transaction> {
+        let mut tx = deposit(transaction_id, None)?;
+        tx.kind = TransactionKind::Dispute { amount: None };
+        Ok(tx)
+    }
+
+    fn ids(txns: impl Iterator<Item = Result<Transaction>>) -> Vec<Option<u64>> {
+        txns.map(|tx| tx.ok().map(|tx| tx.transaction_id)).collect()
+    }
+
+    #[test]
+    fn by_transaction_id() {
+        let first = vec![deposit(1, None), dispute(4), deposit(5, None)];
+        let second = vec![
+            deposit(2, None),
+            Err(DomainError::MissingAmount { transaction_id: 3 }.into()),
+            deposit(6, None),
+        ];
+        let third = vec![deposit(4, None)];
+
+        let merged: Vec<_> = merge(
+            vec![first.into_iter(), second.into_iter(), third.into_iter()],
+            Order::TransactionId,
+        )
+        .collect();
+
+        assert!(matches!(
+            merged[4],
+            Ok(Transaction {
+                transaction_id: 4,
+                kind: TransactionKind::Dispute { .. },
+                ..
+            })
+        ));
+        assert_eq!(
+            ids(merged.into_iter()),
+            [Some(1), Some(2), None, Some(4), Some(4), Some(5), Some(6)]
+        );
+    }
+
+    #[test]
+    fn by_timestamp() {
+        let first = vec![deposit(1, Some(10)), deposit(2, Some(30))];
+        let second = vec![deposit(3, None), deposit(4, Some(20))];
+
+        let merged: Vec<_> = merge(
+            vec![first.into_iter(), second.into_iter()],
+            Order::Timestamp,
+        )
+        .collect();
+
+        assert_eq!(
+            ids(merged.into_iter()),
+            [Some(3), Some(1), Some(4), Some(2)]
+        );
+    }
+}
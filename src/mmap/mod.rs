@@ -0,0 +1,66 @@
+//! Memory-mapped input, for local files.
+
+use std::{
+    fs::File,
+    io::{self, Cursor},
+    path::Path,
+};
+
+use memmap2::Mmap;
+
+use crate::compression;
+
+/// Map the file at `path` into memory, to be read from the mapping instead
+/// of through a buffer of its own.
+///
+/// Returns `None` for what can't be mapped or gains nothing from it, like
+/// pipes, devices or compressed files, which are better read as a stream.
+/// The file must not be changed while mapped.
+pub fn open(path: &Path) -> io::Result<Option<Cursor<Mmap>>> {
+    let file = File::open(path)?;
+    if !file.metadata()?.is_file() {
+        return Ok(None);
+    }
+
+    // SAFETY: the mapping is only read, and input files are not written to
+    // while transactions are read from them.
+    let mapping = unsafe { Mmap::map(&file)? };
+    if compression::is_compressed(&mapping) {
+        return Ok(None);
+    }
+    #[cfg(unix)]
+    mapping.advise(memmap2::Advice::Sequential)?;
+
+    Ok(Some(Cursor::new(mapping)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+
+    #[test]
+    fn mapped() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut input = String::new();
+        open(file.path())
+            .unwrap()
+            .unwrap()
+            .read_to_string(&mut input)
+            .unwrap();
+        assert_eq!(input, "");
+
+        file.write_all(b"type,client,tx,amount\n").unwrap();
+        open(file.path())
+            .unwrap()
+            .unwrap()
+            .read_to_string(&mut input)
+            .unwrap();
+        assert_eq!(input, "type,client,tx,amount\n");
+
+        let compressed = tempfile::NamedTempFile::new().unwrap();
+        zstd::stream::copy_encode(&b"type"[..], compressed.as_file(), 0).unwrap();
+        assert!(open(compressed.path()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,116 @@
+//! MessagePack input and output.
+
+use std::io::{self, Read, Write};
+
+use rmp_serde::{decode, encode, Deserializer};
+use serde::Deserialize;
+
+use crate::{
+    domain::{account::Account, transaction::Transaction},
+    error::Result,
+};
+
+/// Parse [`Transaction`]s from a reader of concatenated MessagePack maps,
+/// with the same keys as CSV columns.
+///
+/// Each map is decoded on its own before being turned into a transaction, so
+/// one that is not a valid transaction only yields an error for itself. Data
+/// that is not MessagePack at all leaves the rest of the input unreadable,
+/// so nothing follows it.
+pub fn read(reader: impl Read) -> impl Iterator<Item = Result<Transaction>> {
+    let mut deserializer = Deserializer::new(reader);
+    let mut failed = false;
+
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+
+        let value = match serde_json::Value::deserialize(&mut deserializer) {
+            Ok(value) => value,
+            Err(decode::Error::InvalidMarkerRead(error))
+                if error.kind() == io::ErrorKind::UnexpectedEof =>
+            {
+                return None
+            }
+            Err(error) => {
+                failed = true;
+                return Some(Err(error.into()));
+            }
+        };
+
+        Some(
+            Transaction::deserialize(value)
+                .map_err(|error| decode::Error::Syntax(error.to_string()).into()),
+        )
+    })
+}
+
+/// Write accounts as concatenated MessagePack maps, with the same keys as
+/// CSV columns.
+pub fn write<'a>(
+    accounts: impl IntoIterator<Item = &'a Account>,
+    mut writer: impl Write,
+) -> Result<()> {
+    for account in accounts {
+        encode::write_named(&mut writer, account)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{domain::transaction::TransactionKind, error::Error};
+
+    #[test]
+    fn read_transactions() {
+        let mut input = Vec::new();
+        for value in [
+            json!({"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}),
+            json!({"type": "payout", "client": 1, "tx": 2, "amount": 1}),
+            json!({"type": "withdrawal", "client": 1, "tx": 3, "amount": 0.5}),
+        ] {
+            encode::write_named(&mut input, &value).unwrap();
+        }
+
+        let txns: Vec<_> = read(input.as_slice()).collect();
+
+        assert_eq!(txns.len(), 3);
+        assert_eq!(
+            txns[0].as_ref().unwrap().kind,
+            TransactionKind::Deposit { amount: dec!(1.5) }
+        );
+        assert!(matches!(txns[1], Err(Error::MsgpackDecodeError(_))));
+        assert_eq!(
+            txns[2].as_ref().unwrap().kind,
+            TransactionKind::Withdrawal { amount: dec!(0.5) }
+        );
+    }
+
+    #[test]
+    fn write_accounts() {
+        let mut account = Account::new(1);
+        account.deposit(1, dec!(1.5)).unwrap();
+
+        let mut output = Vec::new();
+        write([&account], &mut output).unwrap();
+
+        let value: serde_json::Value = rmp_serde::from_slice(&output).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "client": 1,
+                "available": "1.5000",
+                "held": "0.0000",
+                "total": "1.5000",
+                "locked": false,
+            })
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Input streamed from object stores, like S3 or Google Cloud Storage,
+//! available with the `object-store` feature.
+
+use std::{
+    ffi::OsString,
+    io::{self, Read},
+    path::Path,
+    pin::Pin,
+};
+
+use bytes::{Buf, Bytes};
+use object_store::ObjectStore;
+use tokio::runtime::{self, Runtime};
+use tokio_stream::{Stream, StreamExt};
+use url::Url;
+
+use crate::error::Result;
+
+/// Schemes of the URLs read from object stores, like `s3://bucket/key`.
+pub const SCHEMES: &[&str] = &["s3", "s3a", "gs"];
+
+/// Prefixes of the environment variables holding settings of the stores.
+const PREFIXES: &[&str] = &["AWS_", "GOOGLE_"];
+
+/// Whether `path` is the URL of an object, like `s3://bucket/key`, rather
+/// than a local file.
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .and_then(|path| Url::parse(path).ok())
+        .is_some_and(|url| SCHEMES.contains(&url.scheme()))
+}
+
+/// Open the object at `url`, like `s3://bucket/key`, to be read as it is
+/// downloaded rather than once on disk.
+///
+/// Credentials and other settings are read from the `AWS_` and `GOOGLE_`
+/// environment variables, like `AWS_ACCESS_KEY_ID` and `AWS_REGION` for S3,
+/// or `GOOGLE_SERVICE_ACCOUNT` for Google Cloud Storage.
+pub fn open(url: &str) -> Result<Object> {
+    let url =
+        Url::parse(url).map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    let (store, path) = object_store::parse_url_opts(&url, options(std::env::vars_os()))?;
+
+    Object::get(store, path)
+}
+
+/// Settings of the stores among environment variables `vars`, named like
+/// `aws_region`. Other variables are left out, as are those that aren't
+/// Unicode.
+fn options(vars: impl Iterator<Item = (OsString, OsString)>) -> Vec<(String, String)> {
+    vars.filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .filter(|(key, _)| PREFIXES.iter().any(|prefix| key.starts_with(prefix)))
+        .map(|(key, value)| (key.to_ascii_lowercase(), value))
+        .collect()
+}
+
+/// Object of a store, read one chunk at a time as they are downloaded.
+pub struct Object {
+    runtime: Runtime,
+    chunks: Pin<Box<dyn Stream<Item = object_store::Result<Bytes>> + Send>>,
+    chunk: Bytes,
+}
+
+impl Object {
+    fn get(store: Box<dyn ObjectStore>, path: object_store::path::Path) -> Result<Self> {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let chunks = runtime.block_on(store.get(&path))?.into_stream();
+
+        Ok(Self {
+            runtime,
+            chunks,
+            chunk: Bytes::new(),
+        })
+    }
+}
+
+impl Read for Object {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.chunk.has_remaining() {
+            match self.runtime.block_on(self.chunks.next()) {
+                Some(chunk) => self.chunk = chunk.map_err(io::Error::other)?,
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.chunk.remaining());
+        self.chunk.copy_to_slice(&mut buf[..len]);
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use object_store::local::LocalFileSystem;
+
+    use super::*;
+
+    #[test]
+    fn urls() {
+        assert!(is_url(Path::new("s3://archives/2024/transactions.csv.gz")));
+        assert!(is_url(Path::new("gs://archives/transactions.csv")));
+        assert!(!is_url(Path::new("transactions.csv")));
+        assert!(!is_url(Path::new("/var/archives/transactions.csv")));
+        assert!(!is_url(Path::new("file:///var/archives/transactions.csv")));
+    }
+
+    #[test]
+    fn environment() {
+        let vars = [
+            ("AWS_REGION", "eu-west-1"),
+            ("GOOGLE_SERVICE_ACCOUNT", "account.json"),
+            ("HOME", "/root"),
+        ];
+        let options = options(
+            vars.into_iter()
+                .map(|(key, value)| (key.into(), value.into())),
+        );
+
+        assert_eq!(
+            options,
+            [
+                ("aws_region".to_owned(), "eu-west-1".to_owned()),
+                (
+                    "google_service_account".to_owned(),
+                    "account.json".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn streamed() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"type,client,tx,amount\ndeposit,1,1,1.0\n")
+            .unwrap();
+        let path = object_store::path::Path::from_filesystem_path(file.path()).unwrap();
+
+        let mut input = String::new();
+        Object::get(Box::new(LocalFileSystem::new()), path)
+            .unwrap()
+            .read_to_string(&mut input)
+            .unwrap();
+        assert_eq!(input, "type,client,tx,amount\ndeposit,1,1,1.0\n");
+    }
+}
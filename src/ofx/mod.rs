@@ -0,0 +1,219 @@
+//! Import of OFX (Open Financial Exchange) bank statements.
+
+use std::{collections::HashMap, io::Read, str::FromStr};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+
+use crate::{
+    domain::{error::Error as DomainError, transaction::Transaction},
+    error::Result,
+};
+
+/// Parse the statement transactions of an OFX file as deposits, for positive
+/// amounts, and withdrawals, for negative ones, all of them for `client`.
+///
+/// OFX identifies transactions with arbitrary strings, so they are numbered
+/// in order instead, starting at `first_transaction_id`. Their timestamp is
+/// the posting date, and their currency the one of the statement.
+///
+/// Both SGML (OFX 1.x) and XML (OFX 2.x) files are supported. Each item is an
+/// error if the transaction has an invalid amount or date.
+pub fn read(
+    mut reader: impl Read,
+    client: u16,
+    first_transaction_id: u64,
+) -> Result<impl Iterator<Item = Result<Transaction>>> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+
+    let statement = parse(&input);
+    let currency = statement.currency;
+
+    Ok((first_transaction_id..)
+        .zip(statement.transactions)
+        .map(move |(transaction_id, fields)| {
+            let amount = fields
+                .get("TRNAMT")
+                .ok_or(DomainError::MissingAmount { transaction_id })?;
+            let amount = Decimal::from_str(&amount.replace(',', ".")).map_err(|_| {
+                DomainError::InvalidAmount {
+                    transaction_id,
+                    amount: amount.clone(),
+                }
+            })?;
+
+            let mut tx = Transaction::signed(transaction_id, client, amount)?;
+            tx.timestamp = fields
+                .get("DTPOSTED")
+                .map(|date| {
+                    parse_date(date).ok_or_else(|| DomainError::InvalidTimestamp {
+                        transaction_id,
+                        timestamp: date.clone(),
+                    })
+                })
+                .transpose()?;
+            tx.currency = currency.clone();
+
+            Ok(tx)
+        }))
+}
+
+#[derive(Debug, Default)]
+struct Statement {
+    /// Default currency of the statement.
+    currency: Option<String>,
+    /// Fields of each `STMTTRN` element, by tag.
+    transactions: Vec<HashMap<String, String>>,
+}
+
+/// Collect the fields of a statement.
+///
+/// Elements are read as a flat list of tags, each followed by its value if
+/// any, which works whether or not they are closed, as SGML allows.
+fn parse(input: &str) -> Statement {
+    let mut statement = Statement::default();
+    let mut transaction: Option<HashMap<String, String>> = None;
+
+    for element in input.split('<').skip(1) {
+        let Some((tag, value)) = element.split_once('>') else {
+            continue;
+        };
+        let tag = tag.trim().to_ascii_uppercase();
+        let value = value.trim();
+
+        match tag.as_str() {
+            "STMTTRN" => transaction = Some(HashMap::new()),
+            "/STMTTRN" => statement.transactions.extend(transaction.take()),
+            "CURDEF" if !value.is_empty() => statement.currency = Some(value.to_owned()),
+            _ if tag.starts_with('/') || value.is_empty() => {}
+            _ => {
+                if let Some(transaction) = &mut transaction {
+                    transaction.insert(tag, value.to_owned());
+                }
+            }
+        }
+    }
+
+    statement
+}
+
+/// Parse an OFX date, like `20240131`, `20240131120000` or
+/// `20240131120000.000[-5:EST]`, in UTC unless it has an offset.
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let (value, zone) = match value.split_once('[') {
+        Some((value, zone)) => (value, Some(zone.trim_end_matches(']'))),
+        None => (value, None),
+    };
+    // Milliseconds are dropped, like with every other timestamp.
+    let value = value.split('.').next()?;
+
+    let date = NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()?;
+    let time = match value.get(8..) {
+        Some("") | None => NaiveTime::MIN,
+        Some(time) => NaiveTime::parse_from_str(time, "%H%M%S").ok()?,
+    };
+    let datetime = NaiveDateTime::new(date, time);
+
+    let offset = match zone {
+        Some(zone) => {
+            let hours = zone.split(':').next()?;
+            let seconds = f64::from_str(hours).ok()? * 3600.0;
+            FixedOffset::east_opt(seconds as i32)?
+        }
+        None => FixedOffset::east_opt(0)?,
+    };
+
+    Some(offset.from_local_datetime(&datetime).single()?.to_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{domain::transaction::TransactionKind, error::Error};
+
+    #[test]
+    fn read_sgml() {
+        let input = "OFXHEADER:100
+DATA:OFXSGML
+
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<CURDEF>EUR
+<BANKTRANLIST>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20240131120000.000[-5:EST]
+<TRNAMT>1500.00
+<FITID>A1
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20240201
+<TRNAMT>-12,50
+<FITID>A2
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>yesterday
+<TRNAMT>-1
+</STMTTRN>
+</BANKTRANLIST>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>
+";
+
+        let txns: Vec<_> = read(input.as_bytes(), 7, 100).unwrap().collect();
+
+        assert_eq!(txns.len(), 3);
+        let deposit = txns[0].as_ref().unwrap();
+        assert_eq!(deposit.transaction_id, 100);
+        assert_eq!(deposit.client, 7);
+        assert_eq!(
+            deposit.kind,
+            TransactionKind::Deposit { amount: dec!(1500) }
+        );
+        assert_eq!(
+            deposit.timestamp.unwrap().to_rfc3339(),
+            "2024-01-31T17:00:00+00:00"
+        );
+        assert_eq!(deposit.currency.as_deref(), Some("EUR"));
+        let withdrawal = txns[1].as_ref().unwrap();
+        assert_eq!(withdrawal.transaction_id, 101);
+        assert_eq!(
+            withdrawal.kind,
+            TransactionKind::Withdrawal { amount: dec!(12.5) }
+        );
+        assert!(matches!(
+            txns[2],
+            Err(Error::BusinessError(DomainError::InvalidTimestamp {
+                transaction_id: 102,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn read_xml() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220"?>
+<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<BANKTRANLIST>
+<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20240201</DTPOSTED><TRNAMT>-2.5</TRNAMT></STMTTRN>
+</BANKTRANLIST>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>
+"#;
+
+        let txns: Vec<_> = read(input.as_bytes(), 1, 1).unwrap().collect();
+
+        assert_eq!(txns.len(), 1);
+        let withdrawal = txns[0].as_ref().unwrap();
+        assert_eq!(
+            withdrawal.kind,
+            TransactionKind::Withdrawal { amount: dec!(2.5) }
+        );
+        assert_eq!(withdrawal.currency, None);
+    }
+}
@@ -0,0 +1,102 @@
+//! Postgres output, upserting accounts into a table.
+
+use std::io;
+
+use ::postgres::{Client, NoTls};
+
+use crate::{domain::account::Account, error::Result};
+
+/// Whether `output` is a Postgres connection URL rather than a path.
+pub fn is_url(output: &str) -> bool {
+    output.starts_with("postgres://") || output.starts_with("postgresql://")
+}
+
+/// Upsert [`Account`]s into `table` of the database at `url`, like
+/// `postgres://user@localhost/bank`, in a single transaction.
+///
+/// The table, possibly qualified by its schema like `ledger.accounts`, is
+/// created if needed, with `client` as primary key, `NUMERIC` balances and a
+/// `locked` boolean. Rows of other clients are left untouched.
+pub fn write<'a>(
+    accounts: impl IntoIterator<Item = &'a Account>,
+    url: &str,
+    table: &str,
+) -> Result<()> {
+    let table = quote(table)?;
+    let mut client = Client::connect(url, NoTls)?;
+    let mut transaction = client.transaction()?;
+
+    transaction.batch_execute(&format!(
+        "CREATE TABLE IF NOT EXISTS {table} (
+            client INTEGER PRIMARY KEY,
+            available NUMERIC NOT NULL,
+            held NUMERIC NOT NULL,
+            total NUMERIC NOT NULL,
+            locked BOOLEAN NOT NULL
+        )"
+    ))?;
+    // Balances are sent as text, so they reach NUMERIC columns exactly.
+    let statement = transaction.prepare(&format!(
+        "INSERT INTO {table} (client, available, held, total, locked)
+         VALUES ($1, $2::TEXT::NUMERIC, $3::TEXT::NUMERIC, $4::TEXT::NUMERIC, $5)
+         ON CONFLICT (client) DO UPDATE SET
+            available = EXCLUDED.available,
+            held = EXCLUDED.held,
+            total = EXCLUDED.total,
+            locked = EXCLUDED.locked"
+    ))?;
+    for account in accounts {
+        transaction.execute(
+            &statement,
+            &[
+                &i32::from(account.client()),
+                &account.available().to_string(),
+                &account.held().to_string(),
+                &account.total().to_string(),
+                &account.locked(),
+            ],
+        )?;
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Quote a table name, optionally qualified by its schema, made of letters,
+/// digits and underscores only.
+fn quote(table: &str) -> Result<String> {
+    let valid = |name: &str| {
+        name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    if !table.split('.').all(valid) || table.split('.').count() > 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid table name `{table}`"),
+        )
+        .into());
+    }
+
+    Ok(table
+        .split('.')
+        .map(|name| format!("\"{name}\""))
+        .collect::<Vec<_>>()
+        .join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_names() {
+        assert_eq!(quote("accounts").unwrap(), "\"accounts\"");
+        assert_eq!(
+            quote("ledger.accounts_2024").unwrap(),
+            "\"ledger\".\"accounts_2024\""
+        );
+        for table in ["", "1accounts", "accounts; DROP TABLE x", "a.b.c", "a\"b"] {
+            assert!(quote(table).is_err(), "{table}");
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Prometheus metrics of a long-running engine, available with the
+//! `prometheus` feature.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::Arc,
+    thread,
+    time::Instant,
+};
+
+use ::prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+use crate::{
+    domain::transaction::{Transaction, TransactionKind},
+    engine::{Outcome, TransactionEngine},
+    error::{Error, Result},
+};
+
+/// Metrics of the transactions applied to an engine, in the Prometheus text
+/// format:
+///
+/// - `txns_transactions_total`, by `kind`, like `deposit`;
+/// - `txns_ignored_total` and `txns_rejected_total`, by `reason`, like
+///   `insufficient_funds`;
+/// - `txns_clients`, how many clients have an account;
+/// - `txns_processing_seconds`, how long transactions take to apply;
+/// - `txns_journal_lag`, how many transactions were journaled but not applied
+///   yet.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    transactions: IntCounterVec,
+    ignored: IntCounterVec,
+    rejected: IntCounterVec,
+    clients: IntGauge,
+    latency: Histogram,
+    journal_lag: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let counter = |name: &str, help: &str, label: &str| {
+            IntCounterVec::new(Opts::new(name, help), &[label]).expect("metric names are valid")
+        };
+        let metrics = Self {
+            registry: Registry::new(),
+            transactions: counter(
+                "txns_transactions_total",
+                "Transactions processed, by kind.",
+                "kind",
+            ),
+            ignored: counter(
+                "txns_ignored_total",
+                "Transactions ignored, by reason.",
+                "reason",
+            ),
+            rejected: counter(
+                "txns_rejected_total",
+                "Transactions rejected, by reason.",
+                "reason",
+            ),
+            clients: IntGauge::new("txns_clients", "Clients with an account.")
+                .expect("metric names are valid"),
+            latency: Histogram::with_opts(
+                HistogramOpts::new(
+                    "txns_processing_seconds",
+                    "Time taken to apply a transaction.",
+                )
+                .buckets(
+                    ::prometheus::exponential_buckets(1e-7, 4.0, 10).expect("buckets are valid"),
+                ),
+            )
+            .expect("metric names are valid"),
+            journal_lag: IntGauge::new(
+                "txns_journal_lag",
+                "Transactions journaled but not applied yet.",
+            )
+            .expect("metric names are valid"),
+        };
+
+        for metric in [
+            Box::new(metrics.transactions.clone()) as Box<dyn ::prometheus::core::Collector>,
+            Box::new(metrics.ignored.clone()),
+            Box::new(metrics.rejected.clone()),
+            Box::new(metrics.clients.clone()),
+            Box::new(metrics.latency.clone()),
+            Box::new(metrics.journal_lag.clone()),
+        ] {
+            metrics
+                .registry
+                .register(metric)
+                .expect("metrics are registered once");
+        }
+
+        metrics
+    }
+
+    /// Apply `tx` to `engine` with `apply`, recording how long it took and
+    /// what came out of it.
+    pub fn record(
+        &self,
+        engine: &mut TransactionEngine,
+        tx: Transaction,
+        apply: impl FnOnce(&mut TransactionEngine, Transaction) -> Result<Outcome>,
+    ) -> Result<Outcome> {
+        let kind = tx.kind.name();
+        let mut clients = vec![tx.client];
+        if let TransactionKind::Transfer { to_client, .. } = tx.kind {
+            clients.push(to_client);
+        }
+        clients.retain(|&client| engine.account(client).is_none());
+
+        let started = Instant::now();
+        let outcome = apply(engine, tx);
+        self.latency.observe(started.elapsed().as_secs_f64());
+
+        self.transactions.with_label_values(&[kind]).inc();
+        match &outcome {
+            Ok(Outcome::Applied) => {}
+            Ok(Outcome::Ignored(ignored)) => {
+                self.ignored.with_label_values(&[ignored.code()]).inc()
+            }
+            Err(Error::BusinessError(error)) => {
+                self.rejected.with_label_values(&[error.code()]).inc()
+            }
+            Err(_) => self.rejected.with_label_values(&["invalid"]).inc(),
+        }
+        let opened = clients
+            .into_iter()
+            .filter(|&client| engine.account(client).is_some())
+            .count();
+        self.clients.add(opened as i64);
+        if self.journal_lag.get() > 0 {
+            self.journal_lag.dec();
+        }
+
+        outcome
+    }
+
+    /// Count the clients with an account in `engine`, like once it is
+    /// restored.
+    pub fn count_clients(&self, engine: &TransactionEngine) {
+        self.clients.set(engine.accounts().count() as i64);
+    }
+
+    /// Count a transaction written to the journal, until it is
+    /// [`record`](Self::record)ed.
+    pub fn journaled(&self) {
+        self.journal_lag.inc();
+    }
+
+    /// Every metric, in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut text = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut text)
+            .expect("metrics are always encodable");
+
+        String::from_utf8(text).expect("the text format is UTF-8")
+    }
+
+    /// Answer `GET /metrics` requests on `address` from a thread of its own,
+    /// for as long as the process runs, returning the address it listens on.
+    pub fn serve(self: &Arc<Self>, address: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let metrics = Arc::clone(self);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A scraper hanging up early is its own problem.
+                let _ = metrics.respond(stream);
+            }
+        });
+
+        Ok(address)
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+
+        let (status, body) = match request.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+            ["GET", "/metrics"] => ("200 OK", self.render()),
+            _ => ("404 Not Found", String::new()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\n\
+             Content-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::domain::transaction::tx;
+
+    #[test]
+    fn metrics() {
+        let metrics = Arc::new(Metrics::new());
+        let mut engine = TransactionEngine::new();
+        let apply = |engine: &mut TransactionEngine, tx| Ok(engine.apply(tx)?);
+        metrics.journaled();
+        for tx in [
+            tx(
+                1,
+                1,
+                TransactionKind::Deposit {
+                    amount: Decimal::ONE,
+                },
+            ),
+            tx(
+                1,
+                2,
+                TransactionKind::Withdrawal {
+                    amount: Decimal::TEN,
+                },
+            ),
+            tx(1, 3, TransactionKind::Resolve),
+        ] {
+            let _ = metrics.record(&mut engine, tx, apply);
+        }
+
+        let address = metrics.serve("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        for line in [
+            "txns_transactions_total{kind=\"deposit\"} 1",
+            "txns_rejected_total{reason=\"insufficient_funds\"} 1",
+            "txns_ignored_total{reason=\"unknown_transaction\"} 1",
+            "txns_clients 1",
+            "txns_processing_seconds_count 3",
+            "txns_journal_lag 0",
+        ] {
+            assert!(response.contains(line), "{line} in {response}");
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Messages of `proto/txns.proto`, as `prost-build` would generate them, and
+//! its service, as `tonic-build` would with the `grpc` feature.
+//!
+//! They are kept by hand so building doesn't require `protoc`, and must be
+//! updated along with the schema.
+
+/// A single transaction, with the same fields as CSV rows.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Transaction {
+    #[prost(string, tag = "1")]
+    pub r#type: String,
+    #[prost(uint32, tag = "2")]
+    pub client: u32,
+    #[prost(uint64, tag = "3")]
+    pub tx: u64,
+    #[prost(string, optional, tag = "4")]
+    pub amount: Option<String>,
+    #[prost(uint32, optional, tag = "5")]
+    pub to_client: Option<u32>,
+    /// Seconds since the Unix epoch.
+    #[prost(int64, optional, tag = "6")]
+    pub timestamp: Option<i64>,
+    #[prost(string, optional, tag = "7")]
+    pub currency: Option<String>,
+}
+
+/// Balances of a client, with the same fields as CSV output.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Account {
+    #[prost(uint32, tag = "1")]
+    pub client: u32,
+    #[prost(string, tag = "2")]
+    pub available: String,
+    #[prost(string, tag = "3")]
+    pub held: String,
+    #[prost(string, tag = "4")]
+    pub total: String,
+    #[prost(bool, tag = "5")]
+    pub locked: bool,
+    #[prost(string, optional, tag = "6")]
+    pub overdraft: Option<String>,
+    #[prost(string, optional, tag = "7")]
+    pub adjusted: Option<String>,
+    #[prost(string, optional, tag = "8")]
+    pub fees: Option<String>,
+    #[prost(string, optional, tag = "9")]
+    pub currency: Option<String>,
+    #[prost(string, optional, tag = "10")]
+    pub base_total: Option<String>,
+    #[prost(string, optional, tag = "11")]
+    pub interest: Option<String>,
+}
+
+/// What came out of a submitted transaction.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Submitted {
+    #[prost(enumeration = "submitted::Status", tag = "1")]
+    pub status: i32,
+    #[prost(uint64, tag = "2")]
+    pub tx: u64,
+    /// Code of why the transaction was ignored or rejected, like
+    /// "insufficient_funds".
+    #[prost(string, optional, tag = "3")]
+    pub reason: Option<String>,
+    /// Why the transaction was rejected, in words.
+    #[prost(string, optional, tag = "4")]
+    pub error: Option<String>,
+}
+
+/// Nested message and enum types in `Submitted`.
+pub mod submitted {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Status {
+        Applied = 0,
+        Ignored = 1,
+        Rejected = 2,
+    }
+
+    impl Status {
+        /// String value of the enum field names used in the ProtoBuf
+        /// definition.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Applied => "APPLIED",
+                Self::Ignored => "IGNORED",
+                Self::Rejected => "REJECTED",
+            }
+        }
+
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> Option<Self> {
+            match value {
+                "APPLIED" => Some(Self::Applied),
+                "IGNORED" => Some(Self::Ignored),
+                "REJECTED" => Some(Self::Rejected),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct GetAccountRequest {
+    #[prost(uint32, tag = "1")]
+    pub client: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct StreamAccountsRequest {}
+
+/// Generated server implementations.
+#[cfg(feature = "grpc")]
+pub mod txns_server {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value
+    )]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for
+    /// use with TxnsServer.
+    #[async_trait]
+    pub trait Txns: std::marker::Send + std::marker::Sync + 'static {
+        async fn submit_transaction(
+            &self,
+            request: tonic::Request<super::Transaction>,
+        ) -> std::result::Result<tonic::Response<super::Submitted>, tonic::Status>;
+        async fn get_account(
+            &self,
+            request: tonic::Request<super::GetAccountRequest>,
+        ) -> std::result::Result<tonic::Response<super::Account>, tonic::Status>;
+        /// Server streaming response type for the StreamAccounts method.
+        type StreamAccountsStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::Account, tonic::Status>,
+            > + std::marker::Send
+            + 'static;
+        async fn stream_accounts(
+            &self,
+            request: tonic::Request<super::StreamAccountsRequest>,
+        ) -> std::result::Result<tonic::Response<Self::StreamAccountsStream>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct TxnsServer<T> {
+        inner: Arc<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    impl<T> TxnsServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports
+        /// it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for TxnsServer<T>
+    where
+        T: Txns,
+        B: Body + std::marker::Send + 'static,
+        B::Error: Into<StdError> + std::marker::Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            match req.uri().path() {
+                "/txns.Txns/SubmitTransaction" => {
+                    #[allow(non_camel_case_types)]
+                    struct SubmitTransactionSvc<T: Txns>(pub Arc<T>);
+                    impl<T: Txns> tonic::server::UnaryService<super::Transaction> for SubmitTransactionSvc<T> {
+                        type Response = super::Submitted;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Transaction>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Txns>::submit_transaction(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SubmitTransactionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/txns.Txns/GetAccount" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetAccountSvc<T: Txns>(pub Arc<T>);
+                    impl<T: Txns> tonic::server::UnaryService<super::GetAccountRequest> for GetAccountSvc<T> {
+                        type Response = super::Account;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetAccountRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Txns>::get_account(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetAccountSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/txns.Txns/StreamAccounts" => {
+                    #[allow(non_camel_case_types)]
+                    struct StreamAccountsSvc<T: Txns>(pub Arc<T>);
+                    impl<T: Txns>
+                        tonic::server::ServerStreamingService<super::StreamAccountsRequest>
+                        for StreamAccountsSvc<T>
+                    {
+                        type Response = super::Account;
+                        type ResponseStream = T::StreamAccountsStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::StreamAccountsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Txns>::stream_accounts(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = StreamAccountsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    let mut response = http::Response::new(empty_body());
+                    let headers = response.headers_mut();
+                    headers.insert(
+                        tonic::Status::GRPC_STATUS,
+                        (tonic::Code::Unimplemented as i32).into(),
+                    );
+                    headers.insert(
+                        http::header::CONTENT_TYPE,
+                        tonic::metadata::GRPC_CONTENT_TYPE,
+                    );
+                    Ok(response)
+                }),
+            }
+        }
+    }
+    impl<T> Clone for TxnsServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+                max_decoding_message_size: self.max_decoding_message_size,
+                max_encoding_message_size: self.max_encoding_message_size,
+            }
+        }
+    }
+    /// Generated gRPC service name
+    pub const SERVICE_NAME: &str = "txns.Txns";
+    impl<T> tonic::server::NamedService for TxnsServer<T> {
+        const NAME: &'static str = SERVICE_NAME;
+    }
+}
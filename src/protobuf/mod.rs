@@ -0,0 +1,225 @@
+//! Length-delimited protobuf streams, following `proto/txns.proto`.
+//!
+//! Each message is preceded by its length as a varint, as written by
+//! `writeDelimitedTo` in Java or `protodelim` in Go.
+
+use std::{
+    io::{self, Read, Write},
+    str::FromStr,
+};
+
+use chrono::DateTime;
+use prost::Message;
+use rust_decimal::Decimal;
+
+use crate::{
+    domain::{
+        account::Account,
+        error::Error as DomainError,
+        transaction::{Transaction, TransactionRecord},
+    },
+    error::Result,
+};
+
+pub mod messages;
+
+/// Longest varint a length can take.
+const MAX_VARINT_LEN: usize = 10;
+
+/// Parse [`Transaction`]s from a stream of length-delimited
+/// [`messages::Transaction`]s.
+///
+/// Each item is an error if the message is not a valid transaction. Data that
+/// can't be decoded at all leaves the rest of the input unreadable, so
+/// nothing follows it.
+pub fn read(mut reader: impl Read) -> impl Iterator<Item = Result<Transaction>> {
+    let mut failed = false;
+
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+
+        let message = match next_message(&mut reader) {
+            Ok(message) => message?,
+            Err(error) => {
+                failed = true;
+                return Some(Err(error));
+            }
+        };
+
+        Some(Transaction::try_from(message).map_err(Into::into))
+    })
+}
+
+/// Read the next message, or nothing at the end of the input.
+fn next_message(reader: &mut impl Read) -> Result<Option<messages::Transaction>> {
+    let mut length = Vec::with_capacity(MAX_VARINT_LEN);
+    loop {
+        let mut byte = [0];
+        match reader.read_exact(&mut byte) {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof && length.is_empty() => {
+                return Ok(None)
+            }
+            result => result?,
+        }
+
+        length.push(byte[0]);
+        if byte[0] < 0x80 || length.len() == MAX_VARINT_LEN {
+            break;
+        }
+    }
+
+    let length = prost::decode_length_delimiter(length.as_slice())?;
+    let mut message = vec![0; length];
+    reader.read_exact(&mut message)?;
+
+    Ok(Some(messages::Transaction::decode(message.as_slice())?))
+}
+
+impl TryFrom<messages::Transaction> for Transaction {
+    type Error = DomainError;
+
+    fn try_from(message: messages::Transaction) -> std::result::Result<Self, Self::Error> {
+        let transaction_id = message.tx;
+        let client = |client: u32| {
+            u16::try_from(client).map_err(|_| DomainError::InvalidClient {
+                transaction_id,
+                client: client.into(),
+            })
+        };
+
+        let mut record =
+            TransactionRecord::new(&message.r#type, client(message.client)?, message.tx)?;
+        record.amount = message
+            .amount
+            .map(|amount| {
+                Decimal::from_str(&amount).map_err(|_| DomainError::InvalidAmount {
+                    transaction_id,
+                    amount,
+                })
+            })
+            .transpose()?;
+        record.to_client = message.to_client.map(client).transpose()?;
+        record.timestamp = message
+            .timestamp
+            .map(|seconds| {
+                DateTime::from_timestamp(seconds, 0).ok_or(DomainError::InvalidTimestamp {
+                    transaction_id,
+                    timestamp: seconds.to_string(),
+                })
+            })
+            .transpose()?;
+        record.currency = message.currency;
+
+        record.try_into()
+    }
+}
+
+impl From<&Account> for messages::Account {
+    fn from(account: &Account) -> Self {
+        let decimal = |value: Option<Decimal>| value.map(|value| value.to_string());
+
+        Self {
+            client: account.client().into(),
+            available: account.available().to_string(),
+            held: account.held().to_string(),
+            total: account.total().to_string(),
+            locked: account.locked(),
+            overdraft: decimal(account.overdraft()),
+            adjusted: decimal(account.adjusted()),
+            fees: decimal(account.fees()),
+            currency: account.currency().map(Into::into),
+            base_total: decimal(account.base_total()),
+            interest: decimal(account.interest()),
+        }
+    }
+}
+
+/// Write accounts as a stream of length-delimited [`messages::Account`]s.
+pub fn write<'a>(
+    accounts: impl IntoIterator<Item = &'a Account>,
+    mut writer: impl Write,
+) -> Result<()> {
+    for account in accounts {
+        writer.write_all(&messages::Account::from(account).encode_length_delimited_to_vec())?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{domain::transaction::TransactionKind, error::Error};
+
+    fn message(kind: &str, tx: u64, amount: Option<&str>) -> messages::Transaction {
+        messages::Transaction {
+            r#type: kind.into(),
+            client: 1,
+            tx,
+            amount: amount.map(Into::into),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn read_transactions() {
+        let mut input = Vec::new();
+        for message in [
+            message("deposit", 1, Some("1.5")),
+            message("payout", 2, Some("1")),
+            message("withdrawal", 3, Some("one")),
+            message("dispute", 1, None),
+        ] {
+            input.extend(message.encode_length_delimited_to_vec());
+        }
+
+        let txns: Vec<_> = read(input.as_slice()).collect();
+
+        assert_eq!(txns.len(), 4);
+        assert_eq!(
+            txns[0].as_ref().unwrap().kind,
+            TransactionKind::Deposit { amount: dec!(1.5) }
+        );
+        assert!(matches!(
+            &txns[1],
+            Err(Error::BusinessError(DomainError::UnknownType { kind, .. })) if kind == "payout"
+        ));
+        assert!(matches!(
+            txns[2],
+            Err(Error::BusinessError(DomainError::InvalidAmount { .. }))
+        ));
+        assert_eq!(
+            txns[3].as_ref().unwrap().kind,
+            TransactionKind::Dispute { amount: None }
+        );
+    }
+
+    #[test]
+    fn truncated_input() {
+        let mut input = message("deposit", 1, Some("1.5")).encode_length_delimited_to_vec();
+        input.pop();
+
+        let txns: Vec<_> = read(input.as_slice()).collect();
+
+        assert_eq!(txns.len(), 1);
+        assert!(matches!(txns[0], Err(Error::FileError(_))));
+    }
+
+    #[test]
+    fn write_accounts() {
+        let mut account = Account::new(1);
+        account.deposit(1, dec!(1.5)).unwrap();
+
+        let mut output = Vec::new();
+        write([&account], &mut output).unwrap();
+
+        let message = messages::Account::decode_length_delimited(output.as_slice()).unwrap();
+        assert_eq!(message.available, "1.5000");
+        assert_eq!(message.fees, None);
+    }
+}
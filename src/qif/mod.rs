@@ -0,0 +1,183 @@
+//! Import of QIF (Quicken Interchange Format) files.
+
+use std::{io::Read, str::FromStr};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+use crate::{
+    domain::{error::Error as DomainError, transaction::Transaction},
+    error::Result,
+};
+
+/// Parse the transactions of a QIF file as deposits, for positive amounts,
+/// and withdrawals, for negative ones, all of them for `client`.
+///
+/// Like with [OFX](crate::ofx::read), transactions are numbered in order,
+/// starting at `first_transaction_id`, and their timestamp is their date, at
+/// midnight UTC. Dates are month first, as Quicken writes them.
+///
+/// Each item is an error if the transaction has an invalid amount or date.
+pub fn read(
+    mut reader: impl Read,
+    client: u16,
+    first_transaction_id: u64,
+) -> Result<impl Iterator<Item = Result<Transaction>>> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+
+    Ok((first_transaction_id..)
+        .zip(parse(&input))
+        .map(move |(transaction_id, record)| {
+            let amount = record
+                .amount
+                .ok_or(DomainError::MissingAmount { transaction_id })?;
+            let amount = Decimal::from_str(&amount.replace(',', "")).map_err(|_| {
+                DomainError::InvalidAmount {
+                    transaction_id,
+                    amount: amount.clone(),
+                }
+            })?;
+
+            let mut tx = Transaction::signed(transaction_id, client, amount)?;
+            tx.timestamp = record
+                .date
+                .map(|date| {
+                    parse_date(&date).ok_or(DomainError::InvalidTimestamp {
+                        transaction_id,
+                        timestamp: date,
+                    })
+                })
+                .transpose()?;
+
+            Ok(tx)
+        }))
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    date: Option<String>,
+    amount: Option<String>,
+}
+
+/// Collect the records of every account listed in `input`, leaving out
+/// headers and lists like categories or classes, which aren't transactions.
+fn parse(input: &str) -> Vec<Record> {
+    let mut records = Vec::new();
+    let mut record = Record::default();
+    let mut in_transactions = false;
+
+    for line in input.lines() {
+        let line = line.trim_end();
+        let Some(code) = line.chars().next() else {
+            continue;
+        };
+        let value = line[code.len_utf8()..].trim();
+
+        match code {
+            '!' => {
+                let header = line.to_ascii_lowercase();
+                in_transactions = header.starts_with("!type:")
+                    && !matches!(
+                        &header["!type:".len()..],
+                        "cat" | "class" | "memorized" | "prices" | "security"
+                    );
+            }
+            '^' => {
+                let record = std::mem::take(&mut record);
+                if in_transactions {
+                    records.push(record);
+                }
+            }
+            'D' => record.date = Some(value.to_owned()),
+            // `U` repeats the amount with more precision in newer files.
+            'T' | 'U' if record.amount.is_none() => record.amount = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+
+    records
+}
+
+/// Parse a QIF date, like `01/31/2024`, `1/31/24` or `1/31'24`.
+///
+/// Two-digit years are taken to be in the 1900s, as in older files, unless
+/// Quicken wrote them with an apostrophe, which marks the 2000s.
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let (rest, century) = match value.split_once('\'') {
+        Some((rest, year)) => (format!("{rest}/{}", year.trim()), 2000),
+        None => (value.to_owned(), 1900),
+    };
+
+    let mut parts = rest
+        .split(['/', '-', '.'])
+        .map(|part| part.trim().parse::<u32>());
+    let (Some(Ok(month)), Some(Ok(day)), Some(Ok(year)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let year = if year < 100 { century + year } else { year };
+
+    let date = NaiveDate::from_ymd_opt(year.try_into().ok()?, month, day)?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{domain::transaction::TransactionKind, error::Error};
+
+    #[test]
+    fn read_transactions() {
+        let input = "!Type:Cat
+NGroceries
+^
+!Type:Bank
+D01/31/2024
+T1,500.00
+PEmployer
+^
+D2/1'24
+T-12.50
+PShop
+^
+D31/01/2024
+T-1
+^
+";
+
+        let txns: Vec<_> = read(input.as_bytes(), 7, 100).unwrap().collect();
+
+        assert_eq!(txns.len(), 3);
+        let deposit = txns[0].as_ref().unwrap();
+        assert_eq!(deposit.transaction_id, 100);
+        assert_eq!(deposit.client, 7);
+        assert_eq!(
+            deposit.kind,
+            TransactionKind::Deposit { amount: dec!(1500) }
+        );
+        assert_eq!(
+            deposit.timestamp.unwrap().to_rfc3339(),
+            "2024-01-31T00:00:00+00:00"
+        );
+        let withdrawal = txns[1].as_ref().unwrap();
+        assert_eq!(
+            withdrawal.kind,
+            TransactionKind::Withdrawal { amount: dec!(12.5) }
+        );
+        assert_eq!(
+            withdrawal.timestamp.unwrap().to_rfc3339(),
+            "2024-02-01T00:00:00+00:00"
+        );
+        assert!(matches!(
+            txns[2],
+            Err(Error::BusinessError(DomainError::InvalidTimestamp {
+                transaction_id: 102,
+                ..
+            }))
+        ));
+    }
+}
@@ -0,0 +1,218 @@
+//! gRPC service of `proto/txns.proto`, sharing its engine with the HTTP
+//! server, available with the `grpc` feature.
+
+use std::{future::Future, pin::Pin};
+
+use tokio::net::TcpListener;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, TcpListenerStream},
+    Stream, StreamExt,
+};
+use tonic::{transport::Server, Request, Response};
+
+use super::{Shared, Status};
+use crate::{
+    domain::transaction::Transaction,
+    protobuf::messages::{
+        self, submitted,
+        txns_server::{Txns, TxnsServer},
+    },
+};
+
+/// Implementation of the `Txns` service over the engine of a [`Shared`].
+#[derive(Debug)]
+struct Service {
+    shared: Shared,
+}
+
+#[tonic::async_trait]
+impl Txns for Service {
+    async fn submit_transaction(
+        &self,
+        request: Request<messages::Transaction>,
+    ) -> Result<Response<messages::Submitted>, tonic::Status> {
+        let tx = Transaction::try_from(request.into_inner())
+            .map_err(|error| tonic::Status::invalid_argument(error.to_string()))?;
+        let (status, _) = self.shared.apply(tx).await;
+
+        Ok(Response::new(status.into()))
+    }
+
+    async fn get_account(
+        &self,
+        request: Request<messages::GetAccountRequest>,
+    ) -> Result<Response<messages::Account>, tonic::Status> {
+        let client = request.into_inner().client;
+
+        u16::try_from(client)
+            .ok()
+            .and_then(|client| self.shared.engine.account(client))
+            .map(|account| Response::new((&account).into()))
+            .ok_or_else(|| tonic::Status::not_found(format!("client {client} has no account")))
+    }
+
+    type StreamAccountsStream =
+        Pin<Box<dyn Stream<Item = Result<messages::Account, tonic::Status>> + Send>>;
+
+    /// Accounts as transactions change them, until the server stops or the
+    /// client reads them too slowly to get them all, which ends the stream
+    /// with `DATA_LOSS`.
+    // Streams of tonic can only fail with its statuses, however large.
+    #[allow(clippy::result_large_err)]
+    async fn stream_accounts(
+        &self,
+        _: Request<messages::StreamAccountsRequest>,
+    ) -> Result<Response<Self::StreamAccountsStream>, tonic::Status> {
+        let updates = self.shared.subscribe().map(|update| {
+            update.map(|account| (&account).into()).map_err(
+                |BroadcastStreamRecvError::Lagged(missed)| {
+                    tonic::Status::data_loss(format!("missed {missed} account updates"))
+                },
+            )
+        });
+
+        Ok(Response::new(Box::pin(updates)))
+    }
+}
+
+impl From<Status> for messages::Submitted {
+    fn from(status: Status) -> Self {
+        let (status, tx, reason, error) = match status {
+            Status::Applied { transaction } => {
+                (submitted::Status::Applied, transaction, None, None)
+            }
+            Status::Ignored {
+                transaction,
+                reason,
+            } => (submitted::Status::Ignored, transaction, Some(reason), None),
+            Status::Rejected {
+                transaction,
+                reason,
+                error,
+            } => (
+                submitted::Status::Rejected,
+                transaction,
+                Some(reason),
+                Some(error),
+            ),
+        };
+
+        Self {
+            status: status.into(),
+            tx,
+            reason: reason.map(Into::into),
+            error,
+        }
+    }
+}
+
+/// Serve the `Txns` service of `shared` on `listener`, until `shutdown`
+/// completes and the calls being answered then are. Streams of account
+/// updates end then.
+pub async fn serve(
+    listener: TcpListener,
+    shared: Shared,
+    shutdown: impl Future<Output = ()> + Send,
+) -> Result<(), tonic::transport::Error> {
+    let stopping = shared.clone();
+    Server::builder()
+        .add_service(TxnsServer::new(Service { shared }))
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async move {
+            shutdown.await;
+            stopping.stop();
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tonic::{
+        client::Grpc,
+        codec::ProstCodec,
+        codegen::http::uri::PathAndQuery,
+        transport::{Channel, Endpoint},
+    };
+
+    use super::*;
+    use crate::engine::{shard::ConcurrentEngine, TransactionEngine};
+
+    async fn call<Q, R>(client: &mut Grpc<Channel>, method: &'static str, request: Q) -> R
+    where
+        Q: prost::Message + Send + Sync + 'static,
+        R: prost::Message + Default + Send + Sync + 'static,
+    {
+        client.ready().await.unwrap();
+        client
+            .unary(
+                Request::new(request),
+                PathAndQuery::from_static(method),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner()
+    }
+
+    fn deposit(tx: u64, amount: &str) -> messages::Transaction {
+        messages::Transaction {
+            r#type: "deposit".to_owned(),
+            client: 1,
+            tx,
+            amount: Some(amount.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn service() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let shared = Shared::new(Arc::new(
+            ConcurrentEngine::new(TransactionEngine::new(), 2).unwrap(),
+        ));
+        tokio::spawn(serve(listener, shared, std::future::pending()));
+        let channel = Endpoint::from_shared(format!("http://{address}"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = Grpc::new(channel);
+
+        let mut updates = {
+            client.ready().await.unwrap();
+            client
+                .server_streaming(
+                    Request::new(messages::StreamAccountsRequest {}),
+                    PathAndQuery::from_static("/txns.Txns/StreamAccounts"),
+                    ProstCodec::<_, messages::Account>::default(),
+                )
+                .await
+                .unwrap()
+                .into_inner()
+        };
+
+        let submitted: messages::Submitted = call(
+            &mut client,
+            "/txns.Txns/SubmitTransaction",
+            deposit(1, "2.5"),
+        )
+        .await;
+        assert_eq!(submitted.status(), submitted::Status::Applied);
+        let submitted: messages::Submitted =
+            call(&mut client, "/txns.Txns/SubmitTransaction", deposit(1, "1")).await;
+        assert_eq!(submitted.status(), submitted::Status::Rejected);
+        assert_eq!(submitted.reason.as_deref(), Some("duplicate_transaction"));
+
+        let account: messages::Account = call(
+            &mut client,
+            "/txns.Txns/GetAccount",
+            messages::GetAccountRequest { client: 1 },
+        )
+        .await;
+        assert_eq!(account.total, "2.5000");
+        let update = updates.message().await.unwrap().unwrap();
+        assert_eq!(update, account);
+    }
+}
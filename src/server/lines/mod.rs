@@ -0,0 +1,159 @@
+//! Transactions sent over plain TCP, a CSV row or JSON object per line, for
+//! systems that can only write a file to a socket, like with `nc`.
+
+use std::{future::Future, io};
+
+use csv::{ByteRecord, ReaderBuilder, StringRecord, Trim};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{info, warn};
+
+use super::{Shared, Status};
+use crate::{
+    csv::{parse, Headers},
+    domain::transaction::Transaction,
+    error::Result,
+};
+
+/// Columns of CSV rows sent without a header line first.
+const COLUMNS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Apply the lines of each connection accepted on `listener` until
+/// `shutdown` completes.
+///
+/// Nothing is written back, as whatever sends the lines may never read, so
+/// lines that can't be parsed or aren't applied are logged instead.
+pub async fn serve(
+    listener: TcpListener,
+    shared: Shared,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            () = &mut shutdown => return Ok(()),
+            accepted = listener.accept() => {
+                let (stream, peer) = accepted?;
+                let shared = shared.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = receive(stream, &shared).await {
+                        warn!(%peer, "connection from {peer} failed: {error}");
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Apply each line read from `stream` until it is closed.
+///
+/// A line starting with `{` is a JSON transaction, and any other a CSV row,
+/// of the columns named by the first row if one of them is `type`, or of
+/// `type,client,tx,amount` otherwise.
+async fn receive(stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut lines = BufReader::new(stream).lines();
+    let mut headers = None;
+    let (mut line, mut applied) = (0, 0);
+
+    while let Some(text) = lines.next_line().await? {
+        line += 1;
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let tx: Result<Transaction> = match text.starts_with('{') {
+            true => serde_json::from_str(text).map_err(Into::into),
+            false => match row(text) {
+                Ok(row) if headers.is_none() && row.iter().any(|field| field == b"type") => {
+                    let names = StringRecord::from_byte_record_lossy(row);
+                    headers = Some(Headers::new(&names));
+                    continue;
+                }
+                Ok(row) => {
+                    let headers = headers
+                        .get_or_insert_with(|| Headers::new(&StringRecord::from(&COLUMNS[..])));
+                    parse(&row, headers, false)
+                }
+                Err(error) => Err(error),
+            },
+        };
+
+        let status = match tx {
+            Ok(tx) => Ok(shared.apply(tx).await.0),
+            Err(error) => Err(error),
+        };
+        match status {
+            Ok(Status::Applied { .. }) => applied += 1,
+            Ok(Status::Ignored { reason, .. } | Status::Rejected { reason, .. }) => {
+                info!(%peer, line, reason, "line {line} from {peer} not applied: {reason}");
+            }
+            Err(error) => info!(%peer, line, "skipped line {line} from {peer}: {error}"),
+        }
+    }
+
+    info!(%peer, applied, "applied {applied} transactions from {peer}");
+    Ok(())
+}
+
+/// The fields of a CSV line.
+fn row(text: &str) -> Result<ByteRecord> {
+    let mut row = ByteRecord::new();
+    ReaderBuilder::new()
+        .has_headers(false)
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(text.as_bytes())
+        .read_byte_record(&mut row)?;
+
+    Ok(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use rust_decimal_macros::dec;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::engine::{shard::ConcurrentEngine, TransactionEngine};
+
+    #[tokio::test]
+    async fn lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let engine = Arc::new(ConcurrentEngine::new(TransactionEngine::new(), 2).unwrap());
+        tokio::spawn(serve(
+            listener,
+            Shared::new(Arc::clone(&engine)),
+            std::future::pending(),
+        ));
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"deposit, 1, 1, 2.5\nnonsense\n{\"type\": \"deposit\", \"client\": 2, \"tx\": 2, \"amount\": \"1\"}\n")
+            .await
+            .unwrap();
+        drop(stream);
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"client,type,tx,amount\n3,deposit,3,0.5\n")
+            .await
+            .unwrap();
+        drop(stream);
+
+        for _ in 0..100 {
+            let accounts = [1, 2, 3].map(|client| engine.account(client).map(|a| a.total()));
+            if accounts.iter().all(Option::is_some) {
+                assert_eq!(accounts, [dec!(2.5), dec!(1), dec!(0.5)].map(Some));
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the lines were not applied");
+    }
+}
@@ -0,0 +1,703 @@
+//! HTTP server applying transactions to a [`ConcurrentEngine`] as they are
+//! posted, or sent over a WebSocket, and answering queries about accounts,
+//! available with the `server` feature.
+
+use std::{
+    convert::Infallible,
+    future::Future,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{
+        rejection::QueryRejection,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, watch},
+};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream, WatchStream},
+    Stream, StreamExt,
+};
+
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod lines;
+#[cfg(unix)]
+pub mod unix;
+
+use crate::{
+    domain::{
+        account::Account,
+        transaction::{PrecisionPolicy, Transaction},
+    },
+    engine::{journal::Journal, shard::ConcurrentEngine, Outcome},
+    error::Error,
+    json::Record,
+};
+
+/// What came out of a posted transaction, tagged by its `status`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Status {
+    Applied {
+        transaction: u64,
+    },
+    Ignored {
+        transaction: u64,
+        /// Code of why it was ignored, like `unknown_transaction`.
+        reason: &'static str,
+    },
+    Rejected {
+        transaction: u64,
+        /// Code of why it was rejected, like `insufficient_funds`.
+        reason: &'static str,
+        error: String,
+    },
+}
+
+impl Status {
+    /// Status of transaction `transaction`, given what came out of it.
+    fn new(transaction: u64, outcome: Result<Outcome, Error>) -> Self {
+        match outcome {
+            Ok(Outcome::Applied) => Self::Applied { transaction },
+            Ok(Outcome::Ignored(ignored)) => Self::Ignored {
+                transaction,
+                reason: ignored.code(),
+            },
+            Err(error) => Self::Rejected {
+                transaction,
+                reason: match &error {
+                    Error::BusinessError(error) => error.code(),
+                    Error::ShardedTransfer(_) => "sharded_transfer",
+                    // Only the journal is written to while applying.
+                    Error::FileError(_) => "journal",
+                    _ => "invalid",
+                },
+                error: error.to_string(),
+            },
+        }
+    }
+}
+
+/// How a transaction changed the account of a client.
+#[derive(Debug, Serialize)]
+pub struct Delta {
+    pub client: u16,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    /// Whether the account is locked now.
+    pub locked: bool,
+}
+
+impl Delta {
+    /// Change from `before`, or from no account at all, to `after`.
+    fn new(before: Option<&Account>, after: &Account) -> Self {
+        let change = |balance: fn(&Account) -> Decimal| {
+            balance(after) - before.map_or(Decimal::ZERO, balance)
+        };
+
+        Self {
+            client: after.client(),
+            available: change(Account::available),
+            held: change(Account::held),
+            total: change(Account::total),
+            locked: after.locked(),
+        }
+    }
+}
+
+/// Reply to a transaction sent over a WebSocket, or a Unix socket: its
+/// [`Status`] along with the `deltas` of the accounts it changed.
+#[derive(Debug, Serialize)]
+struct Ack {
+    #[serde(flatten)]
+    status: Status,
+    deltas: Vec<Delta>,
+}
+
+/// How many account updates can wait for a subscriber of `/accounts/stream`
+/// before it misses some.
+const UPDATES: usize = 1024;
+
+/// What the routes share, and the gRPC service with the `grpc` feature: the
+/// engine, the journal if any, how amounts that are too precise are handled,
+/// and where accounts are sent when transactions change them.
+#[derive(Debug, Clone)]
+pub struct Shared {
+    engine: Arc<ConcurrentEngine>,
+    /// What to do with amounts with more than four decimal places.
+    precision: PrecisionPolicy,
+    /// Journal every transaction is appended to before being applied.
+    journal: Option<Arc<Mutex<Journal>>>,
+    updates: broadcast::Sender<Account>,
+    /// Whether the server is stopping, which ends the streams of updates.
+    stopping: Arc<watch::Sender<bool>>,
+}
+
+impl Shared {
+    pub fn new(engine: Arc<ConcurrentEngine>) -> Self {
+        Self {
+            engine,
+            precision: PrecisionPolicy::default(),
+            journal: None,
+            updates: broadcast::channel(UPDATES).0,
+            stopping: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Append every transaction to `journal` before applying it, which
+    /// applies them one at a time, in the order of the journal.
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(Arc::new(Mutex::new(journal)));
+        self
+    }
+
+    /// Handle amounts with more than four decimal places with `precision`,
+    /// instead of rounding them.
+    pub fn with_precision(mut self, precision: PrecisionPolicy) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Accounts as transactions change them, until the server stops.
+    fn subscribe(&self) -> impl Stream<Item = Result<Account, BroadcastStreamRecvError>> {
+        let stopped = WatchStream::new(self.stopping.subscribe())
+            .filter(|stopping| *stopping)
+            .map(|_| None);
+
+        BroadcastStream::new(self.updates.subscribe())
+            .map(Some)
+            .merge(stopped)
+            .map_while(|update| update)
+    }
+
+    /// End the streams of updates, as a graceful shutdown would otherwise
+    /// wait for their subscribers to leave.
+    fn stop(&self) {
+        self.stopping.send_replace(true);
+    }
+
+    /// Apply `tx`, with the precision policy enforced and journaling it
+    /// first if there is a journal, sending the accounts it changed to the
+    /// subscribers of updates, and returning how it changed them.
+    /// Transactions that can't be journaled are rejected.
+    ///
+    /// It is applied on a thread of its own, so waiting for the journal to
+    /// reach the disk, or for a store on disk, doesn't hold up the requests
+    /// the runtime is answering meanwhile.
+    async fn apply(&self, tx: Transaction) -> (Status, Vec<Delta>) {
+        let shared = self.clone();
+        tokio::task::spawn_blocking(move || shared.apply_blocking(tx))
+            .await
+            .expect("applying a transaction doesn't panic")
+    }
+
+    fn apply_blocking(&self, tx: Transaction) -> (Status, Vec<Delta>) {
+        let transaction = tx.transaction_id;
+        let tx = match tx.enforce_precision(self.precision) {
+            Ok(tx) => tx,
+            Err(error) => return (Status::new(transaction, Err(error.into())), Vec::new()),
+        };
+        // The journal stays locked until `tx` is applied, so transactions are
+        // applied in the order they were journaled, as they are on restart.
+        let mut journal = self
+            .journal
+            .as_deref()
+            .map(|journal| journal.lock().unwrap_or_else(|error| error.into_inner()));
+        let applied = match &mut journal {
+            Some(journal) => journal
+                .append(&tx)
+                .and_then(|()| self.engine.apply_with_accounts(tx)),
+            None => self.engine.apply_with_accounts(tx),
+        };
+        drop(journal);
+
+        let (outcome, changed) = match applied {
+            Ok((Outcome::Applied, accounts)) => (Ok(Outcome::Applied), accounts),
+            Ok((outcome, _)) => (Ok(outcome), Vec::new()),
+            Err(error) => (Err(error), Vec::new()),
+        };
+
+        let deltas = changed
+            .into_iter()
+            .filter_map(|(before, after)| {
+                let after = after?;
+                let delta = Delta::new(before.as_ref(), &after);
+                // Nobody may be subscribed.
+                let _ = self.updates.send(after);
+                Some(delta)
+            })
+            .collect();
+
+        (Status::new(transaction, outcome), deltas)
+    }
+}
+
+/// Statuses of a single posted transaction or of a batch of them.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Posted {
+    One(Status),
+    Many(Vec<Status>),
+}
+
+/// Which accounts `GET /accounts` answers with, and which page of them.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountsQuery {
+    locked: Option<bool>,
+    min_total: Option<Decimal>,
+    max_total: Option<Decimal>,
+    #[serde(default = "AccountsQuery::first_page")]
+    page: usize,
+    #[serde(default = "AccountsQuery::per_page")]
+    per_page: usize,
+}
+
+impl AccountsQuery {
+    const MAX_PER_PAGE: usize = 1000;
+
+    fn first_page() -> usize {
+        1
+    }
+
+    fn per_page() -> usize {
+        100
+    }
+
+    fn matches(&self, account: &Account) -> bool {
+        self.locked.is_none_or(|locked| account.locked() == locked)
+            && self.min_total.is_none_or(|min| account.total() >= min)
+            && self.max_total.is_none_or(|max| account.total() <= max)
+    }
+}
+
+/// A page of the accounts matching an [`AccountsQuery`], in client order.
+#[derive(Debug, Serialize)]
+struct AccountsPage {
+    accounts: Vec<Account>,
+    page: usize,
+    per_page: usize,
+    /// How many accounts match, on every page.
+    total: usize,
+    pages: usize,
+}
+
+type Failure = (StatusCode, Json<Record<'static>>);
+
+fn failure(status: StatusCode, error: impl ToString) -> Failure {
+    (
+        status,
+        Json(Record::Error {
+            error: error.to_string(),
+        }),
+    )
+}
+
+/// Routes sharing `shared`:
+///
+/// - `POST /transactions` applies a transaction, given as a JSON object with
+///   the same fields as CSV rows, or an array of them in order, answering with
+///   the [`Status`] of each;
+/// - `GET /accounts/:client` answers with the account of `client`, as it is
+///   now, with the same fields as CSV output;
+/// - `GET /accounts` answers with a page of the accounts, in client order, only
+///   those that are `locked` or not and with a total between `min_total` and
+///   `max_total` if given. Pages hold `per_page` accounts, 100 by default and
+///   1000 at most, starting at `page=1`, and tell how many accounts match and
+///   over how many `pages`;
+/// - `GET /accounts/stream` sends server-sent events for as long as the client
+///   listens: an `account` event with the account, as JSON, whenever a
+///   transaction changes its balances or lock, and a `lagged` event with how
+///   many updates were missed when the client reads too slowly to get them all,
+///   until the server stops;
+/// - `GET /transactions/ws` upgrades to a WebSocket, where every text or binary
+///   message is a transaction, given as a JSON object, and is answered in order
+///   by its [`Status`] along with the [`Delta`]s of the accounts it changed, as
+///   `deltas`.
+///
+/// Errors are answered as an `{"error": ...}` object.
+pub fn router(shared: Shared) -> Router {
+    Router::new()
+        .route("/transactions", post(transactions))
+        .route("/transactions/ws", get(websocket))
+        .route("/accounts", get(accounts))
+        .route("/accounts/stream", get(stream))
+        .route("/accounts/:client", get(account))
+        .with_state(shared)
+}
+
+/// Serve the [`router`] of `shared` on `listener`, until `shutdown` completes
+/// and the requests being answered then are. Streams of account updates end
+/// then.
+pub async fn serve(
+    listener: TcpListener,
+    shared: Shared,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    let stopping = shared.clone();
+    axum::serve(listener, router(shared))
+        .with_graceful_shutdown(async move {
+            shutdown.await;
+            stopping.stop();
+        })
+        .await
+}
+
+async fn transactions(State(shared): State<Shared>, body: Bytes) -> Result<Json<Posted>, Failure> {
+    let batch = body.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'[');
+    let invalid = |error| failure(StatusCode::BAD_REQUEST, error);
+
+    Ok(Json(match batch {
+        true => {
+            let txns = serde_json::from_slice::<Vec<Transaction>>(&body).map_err(invalid)?;
+            let mut statuses = Vec::with_capacity(txns.len());
+            for tx in txns {
+                statuses.push(shared.apply(tx).await.0);
+            }
+            Posted::Many(statuses)
+        }
+        false => {
+            let tx = serde_json::from_slice(&body).map_err(invalid)?;
+            Posted::One(shared.apply(tx).await.0)
+        }
+    }))
+}
+
+async fn websocket(State(shared): State<Shared>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| acknowledge(socket, shared))
+}
+
+/// Apply the transactions received on `socket` until it is closed, replying
+/// to each with an [`Ack`], or an `{"error": ...}` object if it can't be
+/// parsed.
+async fn acknowledge(mut socket: WebSocket, shared: Shared) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let tx = match &message {
+            Message::Text(text) => serde_json::from_str::<Transaction>(text),
+            Message::Binary(bytes) => serde_json::from_slice(bytes),
+            Message::Close(_) => break,
+            // Pings are answered by axum.
+            Message::Ping(_) | Message::Pong(_) => continue,
+        };
+        let reply = match tx {
+            Ok(tx) => {
+                let (status, deltas) = shared.apply(tx).await;
+                serde_json::to_string(&Ack { status, deltas })
+            }
+            Err(error) => serde_json::to_string(&Record::Error {
+                error: error.to_string(),
+            }),
+        };
+
+        let reply = reply.expect("replies serialize to JSON");
+        if socket.send(Message::Text(reply)).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn account(
+    State(Shared { engine, .. }): State<Shared>,
+    Path(client): Path<u16>,
+) -> Result<Json<Account>, Failure> {
+    engine.account(client).map(Json).ok_or_else(|| {
+        failure(
+            StatusCode::NOT_FOUND,
+            format!("client {client} has no account"),
+        )
+    })
+}
+
+async fn accounts(
+    State(Shared { engine, .. }): State<Shared>,
+    query: Result<Query<AccountsQuery>, QueryRejection>,
+) -> Result<Json<AccountsPage>, Failure> {
+    let Query(query) =
+        query.map_err(|error| failure(StatusCode::BAD_REQUEST, error.body_text()))?;
+    if query.page == 0 || !(1..=AccountsQuery::MAX_PER_PAGE).contains(&query.per_page) {
+        return Err(failure(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "pages start at 1 and hold 1 to {} accounts",
+                AccountsQuery::MAX_PER_PAGE
+            ),
+        ));
+    }
+
+    let mut accounts: Vec<_> = engine
+        .accounts()
+        .into_iter()
+        .filter(|account| query.matches(account))
+        .collect();
+    accounts.sort_by_key(Account::client);
+    let total = accounts.len();
+    let accounts = accounts
+        .into_iter()
+        .skip((query.page - 1) * query.per_page)
+        .take(query.per_page)
+        .collect();
+
+    Ok(Json(AccountsPage {
+        accounts,
+        page: query.page,
+        per_page: query.per_page,
+        total,
+        pages: total.div_ceil(query.per_page),
+    }))
+}
+
+async fn stream(
+    State(shared): State<Shared>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let updates = shared.subscribe().map(|update| {
+        Ok(match update {
+            Ok(account) => Event::default()
+                .event("account")
+                .json_data(account)
+                .expect("accounts serialize to JSON"),
+            Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                Event::default().event("lagged").data(missed.to_string())
+            }
+        })
+    });
+
+    Sse::new(updates).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use futures_util::SinkExt;
+    use rust_decimal_macros::dec;
+    use serde_json::{json, Value};
+    use tokio_tungstenite::tungstenite;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::engine::TransactionEngine;
+
+    async fn send(router: &Router, request: Request<Body>) -> (StatusCode, Value) {
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn post(body: Value) -> Request<Body> {
+        Request::post("/transactions")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn transactions_and_accounts() {
+        let router = router(Shared::new(Arc::new(
+            ConcurrentEngine::new(TransactionEngine::new(), 2).unwrap(),
+        )));
+
+        let (status, body) = send(
+            &router,
+            post(json!({"type": "deposit", "client": 1, "tx": 1, "amount": "2.5"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"status": "applied", "transaction": 1}));
+
+        let (_, body) = send(
+            &router,
+            post(json!([
+                {"type": "withdrawal", "client": 1, "tx": 2, "amount": "5"},
+                {"type": "resolve", "client": 1, "tx": 1},
+            ])),
+        )
+        .await;
+        assert_eq!(body[0]["status"], "rejected");
+        assert_eq!(body[0]["reason"], "insufficient_funds");
+        assert_eq!(body[1]["status"], "ignored");
+
+        let (status, body) = send(&router, get("/accounts/1")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["client"], 1);
+        assert_eq!(body["total"], "2.5000");
+
+        send(
+            &router,
+            post(json!([
+                {"type": "deposit", "client": 2, "tx": 3, "amount": "10"},
+                {"type": "deposit", "client": 3, "tx": 4, "amount": "20"},
+                {"type": "dispute", "client": 3, "tx": 4},
+                {"type": "chargeback", "client": 3, "tx": 4},
+            ])),
+        )
+        .await;
+        let (_, body) = send(&router, get("/accounts?min_total=2&per_page=1&page=2")).await;
+        assert_eq!(body["total"], 2);
+        assert_eq!(body["pages"], 2);
+        assert_eq!(body["accounts"][0]["client"], 2);
+        let (_, body) = send(&router, get("/accounts?locked=true")).await;
+        assert_eq!(body["accounts"][0]["client"], 3);
+        assert_eq!(body["total"], 1);
+        let (status, _) = send(&router, get("/accounts?page=0")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = send(&router, get("/accounts/4")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, body) = send(&router, post(json!({"type": "deposit"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn precision() {
+        let engine = || Arc::new(ConcurrentEngine::new(TransactionEngine::new(), 1).unwrap());
+        let deposit = json!({"type": "deposit", "client": 1, "tx": 1, "amount": "1.00006"});
+
+        let rounding = router(Shared::new(engine()));
+        let (_, body) = send(&rounding, post(deposit.clone())).await;
+        assert_eq!(body["status"], "applied");
+        let (_, body) = send(&rounding, get("/accounts/1")).await;
+        assert_eq!(body["total"], "1.0001");
+
+        let rejecting = router(Shared::new(engine()).with_precision(PrecisionPolicy::Reject));
+        let (_, body) = send(&rejecting, post(deposit)).await;
+        assert_eq!(body["status"], "rejected");
+        assert_eq!(body["reason"], "excessive_precision");
+        let (status, _) = send(&rejecting, get("/accounts/1")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn journaled() {
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let mut engine = TransactionEngine::new();
+        let (journal, _) = Journal::open(&path, &mut engine).unwrap();
+        let router = router(
+            Shared::new(Arc::new(ConcurrentEngine::new(engine, 1).unwrap())).with_journal(journal),
+        );
+
+        for tx in [
+            json!({"type": "deposit", "client": 1, "tx": 1, "amount": "3"}),
+            json!({"type": "withdrawal", "client": 1, "tx": 2, "amount": "5"}),
+        ] {
+            send(&router, post(tx)).await;
+        }
+
+        let mut restarted = TransactionEngine::new();
+        let (_, count) = Journal::open(&path, &mut restarted).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(restarted.account(1).unwrap().total(), dec!(3));
+    }
+
+    #[tokio::test]
+    async fn account_updates() {
+        let router = router(Shared::new(Arc::new(
+            ConcurrentEngine::new(TransactionEngine::new(), 2).unwrap(),
+        )));
+        let response = router
+            .clone()
+            .oneshot(get("/accounts/stream"))
+            .await
+            .unwrap();
+        let mut events = response.into_body().into_data_stream();
+
+        for tx in [
+            json!({"type": "deposit", "client": 1, "tx": 1, "amount": "3"}),
+            json!({"type": "dispute", "client": 1, "tx": 9}),
+            json!({"type": "deposit", "client": 2, "tx": 2, "amount": "1"}),
+        ] {
+            send(&router, post(tx)).await;
+        }
+
+        for client in [1, 2] {
+            let event = events.next().await.unwrap().unwrap();
+            let event = String::from_utf8(event.to_vec()).unwrap();
+            assert!(event.starts_with("event: account\n"), "{event}");
+            assert!(event.contains(&format!("\"client\":{client},")), "{event}");
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_with_subscriber() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let engine = Arc::new(ConcurrentEngine::new(TransactionEngine::new(), 1).unwrap());
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve(listener, Shared::new(engine), async move {
+            let _ = stopped.await;
+        }));
+
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        let request = format!("GET /accounts/stream HTTP/1.1\r\nHost: {address}\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut head = [0; 12];
+        stream.read_exact(&mut head).await.unwrap();
+        assert_eq!(&head, b"HTTP/1.1 200");
+
+        stop.send(()).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), server)
+            .await
+            .expect("the server stops with a subscriber connected")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn websocket() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let engine = Arc::new(ConcurrentEngine::new(TransactionEngine::new(), 2).unwrap());
+        tokio::spawn(serve(listener, Shared::new(engine), std::future::pending()));
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async(format!("ws://{address}/transactions/ws"))
+                .await
+                .unwrap();
+
+        for tx in [
+            json!({"type": "deposit", "client": 1, "tx": 1, "amount": "3"}),
+            json!({"type": "transfer", "client": 1, "tx": 2, "amount": "1", "to_client": 3}),
+            json!({"type": "deposit", "client": 1}),
+        ] {
+            let message = tungstenite::Message::Text(tx.to_string());
+            socket.send(message).await.unwrap();
+        }
+        let mut acks = Vec::new();
+        for _ in 0..3 {
+            let message = socket.next().await.unwrap().unwrap();
+            acks.push(serde_json::from_str::<Value>(message.to_text().unwrap()).unwrap());
+        }
+
+        assert_eq!(acks[0]["status"], "applied");
+        assert_eq!(acks[0]["deltas"][0]["available"], "3.0000");
+        assert_eq!(acks[1]["deltas"][0]["total"], "-1.0000");
+        assert_eq!(acks[1]["deltas"][1]["client"], 3);
+        assert_eq!(acks[1]["deltas"][1]["total"], "1.0000");
+        assert!(acks[2]["error"].is_string());
+    }
+}
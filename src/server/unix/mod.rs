@@ -0,0 +1,137 @@
+//! Transactions and balance queries over a Unix domain socket, a JSON object
+//! per line each way, for processes on the same host to skip HTTP.
+
+use std::{future::Future, io};
+
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+};
+use tracing::warn;
+
+use super::{Ack, Shared};
+use crate::{domain::transaction::Transaction, json::Record};
+
+/// Line asking for the account of a client, like `{"account": 42}`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountQuery {
+    account: u16,
+}
+
+/// Answer the connections accepted on `listener` until `shutdown` completes.
+pub async fn serve(
+    listener: UnixListener,
+    shared: Shared,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            () = &mut shutdown => return Ok(()),
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                let shared = shared.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = answer(stream, &shared).await {
+                        warn!("unix socket connection failed: {error}");
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Answer each line read from `stream` until it is closed: an
+/// [`AccountQuery`] with the account as it is now, and a transaction with an
+/// [`Ack`] once applied. Lines that are neither, or clients without an
+/// account, are answered with an `{"error": ...}` object.
+async fn answer(stream: UnixStream, shared: &Shared) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match serde_json::from_str::<AccountQuery>(&line) {
+            Ok(AccountQuery { account: client }) => {
+                let account = shared.engine.account(client);
+                let record = match &account {
+                    Some(account) => Record::Account(account),
+                    None => Record::Error {
+                        error: format!("client {client} has no account"),
+                    },
+                };
+                serde_json::to_string(&record)
+            }
+            Err(_) => match serde_json::from_str::<Transaction>(&line) {
+                Ok(tx) => {
+                    let (status, deltas) = shared.apply(tx).await;
+                    serde_json::to_string(&Ack { status, deltas })
+                }
+                Err(error) => serde_json::to_string(&Record::Error {
+                    error: error.to_string(),
+                }),
+            },
+        };
+
+        let mut reply = reply.expect("replies serialize to JSON");
+        reply.push('\n');
+        writer.write_all(reply.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::{json, Value};
+    use tokio::io::Lines;
+
+    use super::*;
+    use crate::engine::{shard::ConcurrentEngine, TransactionEngine};
+
+    async fn request(
+        writer: &mut (impl AsyncWriteExt + Unpin),
+        lines: &mut Lines<impl AsyncBufReadExt + Unpin>,
+        request: Value,
+    ) -> Value {
+        writer
+            .write_all(format!("{request}\n").as_bytes())
+            .await
+            .unwrap();
+        let reply = lines.next_line().await.unwrap().unwrap();
+
+        serde_json::from_str(&reply).unwrap()
+    }
+
+    #[tokio::test]
+    async fn queries() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("txns.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let shared = Shared::new(Arc::new(
+            ConcurrentEngine::new(TransactionEngine::new(), 2).unwrap(),
+        ));
+        tokio::spawn(serve(listener, shared, std::future::pending()));
+
+        let (reader, mut writer) = UnixStream::connect(&path).await.unwrap().into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        let deposit = json!({"type": "deposit", "client": 1, "tx": 1, "amount": "2.5"});
+        let ack = request(&mut writer, &mut lines, deposit).await;
+        assert_eq!(ack["status"], "applied");
+        assert_eq!(ack["deltas"][0]["available"], "2.5000");
+        let account = request(&mut writer, &mut lines, json!({"account": 1})).await;
+        assert_eq!(account["total"], "2.5000");
+        let missing = request(&mut writer, &mut lines, json!({"account": 2})).await;
+        assert_eq!(missing, json!({"error": "client 2 has no account"}));
+        let invalid = request(&mut writer, &mut lines, json!({"type": "deposit"})).await;
+        assert!(invalid["error"].is_string());
+    }
+}
@@ -0,0 +1,141 @@
+//! Kafka topics consumed as part of a consumer group, committing offsets
+//! once their messages are applied.
+
+use std::{collections::BTreeMap, str::FromStr, time::Duration};
+
+use rdkafka::{
+    consumer::{CommitMode, Consumer, StreamConsumer},
+    error::KafkaError,
+    ClientConfig, Message, Offset, TopicPartitionList,
+};
+use tokio::time;
+use tracing::warn;
+
+use super::{Delivery, Source};
+use crate::error::Result;
+
+/// How long to wait after failing to consume a message, so errors that last
+/// aren't logged over and over.
+const RETRY: Duration = Duration::from_secs(1);
+
+/// Brokers, topic and consumer group to consume, written
+/// `brokers/topic/group`, like `localhost:9092/transactions/txns`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    /// Comma-separated `host:port` list of bootstrap brokers.
+    pub brokers: String,
+    pub topic: String,
+    pub group: String,
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(address: &str) -> std::result::Result<Self, String> {
+        match address.splitn(3, '/').collect::<Vec<_>>()[..] {
+            [brokers, topic, group] if [brokers, topic, group].iter().all(|s| !s.is_empty()) => {
+                Ok(Self {
+                    brokers: brokers.to_owned(),
+                    topic: topic.to_owned(),
+                    group: group.to_owned(),
+                })
+            }
+            _ => Err("expected `brokers/topic/group`".to_owned()),
+        }
+    }
+}
+
+/// Position of a message in its topic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub partition: i32,
+    pub offset: i64,
+}
+
+/// Consumer of the topic of an [`Address`], starting from the offsets last
+/// committed by its group, or from the earliest messages if there are none.
+pub struct Kafka {
+    consumer: StreamConsumer,
+    topic: String,
+}
+
+impl Kafka {
+    pub fn connect(address: &Address) -> Result<Self> {
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", &address.brokers)
+            .set("group.id", &address.group)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .create()?;
+        consumer.subscribe(&[&address.topic])?;
+
+        Ok(Self {
+            consumer,
+            topic: address.topic.clone(),
+        })
+    }
+}
+
+impl Source for Kafka {
+    type Receipt = Position;
+
+    /// Next message, logging errors consuming one, like brokers being
+    /// unreachable, which the consumer recovers from by itself.
+    async fn receive(&mut self) -> Result<Delivery<Position>> {
+        let message = loop {
+            match self.consumer.recv().await {
+                Ok(message) => break message,
+                Err(KafkaError::MessageConsumption(code)) => {
+                    warn!(%code, "could not consume from Kafka: {code}");
+                    time::sleep(RETRY).await;
+                }
+                Err(error) => return Err(error.into()),
+            }
+        };
+
+        Ok(Delivery {
+            payload: message.payload().unwrap_or_default().to_vec(),
+            receipt: Position {
+                partition: message.partition(),
+                offset: message.offset(),
+            },
+        })
+    }
+
+    /// Commit the offsets following the last of `receipts` in each partition.
+    async fn acknowledge(&mut self, receipts: Vec<Position>) -> Result<()> {
+        let last = receipts
+            .into_iter()
+            .map(|position| (position.partition, position.offset))
+            .collect::<BTreeMap<_, _>>();
+        if last.is_empty() {
+            return Ok(());
+        }
+
+        let mut offsets = TopicPartitionList::new();
+        for (partition, offset) in last {
+            offsets.add_partition_offset(&self.topic, partition, Offset::Offset(offset + 1))?;
+        }
+
+        Ok(self.consumer.commit(&offsets, CommitMode::Sync)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address() {
+        assert_eq!(
+            "broker1:9092,broker2:9092/transactions/txns/eu".parse(),
+            Ok(Address {
+                brokers: "broker1:9092,broker2:9092".to_owned(),
+                topic: "transactions".to_owned(),
+                group: "txns/eu".to_owned(),
+            })
+        );
+        assert!("localhost:9092/transactions".parse::<Address>().is_err());
+        assert!("localhost:9092//txns".parse::<Address>().is_err());
+    }
+}
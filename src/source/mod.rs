@@ -0,0 +1,57 @@
+//! Message brokers transactions can be consumed from continuously, one JSON
+//! transaction per message, like Kafka with the `kafka` feature, NATS
+//! JetStream with the `nats` feature, Redis streams with the `redis` feature
+//! or RabbitMQ with the `amqp` feature.
+//!
+//! Messages are only acknowledged once applied, so those received by a
+//! consumer that stops before are delivered again.
+
+use std::future::Future;
+
+use crate::{domain::transaction::Transaction, error::Result};
+
+#[cfg(feature = "amqp")]
+pub mod amqp;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "redis")]
+pub mod redis;
+
+/// Message received from a [`Source`].
+#[derive(Debug)]
+pub struct Delivery<R> {
+    pub payload: Vec<u8>,
+    /// What acknowledges the message, like its offset.
+    pub receipt: R,
+}
+
+impl<R> Delivery<R> {
+    /// The transaction the message holds.
+    pub fn transaction(&self) -> Result<Transaction> {
+        Ok(serde_json::from_slice(&self.payload)?)
+    }
+}
+
+/// Broker transactions are consumed from.
+pub trait Source {
+    type Receipt;
+
+    /// Next message, waiting until there is one.
+    fn receive(&mut self) -> impl Future<Output = Result<Delivery<Self::Receipt>>>;
+
+    /// Acknowledge the messages of `receipts`, in the order they were
+    /// received, so they aren't delivered again.
+    fn acknowledge(&mut self, receipts: Vec<Self::Receipt>) -> impl Future<Output = Result<()>>;
+
+    /// Set aside a message that holds no transaction, like by dead-lettering
+    /// it, giving back its receipt if it should rather be acknowledged with
+    /// the others, as by default.
+    fn reject(
+        &mut self,
+        delivery: Delivery<Self::Receipt>,
+    ) -> impl Future<Output = Result<Option<Self::Receipt>>> {
+        async move { Ok(Some(delivery.receipt)) }
+    }
+}
@@ -0,0 +1,152 @@
+//! NATS JetStream subjects consumed through a durable pull consumer,
+//! acknowledging each message explicitly once applied.
+
+use std::{io, str::FromStr, time::Duration};
+
+use async_nats::{
+    jetstream::{
+        self,
+        consumer::{pull, AckPolicy},
+        message::Acker,
+    },
+    ServerAddr,
+};
+use tokio::time;
+use tokio_stream::StreamExt;
+use tracing::warn;
+
+use super::{Delivery, Source};
+use crate::error::{Error, Result};
+
+/// How long to wait after failing to pull a message, so errors that last
+/// aren't logged over and over.
+const RETRY: Duration = Duration::from_secs(1);
+
+/// Servers, subject and durable consumer to consume, written
+/// `servers/subject/durable`, like `localhost:4222/transactions.>/txns`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    /// Comma-separated list of servers, like `nats://localhost:4222`.
+    pub servers: String,
+    pub subject: String,
+    pub durable: String,
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(address: &str) -> std::result::Result<Self, String> {
+        match address.rsplitn(3, '/').collect::<Vec<_>>()[..] {
+            [durable, subject, servers]
+                if [servers, subject, durable].iter().all(|s| !s.is_empty()) =>
+            {
+                Ok(Self {
+                    servers: servers.to_owned(),
+                    subject: subject.to_owned(),
+                    durable: durable.to_owned(),
+                })
+            }
+            _ => Err("expected `servers/subject/durable`".to_owned()),
+        }
+    }
+}
+
+/// Durable consumer of the subject of an [`Address`], created on the stream
+/// holding the subject unless it exists, in which case it resumes from the
+/// messages it last acknowledged.
+pub struct Nats {
+    messages: pull::Stream,
+}
+
+impl Nats {
+    pub async fn connect(address: &Address) -> Result<Self> {
+        let servers = address
+            .servers
+            .split(',')
+            .map(str::parse)
+            .collect::<io::Result<Vec<ServerAddr>>>()?;
+        let jetstream = jetstream::new(async_nats::connect(servers).await.map_err(nats)?);
+        let stream = jetstream
+            .stream_by_subject(&address.subject)
+            .await
+            .map_err(nats)?;
+        let consumer = jetstream
+            .get_stream(stream)
+            .await
+            .map_err(nats)?
+            .get_or_create_consumer(
+                &address.durable,
+                pull::Config {
+                    durable_name: Some(address.durable.clone()),
+                    filter_subject: address.subject.clone(),
+                    ack_policy: AckPolicy::Explicit,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(nats)?;
+
+        Ok(Self {
+            messages: consumer.messages().await.map_err(nats)?,
+        })
+    }
+}
+
+impl Source for Nats {
+    type Receipt = Acker;
+
+    /// Next message, logging errors pulling one, like missed heartbeats,
+    /// which the consumer recovers from by itself.
+    async fn receive(&mut self) -> Result<Delivery<Acker>> {
+        let message = loop {
+            match self.messages.next().await {
+                Some(Ok(message)) => break message,
+                Some(Err(error)) => {
+                    warn!("could not pull from NATS: {error}");
+                    time::sleep(RETRY).await;
+                }
+                None => return Err(nats("connection closed")),
+            }
+        };
+        let (message, acker) = message.split();
+
+        Ok(Delivery {
+            payload: message.payload.to_vec(),
+            receipt: acker,
+        })
+    }
+
+    /// Acknowledge each of `receipts`, as the consumer acknowledges messages
+    /// one by one.
+    async fn acknowledge(&mut self, receipts: Vec<Acker>) -> Result<()> {
+        for acker in receipts {
+            acker.ack().await.map_err(Error::NatsError)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Wrap any of the errors of the NATS client, which has one per operation.
+fn nats(error: impl Into<async_nats::Error>) -> Error {
+    Error::NatsError(error.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address() {
+        assert_eq!(
+            "nats://a:4222,nats://b:4222/transactions.>/txns".parse(),
+            Ok(Address {
+                servers: "nats://a:4222,nats://b:4222".to_owned(),
+                subject: "transactions.>".to_owned(),
+                durable: "txns".to_owned(),
+            })
+        );
+        assert!("localhost:4222/transactions".parse::<Address>().is_err());
+        assert!("localhost:4222//txns".parse::<Address>().is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! Redis streams read as part of a consumer group with `XREADGROUP`,
+//! acknowledging entries with `XACK` once applied.
+
+use std::{collections::VecDeque, str::FromStr};
+
+use redis::{
+    aio::MultiplexedConnection,
+    streams::{StreamId, StreamReadOptions, StreamReadReply},
+    AsyncCommands, Client, RedisResult, Value,
+};
+
+use super::{Delivery, Source};
+use crate::error::Result;
+
+/// Field of each entry holding its JSON transaction.
+pub const FIELD: &str = "transaction";
+
+/// How many entries to read at once.
+const COUNT: usize = 100;
+
+/// How long to block waiting for entries before asking again, in
+/// milliseconds.
+const BLOCK: usize = 5_000;
+
+/// Server, stream, consumer group and consumer to read, written
+/// `url/stream/group/consumer`, like
+/// `redis://localhost:6379/transactions/txns/txns-1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub url: String,
+    pub stream: String,
+    pub group: String,
+    /// Name of this consumer in the group, which must stay the same across
+    /// restarts for the entries it didn't acknowledge to be read again.
+    pub consumer: String,
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(address: &str) -> std::result::Result<Self, String> {
+        match address.rsplitn(4, '/').collect::<Vec<_>>()[..] {
+            [consumer, group, stream, url]
+                if [url, stream, group, consumer].iter().all(|s| !s.is_empty()) =>
+            {
+                Ok(Self {
+                    url: url.to_owned(),
+                    stream: stream.to_owned(),
+                    group: group.to_owned(),
+                    consumer: consumer.to_owned(),
+                })
+            }
+            _ => Err("expected `url/stream/group/consumer`".to_owned()),
+        }
+    }
+}
+
+/// Consumer of the stream of an [`Address`], creating the stream and its
+/// group, reading from the first entry, unless they exist.
+///
+/// Entries delivered to the consumer but never acknowledged, like those read
+/// before a crash, are read again first.
+pub struct Redis {
+    connection: MultiplexedConnection,
+    address: Address,
+    /// Entries read but not received yet.
+    entries: VecDeque<StreamId>,
+    /// ID after which to read the entries pending for the consumer, until
+    /// all were read again, after which only new ones are.
+    pending: Option<String>,
+}
+
+impl Redis {
+    pub async fn connect(address: &Address) -> Result<Self> {
+        let mut connection = Client::open(address.url.as_str())?
+            .get_multiplexed_async_connection()
+            .await?;
+        let created: RedisResult<()> = connection
+            .xgroup_create_mkstream(&address.stream, &address.group, "0")
+            .await;
+        if let Err(error) = created {
+            if error.code() != Some("BUSYGROUP") {
+                return Err(error.into());
+            }
+        }
+
+        Ok(Self {
+            connection,
+            address: address.clone(),
+            entries: VecDeque::new(),
+            pending: Some("0".to_owned()),
+        })
+    }
+
+    /// Read the next entries, blocking until there are some unless reading
+    /// those pending for the consumer.
+    async fn read(&mut self) -> Result<()> {
+        let options = StreamReadOptions::default()
+            .group(&self.address.group, &self.address.consumer)
+            .count(COUNT);
+        let (id, options) = match &self.pending {
+            Some(id) => (id.as_str(), options),
+            None => (">", options.block(BLOCK)),
+        };
+
+        let reply: Option<StreamReadReply> = self
+            .connection
+            .xread_options(&[&self.address.stream], &[id], &options)
+            .await?;
+        self.entries = reply
+            .into_iter()
+            .flat_map(|reply| reply.keys)
+            .flat_map(|key| key.ids)
+            .collect();
+        if self.pending.is_some() {
+            self.pending = self.entries.back().map(|entry| entry.id.clone());
+        }
+
+        Ok(())
+    }
+}
+
+impl Source for Redis {
+    /// ID of the entry.
+    type Receipt = String;
+
+    async fn receive(&mut self) -> Result<Delivery<String>> {
+        loop {
+            if let Some(entry) = self.entries.pop_front() {
+                let payload = match entry.map.get(FIELD) {
+                    Some(Value::BulkString(payload)) => payload.clone(),
+                    _ => Vec::new(),
+                };
+
+                return Ok(Delivery {
+                    payload,
+                    receipt: entry.id,
+                });
+            }
+            self.read().await?;
+        }
+    }
+
+    async fn acknowledge(&mut self, receipts: Vec<String>) -> Result<()> {
+        if receipts.is_empty() {
+            return Ok(());
+        }
+
+        Ok(self
+            .connection
+            .xack(&self.address.stream, &self.address.group, &receipts)
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address() {
+        assert_eq!(
+            "redis://localhost:6379/2/transactions/txns/txns-1".parse(),
+            Ok(Address {
+                url: "redis://localhost:6379/2".to_owned(),
+                stream: "transactions".to_owned(),
+                group: "txns".to_owned(),
+                consumer: "txns-1".to_owned(),
+            })
+        );
+        assert!("transactions/txns/txns-1".parse::<Address>().is_err());
+        assert!("redis://localhost:6379//txns/txns-1"
+            .parse::<Address>()
+            .is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Spreadsheet input, available with the `xlsx` feature.
+
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+};
+
+use calamine::{open_workbook_auto_from_rs, Data, Reader};
+use csv::{Position, StringRecord};
+
+use crate::{domain::transaction::Transaction, error::Result};
+
+/// Parse [`Transaction`]s from the first sheet of a workbook, whose first row
+/// has the same headers as CSV input, matched the same way.
+///
+/// Besides XLSX, older XLS and OpenDocument spreadsheets work as well. The
+/// whole workbook is loaded in memory, so this fails right away if it can't
+/// be read. Otherwise, each item is an error if the row is not a valid
+/// transaction, pointing to the row and column at fault like with CSV. Date
+/// cells are read as timestamps.
+pub fn read(mut reader: impl Read) -> Result<impl Iterator<Item = Result<Transaction>>> {
+    let mut workbook = Vec::new();
+    reader.read_to_end(&mut workbook)?;
+
+    let mut workbook = open_workbook_auto_from_rs(Cursor::new(workbook))?;
+    let range = match workbook.worksheet_range_at(0) {
+        Some(range) => range?,
+        None => Default::default(),
+    };
+
+    let mut rows = range.rows().enumerate().map(|(index, row)| {
+        let mut record: StringRecord = row.iter().map(cell).collect();
+        let mut position = Position::new();
+        position.set_line(range.start().map_or(0, |(row, _)| row.into()) + index as u64 + 1);
+        record.set_position(Some(position));
+        record
+    });
+    let headers = crate::csv::normalize(&rows.next().unwrap_or_default(), &HashMap::new());
+    let headers = crate::csv::Headers::new(&headers);
+    let rows: Vec<_> = rows.collect();
+
+    Ok(rows
+        .into_iter()
+        .map(move |row| crate::csv::parse(row.as_byte_record(), &headers, false)))
+}
+
+/// Text of a cell, as it would be in a CSV export.
+fn cell(data: &Data) -> String {
+    match data {
+        Data::DateTime(datetime) => match datetime.as_datetime() {
+            Some(datetime) => datetime.and_utc().to_rfc3339(),
+            None => data.to_string(),
+        },
+        _ => data.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn cells() {
+        assert_eq!(cell(&Data::Float(1.0)), "1");
+        assert_eq!(cell(&Data::Float(1.5)), "1.5");
+        assert_eq!(cell(&Data::String("deposit".into())), "deposit");
+        assert_eq!(cell(&Data::Empty), "");
+        assert_eq!(
+            cell(&Data::DateTime(ExcelDateTime::new(
+                45322.5,
+                ExcelDateTimeType::DateTime,
+                false
+            ))),
+            "2024-01-31T12:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn not_a_workbook() {
+        assert!(matches!(
+            read("type,client,tx,amount\n".as_bytes()),
+            Err(Error::XlsxError(_))
+        ));
+    }
+}